@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use avian2d::prelude::{LinearVelocity, Rotation};
+use bevy::{prelude::*, utils::EntityHashMap};
+use bevy_kira_audio::{
+    prelude::{
+        AudioChannel, AudioInstance, AudioTween, DefaultSpatialRadius, PlaybackState,
+        SpatialAudioEmitter, SpatialAudioPlugin, SpatialAudioReceiver,
+    },
+    AudioControl,
+};
+
+use camera::GameCamera;
+use car::{AccelerateAction, Car, CarParts, DrivingSystems, Wheel};
+
+use crate::{AudioFxAssets, AudioFxAssetsState, AudioFxSystems, EngineFxChannel};
+
+pub struct EngineFxPlugin;
+
+impl Plugin for EngineFxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(SpatialAudioPlugin)
+            .insert_resource(DefaultSpatialRadius {
+                radius: Self::HEARING_RADIUS,
+            })
+            .init_resource::<CarSounds>()
+            .add_systems(Startup, (Self::spawn_listener, Self::quiet_engines))
+            // throttle is read from the `AccelerateAction`s that are cleared during driving
+            .configure_sets(Update, AudioFxSystems::EngineFx.before(DrivingSystems))
+            .add_systems(
+                Update,
+                (
+                    Self::follow_camera,
+                    Self::stop_car_sounds,
+                    Self::start_car_sounds,
+                    Self::update_car_sounds,
+                )
+                    .chain()
+                    .in_set(AudioFxSystems::EngineFx)
+                    .run_if(in_state(AudioFxAssetsState::Loaded)),
+            );
+    }
+}
+
+impl EngineFxPlugin {
+    /// Distance from the camera past which cars can no longer be heard.
+    const HEARING_RADIUS: f32 = 1600.;
+    /// Wheel speed at which the engine reaches its highest pitch.
+    const TOP_WHEEL_SPEED: f32 = 900.;
+    /// Sideways wheel speed at which tires start to squeal.
+    const SQUEAL_THRESHOLD: f32 = 120.;
+
+    // the 2d camera sits far above the track on the Z axis, which would make every car sound
+    // equally distant, so cars are heard from a listener that follows it on the track's plane
+    fn spawn_listener(mut commands: Commands) {
+        commands.spawn((
+            SpatialAudioReceiver,
+            SpatialBundle::default(),
+            Name::new("Audio Listener"),
+        ));
+    }
+
+    // spatial audio plays a car right next to the listener at full volume, which drowns out
+    // everything else once a dozen engines are running
+    fn quiet_engines(channel: Res<AudioChannel<EngineFxChannel>>) {
+        channel.set_volume(0.35);
+    }
+
+    fn follow_camera(
+        camera: Query<&GlobalTransform, With<GameCamera>>,
+        mut listeners: Query<&mut Transform, With<SpatialAudioReceiver>>,
+    ) {
+        let Ok(camera_transform) = camera.get_single() else {
+            return;
+        };
+        for mut transform in &mut listeners {
+            transform.translation = camera_transform.translation().xy().extend(0.);
+        }
+    }
+
+    fn start_car_sounds(
+        mut commands: Commands,
+        channel: Res<AudioChannel<EngineFxChannel>>,
+        assets: Res<AudioFxAssets>,
+        new_cars: Query<Entity, Added<Car>>,
+        mut sounds: ResMut<CarSounds>,
+    ) {
+        for car in &new_cars {
+            let engine = channel.play(assets.engine_loop.clone()).looped().handle();
+            let squeal = channel
+                .play(assets.tire_squeal.clone())
+                .looped()
+                .paused()
+                .handle();
+            // spatial audio sets how loud both are from how far the car is from the listener
+            commands.entity(car).insert(SpatialAudioEmitter {
+                instances: vec![engine.clone(), squeal.clone()],
+            });
+            sounds.0.insert(car, CarSoundInstances { engine, squeal });
+        }
+    }
+
+    fn stop_car_sounds(
+        mut removed_cars: RemovedComponents<Car>,
+        mut sounds: ResMut<CarSounds>,
+        mut instances: ResMut<Assets<AudioInstance>>,
+    ) {
+        for car in removed_cars.read() {
+            let Some(car_sounds) = sounds.0.remove(&car) else {
+                continue;
+            };
+            for handle in [car_sounds.engine, car_sounds.squeal] {
+                if let Some(instance) = instances.get_mut(&handle) {
+                    instance.stop(AudioTween::default());
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn update_car_sounds(
+        cars: Query<(Entity, &CarParts, Option<&AccelerateAction>), With<Car>>,
+        wheels: Query<(&LinearVelocity, &Rotation), With<Wheel>>,
+        sounds: Res<CarSounds>,
+        mut instances: ResMut<Assets<AudioInstance>>,
+    ) {
+        let tween = AudioTween::linear(Duration::from_millis(50));
+
+        for (car, parts, acceleration) in &cars {
+            let Some(car_sounds) = sounds.0.get(&car) else {
+                continue;
+            };
+
+            let (wheel_speed, slide_speed) = wheels.iter_many(parts.wheels()).fold(
+                (0_f32, 0_f32),
+                |(speed, slide), (velocity, rotation)| {
+                    (
+                        speed + velocity.length() / 4.,
+                        slide.max(Wheel::cross_axis_velocity(**velocity, rotation).length()),
+                    )
+                },
+            );
+            let throttle = if acceleration.is_some() { 1. } else { 0. };
+
+            if let Some(engine) = instances.get_mut(&car_sounds.engine) {
+                let revs = (wheel_speed / Self::TOP_WHEEL_SPEED).clamp(0., 1.);
+                let pitch = 0.7 + revs * 1.3 + throttle * 0.15;
+                engine.set_playback_rate(pitch as f64, tween.clone());
+            }
+
+            // volume belongs to spatial audio, so the squeal is only ever on or off
+            if let Some(squeal) = instances.get_mut(&car_sounds.squeal) {
+                let paused = matches!(
+                    squeal.state(),
+                    PlaybackState::Paused { .. } | PlaybackState::Pausing { .. }
+                );
+                let squealing = slide_speed > Self::SQUEAL_THRESHOLD;
+                if squealing && paused {
+                    squeal.resume(tween.clone());
+                } else if !squealing && !paused {
+                    squeal.pause(tween.clone());
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct CarSoundInstances {
+    engine: Handle<AudioInstance>,
+    squeal: Handle<AudioInstance>,
+}
+
+/// Looping sound instances for each car, kept outside the car entity so they can be stopped
+/// after the car is despawned.
+#[derive(Debug, Default)]
+#[derive(Resource)]
+struct CarSounds(EntityHashMap<Entity, CarSoundInstances>);
@@ -8,6 +8,9 @@ use bevy_kira_audio::{
     AudioApp, AudioControl,
 };
 
+mod engine;
+pub use engine::*;
+
 pub struct AudioFxPlugin;
 
 impl Plugin for AudioFxPlugin {
@@ -20,11 +23,16 @@ impl Plugin for AudioFxPlugin {
             )
             .add_audio_channel::<CrashFxChannel>()
             .add_audio_channel::<ScoreFxChannel>()
+            .add_audio_channel::<EngineFxChannel>()
+            .add_audio_channel::<FuseFxChannel>()
+            .add_plugins(EngineFxPlugin)
             .add_systems(
                 Update,
                 (
                     Self::play_crash_fx.in_set(AudioFxSystems::CrashFx),
                     Self::play_lap_fx.in_set(AudioFxSystems::ScoreFx),
+                    Self::play_fuse_fx.in_set(AudioFxSystems::FuseFx),
+                    Self::play_explosion_fx.in_set(AudioFxSystems::ExplosionFx),
                 )
                     .run_if(in_state(AudioFxAssetsState::Loaded)),
             );
@@ -39,6 +47,23 @@ impl AudioFxPlugin {
     fn play_lap_fx(channel: Res<AudioChannel<ScoreFxChannel>>, music_assets: Res<AudioFxAssets>) {
         channel.play(music_assets.score_fx.clone()).with_volume(0.5);
     }
+
+    fn play_fuse_fx(channel: Res<AudioChannel<FuseFxChannel>>, music_assets: Res<AudioFxAssets>) {
+        channel
+            .play(music_assets.fuse_tick.clone())
+            .with_volume(0.4);
+    }
+
+    fn play_explosion_fx(
+        channel: Res<AudioChannel<CrashFxChannel>>,
+        music_assets: Res<AudioFxAssets>,
+    ) {
+        // a slowed-down crash makes for a deeper boom
+        channel
+            .play(music_assets.crash_fx.clone())
+            .with_playback_rate(0.6)
+            .with_volume(1.);
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -46,6 +71,9 @@ impl AudioFxPlugin {
 pub enum AudioFxSystems {
     CrashFx,
     ScoreFx,
+    EngineFx,
+    FuseFx,
+    ExplosionFx,
 }
 
 #[derive(Clone)]
@@ -56,6 +84,14 @@ pub struct CrashFxChannel;
 #[derive(Resource)]
 pub struct ScoreFxChannel;
 
+#[derive(Clone)]
+#[derive(Resource)]
+pub struct EngineFxChannel;
+
+#[derive(Clone)]
+#[derive(Resource)]
+pub struct FuseFxChannel;
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 #[derive(States)]
 pub enum AudioFxAssetsState {
@@ -72,4 +108,10 @@ pub struct AudioFxAssets {
     #[cfg_attr(not(target_arch = "wasm32"), asset(path = "audio/score-fx.wav"))]
     #[cfg_attr(target_arch = "wasm32", asset(path = "audio/score-fx.mp3"))]
     pub score_fx: Handle<AudioSource>,
+    #[asset(path = "audio/engine-loop.wav")]
+    pub engine_loop: Handle<AudioSource>,
+    #[asset(path = "audio/tire-squeal.wav")]
+    pub tire_squeal: Handle<AudioSource>,
+    #[asset(path = "audio/fuse-tick.wav")]
+    pub fuse_tick: Handle<AudioSource>,
 }
@@ -1,22 +1,44 @@
-use avian2d::prelude::{
-    LayerMask, LinearVelocity, Rotation, ShapeCaster, ShapeHits, SpatialQueryFilter,
+use std::time::Duration;
+
+use avian2d::prelude::{LinearVelocity, Rotation};
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
 };
-use bevy::prelude::*;
 
-use car::{AccelerateAction, CarPhysicsBundle, DrivingSystems, SteerAction};
+use car::{AccelerateAction, Boost, BoostAction, Car, DrivingSystems, SteerAction};
 use entropy::{Entropy, ForkableRng, GlobalEntropy, RngCore};
 use laptag::{BombTagIt, CanBeIt, LapTagIt};
-use resurfacer::Peg;
-use track::{Checkpoint, CheckpointTracker, Track, Wall};
+use resurfacer::{Peg, Resurfacer};
+use track::{Checkpoint, CheckpointTracker, Track};
+
+mod planner;
+use planner::{Hazard, LocalPlanner};
+mod profile;
+pub use profile::{BotPersonality, BotProfile, BotSkill};
+mod racing_line;
+pub use racing_line::RacingLine;
+
+pub struct BotControllerPlugin {
+    /// The schedule bots make their decisions in, which should be the one cars are driven in.
+    pub schedule: InternedScheduleLabel,
+}
 
-pub struct BotControllerPlugin;
+impl Default for BotControllerPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
 
 impl Plugin for BotControllerPlugin {
     fn build(&self, app: &mut App) {
-        app.configure_sets(Update, BotControllerSystems.before(DrivingSystems))
+        app.configure_sets(self.schedule, BotControllerSystems.before(DrivingSystems))
             .add_systems(
-                Update,
+                self.schedule,
                 (
+                    Self::plan_racing_lines,
                     Self::compute_goals,
                     Self::decide_bot_controls,
                     #[cfg(feature = "gizmos")]
@@ -27,11 +49,36 @@ impl Plugin for BotControllerPlugin {
             );
         app.register_type::<BotController>()
             .register_type::<Goal>()
-            .register_type::<BotGoals>();
+            .register_type::<BotGoals>()
+            .register_type::<BotReaction>()
+            .register_type::<BotProgress>()
+            .register_type::<BotProfile>()
+            .register_type::<BotSkill>()
+            .register_type::<BotPersonality>()
+            .register_type::<RacingLine>();
     }
 }
 
 impl BotControllerPlugin {
+    /// Bots hold on to this much boost for when they need it.
+    const BOOST_RESERVE: f32 = 0.2;
+    /// How far ahead on the racing line bots aim when standing still.
+    const MIN_PURSUIT_DISTANCE: f32 = 150.;
+    /// How much further ahead bots aim for every unit of speed.
+    const PURSUIT_DISTANCE_PER_SPEED: f32 = 0.35;
+    /// How far ahead bots make sure the road is fast enough to boost on.
+    const BOOST_LOOKAHEAD: f32 = 1000.;
+    /// How far bots look for tag holders to go after.
+    const CHASE_RANGE: f32 = 800.;
+
+    fn plan_racing_lines(mut commands: Commands, tracks: Query<(Entity, &Track), Changed<Track>>) {
+        for (entity, track) in &tracks {
+            commands
+                .entity(entity)
+                .insert(RacingLine::from_track(track));
+        }
+    }
+
     #[allow(clippy::type_complexity)]
     fn compute_goals(
         mut bots: Query<
@@ -39,37 +86,44 @@ impl BotControllerPlugin {
                 Entity,
                 &LinearVelocity,
                 &mut BotGoals,
-                &ShapeHits,
+                &mut BotProgress,
+                &BotProfile,
                 Option<&CheckpointTracker>,
             ),
             With<BotController>,
         >,
-        track: Query<&Track>,
+        track: Query<(&Track, &RacingLine)>,
         players: Query<(Entity, &Transform, Option<&LapTagIt>, Option<&BombTagIt>), With<CanBeIt>>,
-        obstacles: Query<&Transform, Or<(With<Wall>, With<Peg>)>>,
         _checkpoints: Query<(Entity, &Checkpoint)>,
     ) {
-        let Ok(track) = track.get_single() else {
+        let Ok((track, racing_line)) = track.get_single() else {
             return;
         };
-        for (bot, bot_velocity, mut goals, shape_hits, _tracker) in &mut bots {
+        for (bot, bot_velocity, mut goals, mut progress, profile, _tracker) in &mut bots {
             let Ok((_, bot_transform, bot_lap_tag, bot_bomb_tag)) = players.get(bot) else {
                 continue;
             };
 
             let bot_transform = *bot_transform;
             let bot_position = bot_transform.translation.xy();
-            let _bot_has_lap_tag = bot_lap_tag.is_some();
-            let _bot_has_bomb_tag = bot_bomb_tag.is_some();
+            let bot_has_lap_tag = bot_lap_tag.is_some();
+            let bot_has_bomb_tag = bot_bomb_tag.is_some();
 
             // establish some starting priorities for the bot
             let mut new_goals = BotGoals::default();
             new_goals
                 .0
                 .push(Goal::max_speed(bot_transform, **bot_velocity));
-            new_goals
-                .0
-                .push(Goal::follow_track(bot_position, **bot_velocity, track));
+            progress.line_index = racing_line.nearest(bot_position, progress.line_index);
+            if let Some(index) = progress.line_index {
+                new_goals.0.push(Goal::follow_line(
+                    bot_position,
+                    **bot_velocity,
+                    track,
+                    racing_line,
+                    index,
+                ));
+            }
             // if bot_has_lap_tag && !bot_has_bomb_tag {
             //     if let Some(tracker) = tracker {
             //         new_goals.0.push(Goal::reach_checkpoints(
@@ -81,30 +135,27 @@ impl BotControllerPlugin {
             //     }
             // }
 
-            if bot_position.x.abs() < (track.half_length() - track.radius())
-                && bot_position.y.abs() < track.interior_radius()
-            {
-                let max_length_squared = Vec2::new(
-                    track.half_length() - track.radius(),
-                    track.interior_radius(),
-                )
-                .length();
-                new_goals.0.push(Goal::AvoidGrass(
-                    bot_position.length_squared() / max_length_squared,
-                ));
-            }
-
-            // now shapecast to check if we are trapped against something
-            if let Some(hit) = shape_hits.iter().next() {
-                if let Ok(transform) = players
-                    .get(hit.entity)
-                    .map(|(_, transform, _, _)| transform)
-                    .or(obstacles.get(hit.entity))
-                {
-                    new_goals
-                        .0
-                        .push(Goal::avoid(transform.translation.xy(), hit.time_of_impact));
-                }
+            // go after the flag, or after someone to hand the bomb to
+            let chase_target = players
+                .iter()
+                .filter(|(car, transform, lap_tag, _)| {
+                    let delta = transform.translation.xy() - bot_position;
+                    *car != bot
+                        && delta.length() < Self::CHASE_RANGE
+                        && delta.dot(**bot_velocity) > 0.
+                        && (bot_has_bomb_tag || (!bot_has_lap_tag && lap_tag.is_some()))
+                })
+                .map(|(car, transform, _, _)| (car, transform.translation.xy()))
+                .min_by(|(_, a), (_, b)| {
+                    a.distance_squared(bot_position)
+                        .total_cmp(&b.distance_squared(bot_position))
+                });
+            if let Some((car, target)) = chase_target.filter(|_| profile.aggression > 0.) {
+                new_goals.0.push(Goal::Chase {
+                    car,
+                    target,
+                    eagerness: profile.aggression,
+                });
             }
 
             *goals = new_goals;
@@ -122,21 +173,66 @@ impl BotControllerPlugin {
                 &Rotation,
                 &LinearVelocity,
                 &mut Entropy,
+                &mut BotReaction,
+                &BotProgress,
+                &BotProfile,
+                Option<&Boost>,
             ),
             With<BotController>,
         >,
+        racing_line: Query<(Entity, &RacingLine)>,
+        cars: Query<(Entity, &Transform, &LinearVelocity), With<Car>>,
+        pegs: Query<(Entity, &Transform), With<Peg>>,
+        resurfacers: Query<(Entity, &Transform, &LinearVelocity), With<Resurfacer>>,
+        time: Res<Time>,
     ) {
-        use std::f32::consts::{FRAC_PI_2, PI};
-        for (car, goals, transform, rotation, velocity, mut entropy) in &mut bots {
-            let bot_position = transform.translation.xy();
-            let ideal_position = weighted_avg(
-                goals
-                    .0
+        use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2, PI};
+        let racing_line = racing_line.get_single().ok();
+        let hazards = cars
+            .iter()
+            .map(|(entity, transform, velocity)| Hazard {
+                entity,
+                position: transform.translation.xy(),
+                velocity: **velocity,
+                radius: Car::LENGTH / 2.,
+            })
+            .chain(pegs.iter().map(|(entity, transform)| Hazard {
+                entity,
+                position: transform.translation.xy(),
+                velocity: Vec2::ZERO,
+                radius: Peg::RADIUS,
+            }))
+            // out to the corners of its square
+            .chain(
+                resurfacers
                     .iter()
-                    .map(|goal| goal.to_influence(bot_position, **velocity)),
-            );
+                    .map(|(entity, transform, velocity)| Hazard {
+                        entity,
+                        position: transform.translation.xy(),
+                        velocity: **velocity,
+                        radius: Resurfacer::WIDTH * FRAC_1_SQRT_2,
+                    }),
+            )
+            .collect::<Vec<_>>();
+        for (
+            car,
+            goals,
+            transform,
+            rotation,
+            velocity,
+            mut entropy,
+            mut reaction,
+            progress,
+            profile,
+            boost,
+        ) in &mut bots
+        {
+            let bot_position = transform.translation.xy();
+            let ideal_position = weighted_avg(goals.0.iter().map(|goal| goal.to_influence()));
             let ideal_rotation = (ideal_position - bot_position).to_angle();
-            let aggression = entropy.next_u32() as f32 / u32::MAX as f32;
+            // imprecise bots never quite turn the wheel as far as they mean to
+            let wobble =
+                (1. - profile.steering_precision) * (entropy.next_u32() as f32 / u32::MAX as f32);
             let delta_rotation = modulo_radian(ideal_rotation - rotation.as_radians());
 
             let is_marginal_rotation = delta_rotation.abs() < PI / 64.;
@@ -150,14 +246,65 @@ impl BotControllerPlugin {
                 0.
             };
 
+            let speed = velocity.length();
+            // the slowest the line gets within braking distance, and whether it stays fast enough
+            // to be worth boosting
+            let road = racing_line
+                .zip(progress.line_index)
+                .map(|((entity, line), index)| (entity, line, index));
+            let (target_speed, is_clear_ahead) = road
+                .map(|(_, line, index)| {
+                    let target_speed = line.slowest_ahead(index, profile.braking_point);
+                    let clear_speed = line.slowest_ahead(index, Self::BOOST_LOOKAHEAD);
+                    (target_speed, clear_speed > speed * Boost::MULTIPLIER)
+                })
+                .unwrap_or((f32::INFINITY, false));
+
             let is_forward = delta_rotation.abs() > FRAC_PI_2;
             if is_forward {
                 commands.entity(car).insert(AccelerateAction::Backward);
                 steer_signum = -steer_signum;
+            } else if speed > target_speed {
+                commands.entity(car).insert(AccelerateAction::Backward);
             } else {
                 commands.entity(car).insert(AccelerateAction::Forward);
+                // save boost for the straights, and only once pointed where we want to go
+                let has_charge = boost.is_some_and(|boost| boost.charge() > Self::BOOST_RESERVE);
+                if is_clear_ahead && has_charge && delta_rotation.abs() < PI / 16. {
+                    commands.entity(car).insert(BoostAction);
+                }
             }
-            let steering = steer_signum * aggression * delta_rotation;
+            // pure pursuit: the steering angle that arcs the car through the point it aims for
+            let pursuit_distance = bot_position.distance(ideal_position).max(f32::EPSILON);
+            let steering_angle =
+                (2. * Car::WHEELBASE * delta_rotation.abs().sin() / pursuit_distance).atan();
+            let mut steering =
+                steer_signum * (steering_angle / Car::MAX_STEERING_DEG.to_radians()).min(1.);
+
+            // look for a way around whatever that would run into once the bot has had time to
+            // notice it, unless it's the car being chased
+            if !is_forward {
+                let chased = goals.0.iter().find_map(|goal| match goal {
+                    Goal::Chase { car, .. } => Some(*car),
+                    _ => None,
+                });
+                let planner = LocalPlanner::new(
+                    bot_position,
+                    rotation.as_radians(),
+                    speed,
+                    profile.clearance(),
+                    road,
+                    hazards
+                        .iter()
+                        .filter(|hazard| hazard.entity != car && Some(hazard.entity) != chased)
+                        .copied(),
+                );
+                reaction.observe(planner.hazard_ahead(steering), time.delta());
+                if reaction.seen_for >= profile.reaction_delay && reaction.hazard.is_some() {
+                    steering = planner.plan(ideal_position, steering);
+                }
+            }
+            let steering = steering * (1. - wobble);
             commands.entity(car).insert(SteerAction(steering));
         }
     }
@@ -165,10 +312,15 @@ impl BotControllerPlugin {
     #[cfg(feature = "gizmos")]
     fn render_bot_gizmos(
         mut gizmos: bevy::prelude::Gizmos,
-        bots: Query<(&Transform, &LinearVelocity, &Rotation, &BotGoals)>,
+        bots: Query<(&Transform, &Rotation, &BotGoals)>,
+        racing_lines: Query<&RacingLine>,
     ) {
         use bevy::color::palettes::css::*;
-        for (transform, velocity, rotation, goals) in &bots {
+        for racing_line in &racing_lines {
+            let points = racing_line.points();
+            gizmos.linestrip_2d(points.iter().chain(points.first()).copied(), YELLOW);
+        }
+        for (transform, rotation, goals) in &bots {
             let bot_position = transform.translation.xy();
             gizmos.arrow_2d(
                 bot_position,
@@ -178,10 +330,10 @@ impl BotControllerPlugin {
             for goal in &goals.0 {
                 gizmos.arrow_2d(
                     bot_position,
-                    goal.to_influence(bot_position, **velocity).target,
+                    goal.to_influence().target,
                     match goal {
-                        Goal::FollowTrack(_) => PURPLE,
-                        Goal::AvoidGrass(_) => BLUE,
+                        Goal::FollowLine { .. } => PURPLE,
+                        Goal::Chase { .. } => RED,
                         _ => PINK,
                     },
                 );
@@ -198,26 +350,50 @@ pub struct BotControllerSystems;
 #[derive(Component, Reflect)]
 struct BotController;
 
+/// The point on the racing line a bot was nearest to last, which it looks around first.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct BotProgress {
+    line_index: Option<usize>,
+}
+
+/// What a bot has seen in its way, and for how long.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct BotReaction {
+    hazard: Option<Entity>,
+    seen_for: Duration,
+}
+
+impl BotReaction {
+    fn observe(&mut self, hazard: Option<Entity>, delta: Duration) {
+        if hazard.is_some() && hazard == self.hazard {
+            self.seen_for += delta;
+        } else {
+            self.hazard = hazard;
+            self.seen_for = Duration::ZERO;
+        }
+    }
+}
+
 #[derive(Bundle)]
 pub struct BotControllerBundle {
     controller: BotController,
+    profile: BotProfile,
+    reaction: BotReaction,
+    progress: BotProgress,
     entropy: Entropy,
-    shapecast: ShapeCaster,
     goals: BotGoals,
 }
 
 impl BotControllerBundle {
-    pub fn new(entropy: &mut GlobalEntropy) -> Self {
+    pub fn new(entropy: &mut GlobalEntropy, profile: BotProfile) -> Self {
         Self {
             controller: BotController,
+            profile,
+            reaction: BotReaction::default(),
+            progress: BotProgress::default(),
             entropy: entropy.fork_rng(),
-            shapecast: ShapeCaster::new(CarPhysicsBundle::collider(), Vec2::ZERO, 0., Dir2::X)
-                .with_max_hits(2)
-                .with_max_time_of_impact(250.)
-                .with_ignore_origin_penetration(true)
-                .with_query_filter(SpatialQueryFilter::from_mask(
-                    LayerMask::ALL & (!Checkpoint::COLLISION_LAYER),
-                )),
             goals: BotGoals::default(),
         }
     }
@@ -231,9 +407,15 @@ struct BotGoals(Vec<Goal>);
 #[derive(Reflect)]
 enum Goal {
     MaxSpeed(Vec2),
-    FollowTrack(Vec2),
-    AvoidGrass(f32),
-    Avoid { target: Vec2, time_of_impact: f32 },
+    FollowLine {
+        target: Vec2,
+        urgency: f32,
+    },
+    Chase {
+        car: Entity,
+        target: Vec2,
+        eagerness: f32,
+    },
     // ReachCheckpoints(Vec<Vec2>),
 }
 
@@ -248,68 +430,21 @@ impl Goal {
         Goal::MaxSpeed(bot_transform.translation.xy() + facing_direction * 100.)
     }
 
-    fn follow_track(bot_position: Vec2, bot_velocity: Vec2, track: &Track) -> Self {
-        let straightaway_max = Vec2::new(
-            (track.half_length() - track.radius()) * 0.8,
-            track.radius() * 1.1,
-        );
-        let straightaway_min = Vec2::new(
-            (track.half_length() - track.radius()) * 0.8,
-            track.interior_radius(),
-        );
-        let top_straightaway_bounds =
-            Rect::from_corners(straightaway_min * Vec2::new(-1., 1.), straightaway_max);
-        let bottom_straightaway_bounds =
-            Rect::from_corners(-straightaway_max, straightaway_min * Vec2::new(1., -1.));
-        let interior_max = Vec2::new(
-            track.half_length() + track.radius(),
-            track.interior_radius(),
-        );
-        // it's ok to treat the rect corners as part of the interior
-        let interior_bounds = Rect::from_corners(-interior_max, interior_max);
-
-        fn less(vec1: Vec2, vec2: Vec2) -> bool {
-            vec1.x < vec2.x && vec1.y < vec2.y
-        }
-
-        let desired_direction =
-            if less(interior_bounds.min, bot_position) && less(bot_position, interior_bounds.max) {
-                // if you're in the middle, just keep moving
-                bot_velocity.normalize()
-            } else if less(top_straightaway_bounds.min, bot_position)
-                && less(bot_position, top_straightaway_bounds.max)
-            {
-                // top goes left
-                Vec2::NEG_X
-            } else if less(bottom_straightaway_bounds.min, bot_position)
-                && less(bot_position, bottom_straightaway_bounds.max)
-            {
-                // bottom goes right
-                Vec2::X
-            } else if bot_position.x.is_sign_positive() && bot_position.y.is_sign_positive() {
-                // top right goes left
-                Vec2::NEG_X
-            } else if bot_position.x.is_sign_positive() && bot_position.y.is_sign_negative() {
-                // bottom right goes up
-                Vec2::Y
-            } else if bot_position.x.is_sign_negative() && bot_position.y.is_sign_negative() {
-                // bottom left goes right
-                Vec2::X
-            } else if bot_position.x.is_sign_negative() && bot_position.y.is_sign_positive() {
-                // top left goes down
-                Vec2::NEG_Y
-            } else {
-                // everyone else goes left
-                Vec2::NEG_X
-            };
-
-        Goal::FollowTrack(bot_position + desired_direction * 100.)
-    }
-
-    fn avoid(target: Vec2, time_of_impact: f32) -> Self {
-        Goal::Avoid {
-            target,
-            time_of_impact,
+    // aim a little way along the racing line, further the faster we go, and harder the further
+    // we have strayed from it
+    fn follow_line(
+        bot_position: Vec2,
+        bot_velocity: Vec2,
+        track: &Track,
+        racing_line: &RacingLine,
+        index: usize,
+    ) -> Self {
+        let pursuit_distance = BotControllerPlugin::MIN_PURSUIT_DISTANCE
+            + BotControllerPlugin::PURSUIT_DISTANCE_PER_SPEED * bot_velocity.length();
+        let stray = bot_position.distance(racing_line.points()[index]);
+        Goal::FollowLine {
+            target: racing_line.ahead(index, pursuit_distance),
+            urgency: 1. + stray / (track.thickness() / 2.),
         }
     }
 
@@ -346,50 +481,37 @@ impl Goal {
     //     Goal::ReachCheckpoints(checkpoints)
     // }
 
-    fn to_influence(&self, bot_position: Vec2, bot_velocity: Vec2) -> Influence {
+    fn to_influence(&self) -> Influence {
         match self {
             Goal::MaxSpeed(target) => Influence::new(*target, 1.),
-            Goal::FollowTrack(target) => Influence::new(*target, 8.),
-            Goal::AvoidGrass(strength) => Influence::new(
-                bot_position + (bot_position.normalize() + bot_velocity.normalize()) * 50.,
-                strength * 5.,
-            ),
-            Goal::Avoid {
-                target,
-                time_of_impact,
-            } => {
-                if *time_of_impact < 50. {
-                    Influence::new(bot_position - (*target - bot_position), 100.)
-                } else {
-                    let delta = *target - bot_position;
-                    let prefer_left = delta.angle_between(bot_velocity).is_sign_positive();
-                    let avoidance_offset = if prefer_left { 1. } else { -1. } * delta.perp();
-                    Influence::new(*target + avoidance_offset * 30., 5.)
-                }
-            } // Goal::ReachCheckpoints(targets) => {
-              //     let (targets_ahead, targets_behind): (Vec<_>, Vec<_>) = targets
-              //         .iter()
-              //         .cloned()
-              //         .partition(|target| target.dot(bot_velocity).is_sign_positive());
-              //     let filtered_targets = if targets_ahead.len() >= targets_behind.len() {
-              //         targets_ahead
-              //     } else {
-              //         targets_behind
-              //     };
-              //     let farthest_checkpoint = filtered_targets
-              //         .iter()
-              //         .map(|target| target.distance(bot_position))
-              //         .max_by(|d1, d2| d1.total_cmp(d2))
-              //         .unwrap_or(f32::MAX);
-
-              //     let average = weighted_avg(filtered_targets.into_iter().map(|target| {
-              //         Influence::new(
-              //             target,
-              //             (farthest_checkpoint - target.distance(bot_position)) / farthest_checkpoint,
-              //         )
-              //     }));
-              //     Influence::new(average, 10.)
-              // }
+            Goal::FollowLine { target, urgency } => Influence::new(*target, 8. * urgency),
+            Goal::Chase {
+                target, eagerness, ..
+            } => Influence::new(*target, eagerness * 10.),
+            // Goal::ReachCheckpoints(targets) => {
+            //     let (targets_ahead, targets_behind): (Vec<_>, Vec<_>) = targets
+            //         .iter()
+            //         .cloned()
+            //         .partition(|target| target.dot(bot_velocity).is_sign_positive());
+            //     let filtered_targets = if targets_ahead.len() >= targets_behind.len() {
+            //         targets_ahead
+            //     } else {
+            //         targets_behind
+            //     };
+            //     let farthest_checkpoint = filtered_targets
+            //         .iter()
+            //         .map(|target| target.distance(bot_position))
+            //         .max_by(|d1, d2| d1.total_cmp(d2))
+            //         .unwrap_or(f32::MAX);
+
+            //     let average = weighted_avg(filtered_targets.into_iter().map(|target| {
+            //         Influence::new(
+            //             target,
+            //             (farthest_checkpoint - target.distance(bot_position)) / farthest_checkpoint,
+            //         )
+            //     }));
+            //     Influence::new(average, 10.)
+            // }
         }
     }
 }
@@ -0,0 +1,210 @@
+use bevy::prelude::*;
+
+use car::Car;
+
+use crate::RacingLine;
+
+/// How many points each arc is checked at.
+const STEPS: usize = 8;
+/// How far ahead in time arcs reach, in seconds.
+const HORIZON: f32 = 0.8;
+/// How many steering inputs are tried from full left to full right, besides the bot's own.
+const CANDIDATES: usize = 9;
+/// Arcs are still planned at this speed when the bot is slower, so it can pull away from
+/// something it is stopped against.
+const MIN_SPEED: f32 = 200.;
+/// How much worse an arc is for running into something, more so the sooner it happens.
+const COLLISION_PENALTY: f32 = 1e4;
+/// How much worse an arc is for every unit it passes closer to something than the bot likes.
+const CLOSE_CALL_PENALTY: f32 = 10.;
+
+/// Something a bot would rather not drive into.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Hazard {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub radius: f32,
+}
+
+/// Picks where to steer by trying out a fan of arcs held for a moment each, and keeping the one
+/// that gets closest to where the bot wants to go without running into anything on the way.
+pub(crate) struct LocalPlanner<'a> {
+    position: Vec2,
+    heading: f32,
+    speed: f32,
+    clearance: f32,
+    road: Option<Road<'a>>,
+    hazards: Vec<Hazard>,
+}
+
+// the road's edges, the entity they belong to and where on them the bot is
+#[derive(Clone, Copy)]
+struct Road<'a> {
+    entity: Entity,
+    racing_line: &'a RacingLine,
+    index: usize,
+}
+
+impl<'a> LocalPlanner<'a> {
+    /// Plans for a car at `position`, facing `heading`, that wants `clearance` between itself
+    /// and whatever it passes, whether that is the edge of the track the `road`'s racing line
+    /// belongs to or one of the `hazards`. The `road` also holds the point on the line the car
+    /// is nearest to.
+    pub fn new(
+        position: Vec2,
+        heading: f32,
+        speed: f32,
+        clearance: f32,
+        road: Option<(Entity, &'a RacingLine, usize)>,
+        hazards: impl IntoIterator<Item = Hazard>,
+    ) -> Self {
+        let speed = speed.max(MIN_SPEED);
+        // anything further than an arc can reach is no trouble yet
+        let reach = speed * HORIZON + Car::LENGTH + clearance;
+        let hazards = hazards
+            .into_iter()
+            .filter(|hazard| {
+                let position_later = hazard.position + hazard.velocity * HORIZON;
+                let closest = position
+                    .distance(hazard.position)
+                    .min(position.distance(position_later));
+                closest - hazard.radius < reach
+            })
+            .collect();
+        let road = road.map(|(entity, racing_line, index)| Road {
+            entity,
+            racing_line,
+            index,
+        });
+        Self {
+            position,
+            heading,
+            speed,
+            clearance,
+            road,
+            hazards,
+        }
+    }
+
+    /// The first thing holding `steering` would run into.
+    pub fn hazard_ahead(&self, steering: f32) -> Option<Entity> {
+        self.first_hit(&self.arc(steering))
+            .map(|(entity, _)| entity)
+    }
+
+    /// The best steering input for reaching `target`, which is `preferred` unless another one
+    /// does better.
+    pub fn plan(&self, target: Vec2, preferred: f32) -> f32 {
+        let candidates = (0..CANDIDATES)
+            .map(|index| index as f32 / (CANDIDATES - 1) as f32 * 2. - 1.)
+            .chain([preferred]);
+        candidates
+            .map(|steering| (steering, self.score(&self.arc(steering), target)))
+            // ties go to the last one, which is the preferred input
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(preferred, |(steering, _)| steering)
+    }
+
+    // where the car would be at each step, following the kinematic bicycle model until the
+    // tyres run out of grip
+    fn arc(&self, steering: f32) -> [Vec2; STEPS] {
+        let speed = self.speed;
+        let wheel_angle = steering.clamp(-1., 1.) * Car::MAX_STEERING_DEG.to_radians();
+        let max_turn_rate = RacingLine::GRIP / speed;
+        let turn_rate =
+            (speed * wheel_angle.tan() / Car::WHEELBASE).clamp(-max_turn_rate, max_turn_rate);
+        let delta = HORIZON / STEPS as f32;
+        let mut position = self.position;
+        let mut heading = self.heading;
+        [(); STEPS].map(|_| {
+            heading += turn_rate * delta;
+            position += Vec2::from_angle(heading) * speed * delta;
+            position
+        })
+    }
+
+    fn score(&self, arc: &[Vec2; STEPS], target: Vec2) -> f32 {
+        let closest_approach = arc
+            .iter()
+            .map(|point| point.distance(target))
+            .fold(f32::INFINITY, f32::min);
+        let collision = self.first_hit(arc).map_or(0., |(_, step)| {
+            COLLISION_PENALTY * (STEPS - step) as f32 / STEPS as f32
+        });
+        let close_calls: f32 = arc
+            .iter()
+            .enumerate()
+            .map(|(step, point)| (self.clearance - self.gap(step, *point)).max(0.))
+            .sum();
+        -closest_approach - collision - close_calls * CLOSE_CALL_PENALTY
+    }
+
+    fn first_hit(&self, arc: &[Vec2; STEPS]) -> Option<(Entity, usize)> {
+        arc.iter().enumerate().find_map(|(step, point)| {
+            self.hazard_at(step, *point)
+                .filter(|(_, gap)| *gap < 0.)
+                .map(|(entity, _)| (entity, step))
+        })
+    }
+
+    // how much room there is around the car at a step, to the nearest edge or hazard
+    fn gap(&self, step: usize, point: Vec2) -> f32 {
+        self.hazard_at(step, point)
+            .map_or(f32::INFINITY, |(_, gap)| gap)
+    }
+
+    fn hazard_at(&self, step: usize, point: Vec2) -> Option<(Entity, f32)> {
+        let time = (step + 1) as f32 * HORIZON / STEPS as f32;
+        let car_radius = Car::LENGTH / 2.;
+        let edge = self.road.map(|road| {
+            let margin = road.racing_line.road_margin(road.index, point);
+            (road.entity, margin - Car::WIDTH / 2.)
+        });
+        self.hazards
+            .iter()
+            .map(|hazard| {
+                let position = hazard.position + hazard.velocity * time;
+                let gap = point.distance(position) - hazard.radius - car_radius;
+                (hazard.entity, gap)
+            })
+            .chain(edge)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_peg(peg: Vec2) -> LocalPlanner<'static> {
+        let peg = Hazard {
+            entity: Entity::from_raw(1),
+            position: peg,
+            velocity: Vec2::ZERO,
+            radius: 20.,
+        };
+        LocalPlanner::new(Vec2::ZERO, 0., 500., Car::WIDTH / 2., None, [peg])
+    }
+
+    #[test]
+    fn test_plan_around_peg() {
+        let target = Vec2::new(600., 0.);
+        // a peg just left of straight ahead is passed on the right, and the other way round
+        for (peg, side) in [(Vec2::new(150., 15.), -1.), (Vec2::new(150., -15.), 1.)] {
+            let planner = with_peg(peg);
+            assert_eq!(planner.hazard_ahead(0.), Some(Entity::from_raw(1)));
+            let steering = planner.plan(target, 0.);
+            assert_eq!(
+                steering.signum(),
+                side,
+                "steered {steering} around a peg at {peg}"
+            );
+            assert_eq!(planner.hazard_ahead(steering), None);
+        }
+        // with nothing in the way, the preferred input is kept
+        let planner = with_peg(Vec2::new(150., 300.));
+        assert_eq!(planner.hazard_ahead(0.), None);
+        assert_eq!(planner.plan(target, 0.), 0.);
+    }
+}
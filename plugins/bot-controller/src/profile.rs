@@ -0,0 +1,129 @@
+use std::{fmt, time::Duration};
+
+use bevy::prelude::*;
+
+use car::Car;
+use entropy::RngCore;
+
+/// How a bot drives, from how quickly it reacts to how hard it goes after other cars.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Component, Reflect)]
+pub struct BotProfile {
+    pub skill: BotSkill,
+    pub personality: BotPersonality,
+    /// How long something has to be in the way before the bot swerves around it.
+    pub reaction_delay: Duration,
+    /// From 0 for a bot that saws at the wheel to 1 for one that steers exactly where it means to.
+    pub steering_precision: f32,
+    /// How much earlier than the racing line calls for the bot starts braking for a corner, as a
+    /// distance along the line.
+    pub braking_point: f32,
+    /// From 0 for a bot that ignores the tags to 1 for one that hunts the flag holder down and
+    /// rams the bomb into whoever is nearest.
+    pub aggression: f32,
+    /// From 0 for a bot that gives pegs, walls and cars a wide berth to 1 for one that shaves
+    /// past them.
+    pub risk_tolerance: f32,
+}
+
+impl BotProfile {
+    pub fn new(skill: BotSkill, personality: BotPersonality) -> Self {
+        let (reaction_delay, steering_precision, braking_point, aggression, risk_tolerance) =
+            match skill {
+                BotSkill::Easy => (0.35, 0.4, 450., 0.2, 0.2),
+                BotSkill::Normal => (0.2, 0.65, 200., 0.45, 0.45),
+                BotSkill::Hard => (0.08, 0.9, 60., 0.7, 0.6),
+            };
+        let (reaction_factor, precision_offset, braking_factor, aggression_offset, risk_offset) =
+            match personality {
+                BotPersonality::Steady => (1., 0., 1., 0., 0.),
+                BotPersonality::Hothead => (1., 0., 0.75, 0.3, 0.3),
+                BotPersonality::Cautious => (0.8, 0.05, 1.3, -0.3, -0.3),
+                BotPersonality::Sloppy => (1.5, -0.25, 1., 0., 0.1),
+            };
+        Self {
+            skill,
+            personality,
+            reaction_delay: Duration::from_secs_f32(reaction_delay * reaction_factor),
+            steering_precision: (steering_precision + precision_offset).clamp(0., 1.),
+            braking_point: braking_point * braking_factor,
+            aggression: (aggression + aggression_offset).clamp(0., 1.),
+            risk_tolerance: (risk_tolerance + risk_offset).clamp(0., 1.),
+        }
+    }
+
+    /// How much room the bot wants between itself and anything it passes.
+    pub(crate) fn clearance(&self) -> f32 {
+        Car::WIDTH * (1.5 - self.risk_tolerance)
+    }
+
+    /// A profile of the given skill with a personality picked at random.
+    pub fn random(skill: BotSkill, rng: &mut impl RngCore) -> Self {
+        let index = rng.next_u32() as usize % BotPersonality::ALL.len();
+        Self::new(skill, BotPersonality::ALL[index])
+    }
+}
+
+impl Default for BotProfile {
+    fn default() -> Self {
+        Self::new(BotSkill::default(), BotPersonality::default())
+    }
+}
+
+impl fmt::Display for BotProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.skill.name(), self.personality.name())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Reflect)]
+pub enum BotSkill {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl BotSkill {
+    pub fn name(self) -> &'static str {
+        match self {
+            BotSkill::Easy => "Easy",
+            BotSkill::Normal => "Normal",
+            BotSkill::Hard => "Hard",
+        }
+    }
+}
+
+/// Leans a bot's skill one way or another.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Reflect)]
+pub enum BotPersonality {
+    /// Drives by the book.
+    #[default]
+    Steady,
+    /// Goes after the tags, brakes late and cuts things close.
+    Hothead,
+    /// Keeps out of trouble and brakes early.
+    Cautious,
+    /// Slow to notice things and wobbly on the wheel.
+    Sloppy,
+}
+
+impl BotPersonality {
+    pub const ALL: [BotPersonality; 4] = [
+        BotPersonality::Steady,
+        BotPersonality::Hothead,
+        BotPersonality::Cautious,
+        BotPersonality::Sloppy,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BotPersonality::Steady => "Steady",
+            BotPersonality::Hothead => "Hothead",
+            BotPersonality::Cautious => "Cautious",
+            BotPersonality::Sloppy => "Sloppy",
+        }
+    }
+}
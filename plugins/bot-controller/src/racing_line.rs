@@ -0,0 +1,283 @@
+use bevy::prelude::*;
+
+use car::Car;
+use track::{Track, TrackChunk};
+
+/// The line bots drive around a [`Track`], with how fast to go at each point of it.
+///
+/// There is a point on every chunk of the track, each slid across the road until the line
+/// bends as gently as it can: wide into corners, clipping the inside at the apex and wide again
+/// on the way out. Target speeds come from how tight the line is at each point, then are
+/// lowered ahead of every corner so there is room to brake for it.
+#[derive(Clone, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct RacingLine {
+    points: Vec<Vec2>,
+    target_speeds: Vec<f32>,
+    /// The inner and outer edge of the road across from each point.
+    edges: Vec<(Vec2, Vec2)>,
+}
+
+impl RacingLine {
+    /// How many times every point is nudged towards the curvature of its neighbours.
+    const ITERATIONS: usize = 1000;
+    /// How hard a car can corner before it slides, as sideways acceleration.
+    pub(crate) const GRIP: f32 = 700.;
+    /// How hard a car can slow down.
+    const BRAKING: f32 = 900.;
+    const TOP_SPEED: f32 = 1500.;
+    /// How many points behind a hint, and ahead of it, are searched for the one nearest a
+    /// position.
+    const BEHIND: usize = 4;
+    const AHEAD: usize = 12;
+
+    pub fn from_track(track: &Track) -> Self {
+        let chunks = track.chunks().collect::<Vec<_>>();
+        let count = chunks.len();
+        if count < 3 {
+            return Self::default();
+        }
+
+        // how far across each chunk the line is, from 0 at the inner edge to 1 at the outer,
+        // keeping a car's width from either edge
+        let mut offsets = vec![0.5; count];
+        let point =
+            |chunk: &TrackChunk, offset: f32| chunk.inner_edge().lerp(chunk.outer_edge(), offset);
+        for _ in 0..Self::ITERATIONS {
+            for index in 0..count {
+                let at = |delta: isize| {
+                    let index = (index as isize + delta).rem_euclid(count as isize) as usize;
+                    point(&chunks[index], offsets[index])
+                };
+                // aim for the average of the curvature either side
+                let target =
+                    (curvature(at(-2), at(-1), at(0)) + curvature(at(0), at(1), at(2))) / 2.;
+                // curvature is close enough to linear in the offset for a secant step
+                let chunk = &chunks[index];
+                let offset = offsets[index];
+                let step = 0.01;
+                let current = curvature(at(-1), point(chunk, offset), at(1));
+                let stepped = curvature(at(-1), point(chunk, offset + step), at(1));
+                if (stepped - current).abs() < f32::EPSILON {
+                    continue;
+                }
+                let margin = (Car::WIDTH / chunk.width()).min(0.5);
+                offsets[index] = (offset + (target - current) * step / (stepped - current))
+                    .clamp(margin, 1. - margin);
+            }
+        }
+        let points = chunks
+            .iter()
+            .zip(&offsets)
+            .map(|(chunk, offset)| point(chunk, *offset))
+            .collect::<Vec<_>>();
+
+        let mut target_speeds = (0..count)
+            .map(|index| {
+                let previous = points[(index + count - 1) % count];
+                let next = points[(index + 1) % count];
+                let curvature = curvature(previous, points[index], next).abs();
+                (Self::GRIP / curvature.max(f32::EPSILON))
+                    .sqrt()
+                    .min(Self::TOP_SPEED)
+            })
+            .collect::<Vec<_>>();
+        // working backwards twice round the loop reaches every corner from its approach
+        for index in (0..count * 2).rev() {
+            let (index, next) = (index % count, (index + 1) % count);
+            let distance = points[index].distance(points[next]);
+            let braking_speed =
+                (target_speeds[next].powi(2) + 2. * Self::BRAKING * distance).sqrt();
+            target_speeds[index] = target_speeds[index].min(braking_speed);
+        }
+
+        Self {
+            points,
+            target_speeds,
+            edges: chunks
+                .iter()
+                .map(|chunk| (chunk.inner_edge(), chunk.outer_edge()))
+                .collect(),
+        }
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    /// The point on the line closest to `position`, looking only a little behind and ahead of
+    /// `hint` when given one, like the point found last time, so that another stretch of road
+    /// passing close by isn't picked instead. The whole line is searched without a hint, or
+    /// when the position is further from the point found than the road is wide.
+    pub fn nearest(&self, position: Vec2, hint: Option<usize>) -> Option<usize> {
+        let count = self.points.len();
+        if let Some(hint) = hint.filter(|hint| *hint < count) {
+            let index = self.nearest_around(hint, position);
+            let (inner, outer) = self.edges[index];
+            if self.points[index].distance(position) <= inner.distance(outer) {
+                return Some(index);
+            }
+        }
+        self.closest(0..count, position)
+    }
+
+    /// The position `distance` further along the line than the point at `index`.
+    pub fn ahead(&self, index: usize, distance: f32) -> Vec2 {
+        let count = self.points.len();
+        let mut remaining = distance;
+        for step in 0..count {
+            let start = self.points[(index + step) % count];
+            let end = self.points[(index + step + 1) % count];
+            let length = start.distance(end);
+            if length >= remaining {
+                return start.lerp(end, remaining / length.max(f32::EPSILON));
+            }
+            remaining -= length;
+        }
+        self.points[index]
+    }
+
+    /// How far `position` is inside the nearest edge of the road, or outside it when negative,
+    /// for positions a little behind or ahead of the point at `index`.
+    pub fn road_margin(&self, index: usize, position: Vec2) -> f32 {
+        let count = self.points.len();
+        if count == 0 {
+            return f32::INFINITY;
+        }
+        let (inner, outer) = self.edges[self.nearest_around(index, position)];
+        let width = inner.distance(outer);
+        let across = (position - inner).dot((outer - inner) / width.max(f32::EPSILON));
+        across.min(width - across)
+    }
+
+    // the point closest to `position` from a little behind `index` to a little ahead of it
+    fn nearest_around(&self, index: usize, position: Vec2) -> usize {
+        let count = self.points.len();
+        let around = (count - Self::BEHIND.min(count)..count + Self::AHEAD)
+            .map(|offset| (index + offset) % count);
+        self.closest(around, position).unwrap_or(index)
+    }
+
+    fn closest(&self, indices: impl Iterator<Item = usize>, position: Vec2) -> Option<usize> {
+        indices.min_by(|a, b| {
+            let distance = |index: usize| self.points[index].distance_squared(position);
+            distance(*a).total_cmp(&distance(*b))
+        })
+    }
+
+    /// The slowest target speed from the point at `index` to `distance` further along.
+    pub fn slowest_ahead(&self, index: usize, distance: f32) -> f32 {
+        let count = self.points.len();
+        let mut slowest = self.target_speeds[index];
+        let mut travelled = 0.;
+        for step in 1..count {
+            let previous = self.points[(index + step - 1) % count];
+            let current = (index + step) % count;
+            travelled += previous.distance(self.points[current]);
+            if travelled > distance {
+                break;
+            }
+            slowest = slowest.min(self.target_speeds[current]);
+        }
+        slowest
+    }
+}
+
+// signed, positive when turning left
+fn curvature(a: Vec2, b: Vec2, c: Vec2) -> f32 {
+    let lengths = a.distance(b) * b.distance(c) * a.distance(c);
+    if lengths <= f32::EPSILON {
+        return 0.;
+    }
+    2. * (b - a).perp_dot(c - b) / lengths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // how far across the road the point at `index` is, from 0 at the inner edge to 1 at the outer
+    fn offset(line: &RacingLine, index: usize) -> f32 {
+        let (inner, outer) = line.edges[index];
+        (line.points[index] - inner).dot(outer - inner) / inner.distance_squared(outer)
+    }
+
+    #[test]
+    fn test_oval_line() {
+        let track = Track::default();
+        let line = RacingLine::from_track(&track);
+        let chunks = track.chunks().collect::<Vec<_>>();
+        assert_eq!(line.points().len(), chunks.len());
+
+        // the straights end where the half circles start
+        let straight_end = track.half_length() - track.radius();
+        for side in [-1., 1.] {
+            let turn = (0..chunks.len())
+                .filter(|index| chunks[*index].center().x * side > straight_end + 1.)
+                .collect::<Vec<_>>();
+            let straight = (0..chunks.len())
+                .filter(|index| chunks[*index].center().x * side <= straight_end + 1.)
+                .collect::<Vec<_>>();
+            assert!(!turn.is_empty());
+            // the apex is the middle of the turn, furthest out along x
+            let apex = *turn
+                .iter()
+                .max_by(|a, b| {
+                    (chunks[**a].center().x * side).total_cmp(&(chunks[**b].center().x * side))
+                })
+                .unwrap();
+            assert!(
+                offset(&line, apex) < 0.5,
+                "apex at {apex} is {} across the road",
+                offset(&line, apex)
+            );
+            // the middle of each straight is further out than the apex
+            let middle = *straight
+                .iter()
+                .min_by(|a, b| {
+                    let x = |index: usize| chunks[index].center().x.abs();
+                    x(**a).total_cmp(&x(**b))
+                })
+                .unwrap();
+            assert!(offset(&line, middle) > offset(&line, apex));
+
+            let fastest = |indices: &[usize]| {
+                indices
+                    .iter()
+                    .map(|index| line.target_speeds[*index])
+                    .fold(0., f32::max)
+            };
+            assert!(fastest(&turn) < fastest(&straight));
+            assert!(line.target_speeds[apex] < line.target_speeds[middle]);
+        }
+    }
+
+    #[test]
+    fn test_road_margin() {
+        let line = RacingLine::from_track(&Track::default());
+        for index in 0..line.points().len() {
+            let (inner, outer) = line.edges[index];
+            let margin = |across: f32| line.road_margin(index, inner.lerp(outer, across));
+            assert!(margin(0.5) > margin(0.1), "middle of {index}");
+            assert!(margin(0.1) > 0., "inside the inner edge of {index}");
+            assert!(margin(0.9) > 0., "inside the outer edge of {index}");
+            assert!(margin(-0.1) < 0., "past the inner edge of {index}");
+            assert!(margin(1.1) < 0., "past the outer edge of {index}");
+        }
+    }
+
+    #[test]
+    fn test_nearest() {
+        let line = RacingLine::from_track(&Track::default());
+        let count = line.points().len();
+        let points = line.points();
+
+        assert_eq!(line.nearest(points[5], None), Some(5));
+        assert_eq!(line.nearest(points[7], Some(5)), Some(7));
+        assert_eq!(line.nearest(points[1], Some(count - 1)), Some(1));
+        // too far from the hint to be around it, or a hint from a line that has since changed
+        assert_eq!(line.nearest(points[count / 2], Some(0)), Some(count / 2));
+        assert_eq!(line.nearest(points[3], Some(count + 3)), Some(3));
+        assert_eq!(RacingLine::default().nearest(Vec2::ZERO, Some(0)), None);
+    }
+}
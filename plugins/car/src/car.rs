@@ -1,11 +1,17 @@
+use std::time::Duration;
+
 use avian2d::prelude::{
-    AngularDamping, Collider, CollisionLayers, LayerMask, LinearDamping, Mass, RigidBody, Sleeping,
+    AngularDamping, AngularVelocity, Collider, CollisionLayers, LayerMask, LinearDamping,
+    LinearVelocity, Mass, Position, RigidBody, Rotation, Sleeping,
+};
+use bevy::{
+    ecs::system::{EntityCommand, StaticSystemParam},
+    prelude::*,
 };
-use bevy::{ecs::system::StaticSystemParam, prelude::*};
 
 use bevy_reactive_blueprints::{Blueprint, FromBlueprint};
 
-use crate::Wheel;
+use crate::{CarClass, Drivetrain, PowerCurve, Wheel};
 
 #[derive(Clone, Copy, Debug, Default)]
 #[derive(Component, Reflect)]
@@ -19,7 +25,260 @@ impl Car {
     pub const ENGINE_POWER: f32 = 4.2e3;
     pub const REVERSE_POWER: f32 = -3e3;
     pub const MAX_STEERING_DEG: f32 = 18.;
+    /// How far the front wheel joints allow the wheels to turn, leaving room for the inside wheel
+    /// to turn further than [`Car::MAX_STEERING_DEG`] in a tight corner.
+    pub const MAX_WHEEL_ANGLE_DEG: f32 = 30.;
+    /// Distance between the front and back axles.
+    pub const WHEELBASE: f32 = Wheel::OFFSET.x * 2.;
     pub const COLLISION_LAYER: LayerMask = LayerMask(1 << 1);
+    pub const Z_INDEX: f32 = 30.;
+}
+
+/// Overrides the default body color of a car.
+#[derive(Clone, Copy, Debug)]
+#[derive(Component, Deref, Reflect)]
+pub struct CarPaint(pub Color);
+
+/// Takes a car out of play: it stops moving, stops colliding, and is hidden until this is removed.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct Parked;
+
+/// Lets a car pass through other cars for a short while, e.g. right after being reset.
+#[derive(Clone, Copy, Debug)]
+#[derive(Component, Reflect)]
+pub struct Ghost {
+    remaining: Duration,
+}
+
+impl Ghost {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            remaining: duration,
+        }
+    }
+
+    pub(crate) fn fade(&mut self, delta: Duration) -> bool {
+        self.remaining = self.remaining.saturating_sub(delta);
+        self.remaining.is_zero()
+    }
+}
+
+/// How a car turns its front wheels.
+///
+/// The driver's input sets a target angle which the wheels swing towards at [`Steering::rate`].
+/// Each front wheel is then angled with Ackermann geometry, so that the inside wheel turns more
+/// sharply than the outside one and both roll around the same point.
+#[derive(Clone, Copy, Debug)]
+#[derive(Component, Reflect)]
+pub struct Steering {
+    /// Steering angle at full lock, in radians.
+    pub max_angle: f32,
+    /// How quickly the steering angle can change, in radians per second.
+    pub rate: f32,
+    angle: f32,
+}
+
+impl Default for Steering {
+    fn default() -> Self {
+        Self {
+            max_angle: Car::MAX_STEERING_DEG.to_radians(),
+            rate: 2.5,
+            angle: 0.,
+        }
+    }
+}
+
+impl Steering {
+    /// How hard the wheels are pushed towards their target angle.
+    const STIFFNESS: f32 = 30.;
+
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+
+    /// Moves the steering angle towards `input` (from -1 for full right to 1 for full left).
+    pub(crate) fn turn(&mut self, input: f32, max_angle: f32, delta: f32) {
+        let target = input.clamp(-1., 1.) * max_angle;
+        let max_step = self.rate * delta;
+        self.angle += (target - self.angle).clamp(-max_step, max_step);
+    }
+
+    /// The angle for a front wheel mounted `lateral_offset` to the left of the car's center line.
+    pub fn wheel_angle(&self, lateral_offset: f32) -> f32 {
+        if self.angle.abs() < 1e-4 {
+            return 0.;
+        }
+        // the signed distance from the car to the point it is turning around
+        let turning_radius = Car::WHEELBASE / self.angle.tan();
+        (Car::WHEELBASE / (turning_radius - lateral_offset)).atan()
+    }
+
+    /// The angular velocity that swings a wheel from `current_angle` towards `target_angle`.
+    pub(crate) fn correction(current_angle: f32, target_angle: f32) -> f32 {
+        (target_angle - current_angle) * Self::STIFFNESS
+    }
+}
+
+/// A car's nitro meter, from empty at `0.` to full at `1.`.
+#[derive(Clone, Copy, Debug)]
+#[derive(Component, Reflect)]
+pub struct Boost {
+    charge: f32,
+    /// The furthest checkpoint along the track that has paid out charge.
+    charged_checkpoint: Option<usize>,
+}
+
+impl Default for Boost {
+    fn default() -> Self {
+        Self {
+            charge: 0.5,
+            charged_checkpoint: None,
+        }
+    }
+}
+
+impl Boost {
+    /// Engine power multiplier while boosting.
+    pub const MULTIPLIER: f32 = 1.6;
+    /// Charge used per second of boosting.
+    pub const DRAIN_RATE: f32 = 0.35;
+    /// Charge regained per second when not boosting.
+    pub const RECHARGE_RATE: f32 = 0.03;
+    /// Extra charge regained per second while drifting.
+    pub const DRIFT_RECHARGE_RATE: f32 = 0.15;
+
+    pub fn charge(&self) -> f32 {
+        self.charge
+    }
+
+    pub fn can_boost(&self) -> bool {
+        self.charge > 0.
+    }
+
+    pub fn add(&mut self, charge: f32) {
+        self.charge = (self.charge + charge).clamp(0., 1.);
+    }
+
+    /// Adds `charge` for reaching checkpoint `index` of `count`, as long as it is further along
+    /// the track than the last one that paid out, so driving back and forth over a checkpoint
+    /// only pays once.
+    pub fn add_at_checkpoint(&mut self, index: usize, count: usize, charge: f32) {
+        if count == 0 {
+            return;
+        }
+        let advanced = self.charged_checkpoint.map_or(true, |last| {
+            let ahead = (index % count + count - last % count) % count;
+            ahead > 0 && ahead <= count / 2
+        });
+        if advanced {
+            self.charged_checkpoint = Some(index);
+            self.add(charge);
+        }
+    }
+}
+
+/// Wear and tear from collisions, from pristine at `0.` to wrecked at `1.`.
+///
+/// A damaged car has less power and sluggish steering, and slowly repairs itself over time.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct Damage {
+    amount: f32,
+}
+
+impl Damage {
+    /// Collision impulses below this are shrugged off.
+    pub const IMPULSE_THRESHOLD: f32 = 8e3;
+    /// Damage taken per unit of impulse above the threshold.
+    pub const DAMAGE_PER_IMPULSE: f32 = 1. / 2.5e5;
+    /// Damage repaired per second.
+    pub const REPAIR_RATE: f32 = 0.02;
+    /// A wrecked car is back in working order once it has been repaired below this.
+    pub const RECOVERED: f32 = 0.75;
+
+    pub fn amount(&self) -> f32 {
+        self.amount
+    }
+
+    pub fn is_wrecked(&self) -> bool {
+        self.amount >= 1.
+    }
+
+    /// Fraction of engine power left.
+    pub fn power_factor(&self) -> f32 {
+        1. - 0.4 * self.amount
+    }
+
+    /// Fraction of steering response left.
+    pub fn steering_factor(&self) -> f32 {
+        1. - 0.5 * self.amount
+    }
+
+    pub fn take_impulse(&mut self, impulse: f32) {
+        let damage = (impulse - Self::IMPULSE_THRESHOLD).max(0.) * Self::DAMAGE_PER_IMPULSE;
+        self.amount = (self.amount + damage).min(1.);
+    }
+
+    pub fn repair(&mut self, amount: f32) {
+        self.amount = (self.amount - amount).max(0.);
+    }
+}
+
+/// Marks a car that took so much [`Damage`] it is barely holding together.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct Wrecked;
+
+/// Present while a car is sliding sideways, tracking how long the drift has lasted.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct Drifting {
+    elapsed: Duration,
+}
+
+impl Drifting {
+    /// Slowest a car can go and still be drifting.
+    pub const MIN_SPEED: f32 = 150.;
+    /// Angle between where a car points and where it goes before it counts as drifting.
+    pub const MIN_SLIP_ANGLE: f32 = 15. * std::f32::consts::PI / 180.;
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub(crate) fn tick(&mut self, delta: Duration) {
+        self.elapsed += delta;
+    }
+}
+
+/// Watches for a car that is throttling without getting anywhere.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct StuckDetector {
+    anchor: Vec2,
+    stuck_for: Duration,
+}
+
+impl StuckDetector {
+    /// How far a car has to move to count as making progress.
+    const PROGRESS_DISTANCE: f32 = Car::LENGTH;
+    /// How long a car can go without progress before it is reset.
+    const PATIENCE: Duration = Duration::from_secs(3);
+
+    pub(crate) fn update(&mut self, position: Vec2, is_throttling: bool, delta: Duration) -> bool {
+        if !is_throttling || position.distance(self.anchor) > Self::PROGRESS_DISTANCE {
+            self.anchor = position;
+            self.stuck_for = Duration::ZERO;
+            return false;
+        }
+        self.stuck_for += delta;
+        if self.stuck_for < Self::PATIENCE {
+            return false;
+        }
+        self.stuck_for = Duration::ZERO;
+        true
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -27,6 +286,13 @@ impl Car {
 pub struct CarBundle {
     pub car: Car,
     pub name: Name,
+    pub class: CarClass,
+    pub drivetrain: Drivetrain,
+    pub power_curve: PowerCurve,
+    pub steering: Steering,
+    pub boost: Boost,
+    pub damage: Damage,
+    pub stuck_detector: StuckDetector,
 }
 
 #[derive(Clone, Debug)]
@@ -47,15 +313,28 @@ impl CarPhysicsBundle {
         Collider::rectangle(Car::LENGTH, Car::WIDTH)
     }
 
+    pub fn collision_layers() -> CollisionLayers {
+        CollisionLayers::new(
+            Car::COLLISION_LAYER,
+            LayerMask::ALL & !Wheel::COLLISION_LAYER,
+        )
+    }
+
+    /// The layers for a [`Ghost`] car, which drives through other cars but still hits walls and
+    /// crosses checkpoints.
+    pub fn ghost_collision_layers() -> CollisionLayers {
+        CollisionLayers::new(
+            Car::COLLISION_LAYER,
+            LayerMask::ALL & !Car::COLLISION_LAYER & !Wheel::COLLISION_LAYER,
+        )
+    }
+
     pub fn from_transform(transform: Transform) -> Self {
         CarPhysicsBundle {
             rigid_body: RigidBody::Dynamic,
             collider: Self::collider(),
             spatial: SpatialBundle::from_transform(transform),
-            layer: CollisionLayers::new(
-                Car::COLLISION_LAYER,
-                LayerMask::ALL & !Wheel::COLLISION_LAYER,
-            ),
+            layer: Self::collision_layers(),
             linear_damping: LinearDamping(1.),
             angular_damping: AngularDamping(1.5),
             sleeping: Sleeping,
@@ -70,14 +349,16 @@ pub struct CarBlueprint {
     pub origin: Vec2,
     pub angle: f32,
     pub is_player: bool,
+    pub class: CarClass,
 }
 
 impl CarBlueprint {
-    pub fn new(origin: Vec2, angle: f32, is_player: bool) -> Blueprint<Self> {
+    pub fn new(origin: Vec2, angle: f32, is_player: bool, class: CarClass) -> Blueprint<Self> {
         Blueprint::new(Self {
             origin,
             angle,
             is_player,
+            class,
         })
     }
 }
@@ -95,9 +376,16 @@ impl FromBlueprint<CarBlueprint> for TotalCarBundle {
             CarBundle {
                 car: Car,
                 name: Name::new("Car"),
+                class: blueprint.class,
+                drivetrain: blueprint.class.drivetrain(),
+                power_curve: blueprint.class.power_curve(),
+                steering: Steering::default(),
+                boost: Boost::default(),
+                damage: Damage::default(),
+                stuck_detector: StuckDetector::default(),
             },
             CarPhysicsBundle::from_transform(
-                Transform::from_translation(Vec3::new(blueprint.origin.x, blueprint.origin.y, 30.))
+                Transform::from_translation(blueprint.origin.extend(Car::Z_INDEX))
                     .with_rotation(Quat::from_rotation_z(blueprint.angle)),
             ),
         )
@@ -118,6 +406,33 @@ pub struct CarParts {
 }
 
 impl CarParts {
+    pub fn wheels(&self) -> [Entity; 4] {
+        [
+            self.wheel_front_left,
+            self.wheel_front_right,
+            self.wheel_back_left,
+            self.wheel_back_right,
+        ]
+    }
+
+    pub fn front_wheels(&self) -> [Entity; 2] {
+        [self.wheel_front_left, self.wheel_front_right]
+    }
+
+    pub fn back_wheels(&self) -> [Entity; 2] {
+        [self.wheel_back_left, self.wheel_back_right]
+    }
+
+    /// Each wheel alongside where it is mounted relative to the center of the car.
+    pub fn wheel_offsets(&self) -> [(Entity, Vec2); 4] {
+        [
+            (self.wheel_front_right, Wheel::OFFSET),
+            (self.wheel_front_left, Wheel::OFFSET * Vec2::new(1., -1.)),
+            (self.wheel_back_right, Wheel::OFFSET * Vec2::new(-1., 1.)),
+            (self.wheel_back_left, Wheel::OFFSET * Vec2::new(-1., -1.)),
+        ]
+    }
+
     pub fn entities(&self) -> [Entity; 8] {
         [
             self.wheel_front_left,
@@ -131,3 +446,42 @@ impl CarParts {
         ]
     }
 }
+
+/// Moves a car and its wheels to a new pose, bringing everything to a standstill.
+#[derive(Clone, Copy, Debug)]
+pub struct TeleportCar {
+    pub position: Vec2,
+    pub angle: f32,
+}
+
+impl EntityCommand for TeleportCar {
+    fn apply(self, car: Entity, world: &mut World) {
+        let parts = world.get::<CarParts>(car).copied();
+        let mut place = |entity: Entity, offset: Vec2| {
+            let position = self.position + Vec2::from_angle(self.angle).rotate(offset);
+            let Some(mut entity) = world.get_entity_mut(entity) else {
+                return;
+            };
+            if let Some(mut transform) = entity.get_mut::<Transform>() {
+                transform.translation = position.extend(transform.translation.z);
+                transform.rotation = Quat::from_rotation_z(self.angle);
+            }
+            if let Some(mut physics_position) = entity.get_mut::<Position>() {
+                physics_position.0 = position;
+            }
+            if let Some(mut rotation) = entity.get_mut::<Rotation>() {
+                *rotation = Rotation::radians(self.angle);
+            }
+            if let Some(mut velocity) = entity.get_mut::<LinearVelocity>() {
+                velocity.0 = Vec2::ZERO;
+            }
+            if let Some(mut velocity) = entity.get_mut::<AngularVelocity>() {
+                velocity.0 = 0.;
+            }
+        };
+        place(car, Vec2::ZERO);
+        for (wheel, offset) in parts.iter().flat_map(CarParts::wheel_offsets) {
+            place(wheel, offset);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use bevy::prelude::*;
+
+use crate::Car;
+
+/// Which wheels the engine drives.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Component, Reflect)]
+pub enum Drivetrain {
+    RearWheelDrive,
+    FrontWheelDrive,
+    /// Drives every wheel, sending `front_split` of the power to the front axle.
+    AllWheelDrive {
+        front_split: f32,
+    },
+}
+
+impl Default for Drivetrain {
+    fn default() -> Self {
+        Drivetrain::AllWheelDrive { front_split: 0.5 }
+    }
+}
+
+impl Drivetrain {
+    /// The fraction of engine power sent to the front and back axles.
+    pub fn split(&self) -> (f32, f32) {
+        match self {
+            Drivetrain::RearWheelDrive => (0., 1.),
+            Drivetrain::FrontWheelDrive => (1., 0.),
+            Drivetrain::AllWheelDrive { front_split } => {
+                let front_split = front_split.clamp(0., 1.);
+                (front_split, 1. - front_split)
+            }
+        }
+    }
+}
+
+/// How much power a car's engine makes as it picks up speed.
+///
+/// Power starts at `launch` times the peak, builds up to the peak at `peak_speed`, and then falls
+/// away to nothing at `top_speed`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Component, Reflect)]
+pub struct PowerCurve {
+    /// Peak power for each wheel if it were spread evenly over all four.
+    pub peak_power: f32,
+    pub launch: f32,
+    pub peak_speed: f32,
+    pub top_speed: f32,
+}
+
+impl Default for PowerCurve {
+    fn default() -> Self {
+        Self {
+            peak_power: Car::ENGINE_POWER,
+            launch: 0.85,
+            peak_speed: 250.,
+            top_speed: 2000.,
+        }
+    }
+}
+
+impl PowerCurve {
+    pub fn power_at(&self, speed: f32) -> f32 {
+        let factor = if speed < self.peak_speed {
+            self.launch + (1. - self.launch) * speed / self.peak_speed
+        } else {
+            1. - (speed - self.peak_speed) / (self.top_speed - self.peak_speed).max(f32::EPSILON)
+        };
+        self.peak_power * factor.clamp(0., 1.)
+    }
+}
+
+/// Presets that give cars their own feel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[derive(Reflect)]
+pub enum CarClass {
+    /// All-wheel drive with an even split, easy to handle.
+    #[default]
+    Balanced,
+    /// Rear-wheel drive with lots of power that has to be fed in carefully.
+    Muscle,
+    /// Front-wheel drive with a quick launch and a low top speed.
+    Hatchback,
+}
+
+impl CarClass {
+    pub const ALL: [CarClass; 3] = [CarClass::Balanced, CarClass::Muscle, CarClass::Hatchback];
+
+    pub fn drivetrain(&self) -> Drivetrain {
+        match self {
+            CarClass::Balanced => Drivetrain::default(),
+            CarClass::Muscle => Drivetrain::RearWheelDrive,
+            CarClass::Hatchback => Drivetrain::FrontWheelDrive,
+        }
+    }
+
+    pub fn power_curve(&self) -> PowerCurve {
+        match self {
+            CarClass::Balanced => PowerCurve::default(),
+            CarClass::Muscle => PowerCurve {
+                peak_power: 5e3,
+                launch: 0.7,
+                peak_speed: 400.,
+                top_speed: 2200.,
+            },
+            CarClass::Hatchback => PowerCurve {
+                peak_power: 3.8e3,
+                launch: 1.,
+                peak_speed: 150.,
+                top_speed: 1500.,
+            },
+        }
+    }
+}
+
+/// The driving force the engine put through a wheel this frame, which eats into its grip.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Deref, DerefMut, Reflect)]
+pub struct DriveForce(pub(crate) f32);
@@ -4,25 +4,163 @@ use bevy::sprite::MaterialMesh2dBundle;
 
 use bevy_reactive_blueprints::{AsChild, BlueprintPlugin, FromBlueprint};
 
-use crate::{Car, CarBlueprint, Wheel};
+use crate::{Car, CarBlueprint, CarPaint, Damage, Ghost, Wheel};
 
 pub struct CarGraphicsPlugin;
 
 impl Plugin for CarGraphicsPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(BlueprintPlugin::<CarBlueprint, CarGraphicsBundle, AsChild>::default())
-            .add_plugins(BlueprintPlugin::<Wheel, WheelGraphicsBundle, AsChild>::default());
+            .add_plugins(BlueprintPlugin::<Wheel, WheelGraphicsBundle, AsChild>::default())
+            .add_systems(
+                Update,
+                (Self::paint_cars, Self::fade_ghosts, Self::show_damage).chain(),
+            );
+        app.register_type::<CarGraphics>()
+            .register_type::<DamageGraphics>();
+    }
+}
+
+impl CarGraphicsPlugin {
+    #[allow(clippy::type_complexity)]
+    fn paint_cars(
+        painted_cars: Query<(&CarPaint, &Children), Or<(Changed<CarPaint>, Changed<Children>)>>,
+        graphics: Query<&Handle<ColorMaterial>, With<CarGraphics>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        for (paint, children) in &painted_cars {
+            for handle in graphics.iter_many(children) {
+                if let Some(material) = materials.get_mut(handle) {
+                    // keep whatever fade a ghost has, as this also runs when damage adds a child
+                    material.color = paint.with_alpha(material.color.alpha());
+                }
+            }
+        }
+    }
+
+    fn show_damage(
+        mut commands: Commands,
+        mut cars: Query<(Entity, &Damage, Option<&mut DamageGraphics>), Changed<Damage>>,
+        tints: Query<&Handle<ColorMaterial>>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        for (car, damage, graphics) in &mut cars {
+            let Some(mut graphics) = graphics else {
+                // spawn an overlay that darkens the car as it gets beaten up
+                let tint = commands
+                    .spawn(ColorMesh2dBundle {
+                        mesh: meshes.add(Rectangle::new(Car::LENGTH, Car::WIDTH)).into(),
+                        material: materials.add(DamageGraphics::tint(damage.amount())),
+                        transform: Transform::from_xyz(0., 0., 0.5),
+                        ..Default::default()
+                    })
+                    .id();
+                commands.entity(car).add_child(tint).insert(DamageGraphics {
+                    tint,
+                    dents: vec![],
+                });
+                continue;
+            };
+
+            if let Some(material) = tints
+                .get(graphics.tint)
+                .ok()
+                .and_then(|handle| materials.get_mut(handle))
+            {
+                material.color = DamageGraphics::tint(damage.amount()).color;
+            }
+
+            let dent_count = ((damage.amount() * DamageGraphics::DENTS.len() as f32) as usize)
+                .min(DamageGraphics::DENTS.len());
+            if dent_count == graphics.dents.len() {
+                continue;
+            }
+            for dent in graphics.dents.drain(..) {
+                commands.entity(dent).despawn_recursive();
+            }
+            for (offset, radius) in DamageGraphics::DENTS.into_iter().take(dent_count) {
+                let dent = commands
+                    .spawn(ColorMesh2dBundle {
+                        mesh: meshes.add(Circle::new(radius)).into(),
+                        material: materials.add(Color::srgba(0.1, 0.1, 0.1, 0.7)),
+                        transform: Transform::from_translation(offset.extend(0.6)),
+                        ..Default::default()
+                    })
+                    .id();
+                commands.entity(car).add_child(dent);
+                graphics.dents.push(dent);
+            }
+        }
+    }
+
+    fn fade_ghosts(
+        new_ghosts: Query<Entity, Added<Ghost>>,
+        mut removed_ghosts: RemovedComponents<Ghost>,
+        cars: Query<(&Children, Has<Ghost>), With<Car>>,
+        graphics: Query<&Handle<ColorMaterial>, With<CarGraphics>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        for car in new_ghosts.iter().chain(removed_ghosts.read()) {
+            let Ok((children, is_ghost)) = cars.get(car) else {
+                continue;
+            };
+            let alpha = if is_ghost {
+                CarGraphics::GHOST_ALPHA
+            } else {
+                1.
+            };
+            for handle in graphics.iter_many(children) {
+                if let Some(material) = materials.get_mut(handle) {
+                    material.color.set_alpha(alpha);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct CarGraphics;
+
+impl CarGraphics {
+    const GHOST_ALPHA: f32 = 0.4;
+}
+
+/// Overlays drawn on a car to show how much [`Damage`] it has taken.
+#[derive(Clone, Debug)]
+#[derive(Component, Reflect)]
+struct DamageGraphics {
+    tint: Entity,
+    dents: Vec<Entity>,
+}
+
+impl DamageGraphics {
+    // where dents show up as damage builds, and how big they are
+    const DENTS: [(Vec2, f32); 4] = [
+        (Vec2::new(22., 12.), 6.),
+        (Vec2::new(-18., -10.), 7.),
+        (Vec2::new(4., -14.), 5.),
+        (Vec2::new(-24., 13.), 6.),
+    ];
+
+    fn tint(amount: f32) -> ColorMaterial {
+        ColorMaterial::from(Color::srgba(0.05, 0.05, 0.05, amount * 0.45))
     }
 }
 
 #[derive(Bundle)]
 pub struct CarGraphicsBundle {
     pub shape: MaterialMesh2dBundle<ColorMaterial>,
+    pub marker: CarGraphics,
 }
 
 impl CarGraphicsBundle {
     pub fn new(shape: MaterialMesh2dBundle<ColorMaterial>) -> Self {
-        CarGraphicsBundle { shape }
+        CarGraphicsBundle {
+            shape,
+            marker: CarGraphics,
+        }
     }
 }
 
@@ -38,13 +176,11 @@ impl FromBlueprint<CarBlueprint> for CarGraphicsBundle {
         } else {
             Color::srgb(0.77, 0.42, 0.34)
         };
-        CarGraphicsBundle {
-            shape: MaterialMesh2dBundle {
-                mesh: params.0.add(Rectangle::new(Car::LENGTH, Car::WIDTH)).into(),
-                material: params.1.add(color),
-                ..Default::default()
-            },
-        }
+        CarGraphicsBundle::new(MaterialMesh2dBundle {
+            mesh: params.0.add(Rectangle::new(Car::LENGTH, Car::WIDTH)).into(),
+            material: params.1.add(color),
+            ..Default::default()
+        })
     }
 }
 
@@ -1,13 +1,19 @@
 use avian2d::prelude::{
-    AngularVelocity, ExternalAngularImpulse, ExternalImpulse, ExternalTorque, Gravity,
-    LinearVelocity, Rotation,
+    AngularVelocity, Collision, CollisionLayers, ExternalAngularImpulse, ExternalImpulse,
+    ExternalTorque, Gravity, LinearVelocity, RigidBody, Rotation,
+};
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+    utils::EntityHashSet,
 };
-use bevy::prelude::*;
 
 use bevy_reactive_blueprints::BlueprintPlugin;
 
 mod car;
 pub use car::*;
+mod drivetrain;
+pub use drivetrain::*;
 mod wheel;
 pub use wheel::*;
 
@@ -16,7 +22,18 @@ mod graphics;
 #[cfg(feature = "graphics")]
 pub use graphics::*;
 
-pub struct CarPlugin;
+pub struct CarPlugin {
+    /// The schedule cars are driven in.
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for CarPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
 
 impl Plugin for CarPlugin {
     fn build(&self, app: &mut App) {
@@ -24,24 +41,50 @@ impl Plugin for CarPlugin {
         app.add_plugins(CarGraphicsPlugin);
         app.add_plugins(BlueprintPlugin::<CarBlueprint, TotalCarBundle>::default());
         app.add_systems(
-            Update,
+            self.schedule,
             (
                 Self::reset_overspinning_objects,
+                Self::apply_collision_damage,
+                Self::repair_damage,
                 Self::apply_steering,
                 Self::apply_acceleration,
                 Self::apply_wheel_friction,
                 Self::apply_car_drag,
+                Self::detect_drifts,
+                Self::update_boost,
+                Self::detect_stuck_cars,
                 Self::clear_action_components,
                 Self::despawn_car_parts,
                 Self::spawn_car_parts,
+                Self::park_cars,
+                Self::fade_ghosts,
+                Self::update_collision_layers,
             )
                 .chain()
                 .in_set(DrivingSystems),
         );
+        app.add_event::<CarResetRequested>();
         app.register_type::<AccelerateAction>()
             .register_type::<SteerAction>()
+            .register_type::<HandbrakeAction>()
+            .register_type::<BoostAction>()
+            .register_type::<Boost>()
+            .register_type::<Steering>()
+            .register_type::<CarClass>()
+            .register_type::<Drivetrain>()
+            .register_type::<PowerCurve>()
+            .register_type::<DriveForce>()
+            .register_type::<ResetAction>()
+            .register_type::<Drifting>()
+            .register_type::<Damage>()
+            .register_type::<Wrecked>()
+            .register_type::<CarResetRequested>()
+            .register_type::<Ghost>()
+            .register_type::<StuckDetector>()
             .register_type::<Car>()
+            .register_type::<CarPaint>()
             .register_type::<CarParts>()
+            .register_type::<Parked>()
             .register_type::<Wheel>()
             .register_type::<FrontWheel>()
             .register_type::<BackWheel>()
@@ -84,63 +127,156 @@ impl CarPlugin {
         }
     }
 
-    fn apply_steering(
-        cars: Query<(&Rotation, &CarParts, Option<&SteerAction>), With<Car>>,
-        mut front_wheels: Query<(&Rotation, &mut ExternalAngularImpulse), With<FrontWheel>>,
+    fn apply_collision_damage(
+        mut collisions: EventReader<Collision>,
+        mut cars: Query<&mut Damage, With<Car>>,
     ) {
-        for (car_rotation, parts, steering) in &cars {
-            for (wheel_rotation, mut impulse) in front_wheels
-                .get_many_mut([parts.wheel_front_left, parts.wheel_front_right])
-                .into_iter()
-                .flatten()
-            {
-                if let Some(SteerAction(steer_angle)) = steering {
-                    **impulse += *steer_angle * 100.;
-                } else {
-                    let wheel_rotation = car_rotation.angle_between(*wheel_rotation);
-                    if wheel_rotation > 1_f32.to_radians() {
-                        **impulse -= 100. * wheel_rotation * std::f32::consts::FRAC_1_PI;
-                    }
+        for Collision(contacts) in collisions.read() {
+            for entity in [contacts.entity1, contacts.entity2] {
+                if let Ok(mut damage) = cars.get_mut(entity) {
+                    damage.take_impulse(contacts.total_normal_impulse);
                 }
             }
         }
     }
 
-    fn apply_acceleration(
-        cars: Query<(&CarParts, &AccelerateAction), With<Car>>,
-        mut wheels: Query<(&Rotation, &mut ExternalImpulse), With<Wheel>>,
+    fn repair_damage(
+        mut commands: Commands,
+        mut cars: Query<(Entity, &mut Damage, Has<Wrecked>), With<Car>>,
+        time: Res<Time>,
     ) {
-        for (car_wheels, acceleration) in &cars {
-            let CarParts {
-                wheel_front_left,
-                wheel_front_right,
-                wheel_back_left,
-                wheel_back_right,
-                ..
-            } = car_wheels;
-            for (rotation, mut impulse) in wheels
-                .get_many_mut([
-                    *wheel_front_left,
-                    *wheel_front_right,
-                    *wheel_back_left,
-                    *wheel_back_right,
-                ])
-                .expect("Car wheels to be valid entities")
-            {
-                let forward = Vec2::from_angle(rotation.as_radians());
-                let power = match acceleration {
-                    AccelerateAction::Forward => Car::ENGINE_POWER,
-                    AccelerateAction::Backward => Car::REVERSE_POWER,
+        for (car, mut damage, is_wrecked) in &mut cars {
+            if damage.is_wrecked() && !is_wrecked {
+                commands.entity(car).insert(Wrecked);
+            } else if is_wrecked && damage.amount() < Damage::RECOVERED {
+                commands.entity(car).remove::<Wrecked>();
+            }
+            if damage.amount() > 0. {
+                damage.repair(Damage::REPAIR_RATE * time.delta_seconds());
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn apply_steering(
+        mut cars: Query<
+            (
+                &Rotation,
+                &AngularVelocity,
+                &CarParts,
+                &mut Steering,
+                Option<&SteerAction>,
+                Option<&Damage>,
+            ),
+            With<Car>,
+        >,
+        mut front_wheels: Query<
+            (&Rotation, &mut AngularVelocity),
+            (With<FrontWheel>, Without<Car>),
+        >,
+        time: Res<Time>,
+    ) {
+        for (car_rotation, car_spin, parts, mut steering, input, damage) in &mut cars {
+            let max_angle = steering.max_angle * damage.map_or(1., Damage::steering_factor);
+            let input = input.map_or(0., |SteerAction(input)| *input);
+            steering.turn(input, max_angle, time.delta_seconds());
+
+            for (wheel, offset) in parts.wheel_offsets() {
+                let Ok((wheel_rotation, mut wheel_spin)) = front_wheels.get_mut(wheel) else {
+                    continue;
                 };
-                **impulse += forward * power;
+                let current_angle = car_rotation.angle_between(*wheel_rotation);
+                let target_angle = steering.wheel_angle(offset.y);
+                // spin the wheel along with the car, plus whatever it takes to reach the target
+                wheel_spin.0 = car_spin.0 + Steering::correction(current_angle, target_angle);
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn apply_acceleration(
+        cars: Query<
+            (
+                &CarParts,
+                &AccelerateAction,
+                &LinearVelocity,
+                &Drivetrain,
+                &PowerCurve,
+                Has<HandbrakeAction>,
+                Option<(&Boost, &BoostAction)>,
+                Option<&Damage>,
+            ),
+            With<Car>,
+        >,
+        mut wheels: Query<(&Rotation, &mut ExternalImpulse, &mut DriveForce), With<Wheel>>,
+    ) {
+        for (
+            car_wheels,
+            acceleration,
+            velocity,
+            drivetrain,
+            power_curve,
+            is_handbraking,
+            boost,
+            damage,
+        ) in &cars
+        {
+            let damage_multiplier = damage.map_or(1., Damage::power_factor);
+            let boost_multiplier = match (acceleration, boost) {
+                (AccelerateAction::Forward, Some((boost, _))) if boost.can_boost() => {
+                    Boost::MULTIPLIER
+                }
+                _ => 1.,
+            };
+            let power = match acceleration {
+                AccelerateAction::Forward => power_curve.power_at(velocity.length()),
+                AccelerateAction::Backward => Car::REVERSE_POWER,
+            } * boost_multiplier
+                * damage_multiplier;
+            // each axle has two wheels sharing its part of the power that
+            // would otherwise be spread over all four
+            let (front_split, back_split) = drivetrain.split();
+            let axles = [
+                (car_wheels.front_wheels(), front_split * 2.),
+                // locked wheels can't put power down
+                (
+                    car_wheels.back_wheels(),
+                    if is_handbraking { 0. } else { back_split * 2. },
+                ),
+            ];
+            for (axle, share) in axles {
+                for wheel in axle {
+                    let Ok((rotation, mut impulse, mut drive_force)) = wheels.get_mut(wheel) else {
+                        continue;
+                    };
+                    let forward = Vec2::from_angle(rotation.as_radians());
+                    **drive_force = power * share;
+                    **impulse += forward * **drive_force;
+                }
             }
         }
     }
 
     fn apply_wheel_friction(
-        mut wheels: Query<(&mut ExternalImpulse, &LinearVelocity, &Rotation), With<Wheel>>,
+        handbraking_cars: Query<&CarParts, (With<Car>, With<HandbrakeAction>)>,
+        mut wheels: Query<
+            (
+                Entity,
+                &mut ExternalImpulse,
+                &mut DriveForce,
+                &LinearVelocity,
+                &Rotation,
+            ),
+            With<Wheel>,
+        >,
     ) {
-        for (mut impulse, velocity, rotation) in &mut wheels {
+        let locked_wheels = handbraking_cars
+            .iter()
+            .flat_map(CarParts::back_wheels)
+            .collect::<EntityHashSet<_>>();
+        for (wheel, mut impulse, mut drive_force, velocity, rotation) in &mut wheels {
+            // whatever power went through the wheel this frame has been used up
+            let drive_force = std::mem::take(&mut **drive_force);
             if velocity.length() <= f32::EPSILON {
                 continue;
             }
@@ -151,19 +287,31 @@ impl CarPlugin {
             let force_against_ground =
                 (Wheel::MASS.0 + Car::MASS.0 / 4.) * Gravity::default().0.length();
 
-            let main_axis_friction = if velocity.dot(forward).is_sign_positive() {
-                // main-axis friction with velocity facing forward can be calculated using the projection of the normalized
-                // velocity vector onto the forward vector
-                -0.8 * velocity.normalize().project_onto(forward) * force_against_ground
+            let is_locked = locked_wheels.contains(&wheel);
+
+            let main_axis_friction_coefficient = if is_locked {
+                // a locked wheel doesn't roll, so it scrubs against the ground instead
+                Wheel::LOCKED_FRICTION
             } else if velocity.dot(forward).is_sign_positive() {
-                // main-axis friction with velocity opposite forward is lower, since the car is slipping
-                -0.3 * velocity.normalize().project_onto(forward) * force_against_ground
+                Wheel::ROLLING_FRICTION
             } else {
-                Vec2::ZERO
+                // main-axis friction with velocity opposite forward is lower, since the car is
+                // slipping
+                Wheel::REVERSE_ROLLING_FRICTION
             };
+            // main-axis friction can be calculated using the projection of the normalized velocity
+            // vector onto the forward vector
+            let main_axis_friction = -main_axis_friction_coefficient
+                * velocity.normalize().project_onto(forward)
+                * force_against_ground;
             // in the cross-axis direction, friction is much higher
+            // until the tire is pushed past its limit and starts to slide
+            // and a wheel that is putting power down has less grip to spare
+            let grip = Wheel::lateral_grip(slip_angle(**velocity, rotation))
+                * Wheel::traction_grip(drive_force)
+                * if is_locked { Wheel::HANDBRAKE_GRIP } else { 1. };
             let cross_axis_friction =
-                -4. * velocity.normalize().reject_from(forward) * force_against_ground;
+                -grip * velocity.normalize().reject_from(forward) * force_against_ground;
             **impulse += main_axis_friction + cross_axis_friction;
         }
     }
@@ -183,7 +331,147 @@ impl CarPlugin {
             commands
                 .entity(car_entity)
                 .remove::<SteerAction>()
-                .remove::<AccelerateAction>();
+                .remove::<AccelerateAction>()
+                .remove::<HandbrakeAction>()
+                .remove::<BoostAction>()
+                .remove::<ResetAction>();
+        }
+    }
+
+    fn detect_drifts(
+        mut commands: Commands,
+        mut cars: Query<(Entity, &LinearVelocity, &Rotation, Option<&mut Drifting>), With<Car>>,
+        time: Res<Time>,
+    ) {
+        for (car, velocity, rotation, drifting) in &mut cars {
+            let is_drifting = velocity.length() > Drifting::MIN_SPEED
+                && slip_angle(**velocity, rotation) > Drifting::MIN_SLIP_ANGLE;
+            match (is_drifting, drifting) {
+                (true, Some(mut drifting)) => drifting.tick(time.delta()),
+                (true, None) => {
+                    commands.entity(car).insert(Drifting::default());
+                }
+                (false, Some(_)) => {
+                    commands.entity(car).remove::<Drifting>();
+                }
+                (false, None) => {}
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn update_boost(
+        mut cars: Query<
+            (
+                &mut Boost,
+                Option<&AccelerateAction>,
+                Has<BoostAction>,
+                Has<Drifting>,
+            ),
+            With<Car>,
+        >,
+        time: Res<Time>,
+    ) {
+        let delta = time.delta_seconds();
+        for (mut boost, acceleration, wants_boost, is_drifting) in &mut cars {
+            let is_boosting =
+                wants_boost && matches!(acceleration, Some(AccelerateAction::Forward));
+            if is_boosting && boost.can_boost() {
+                boost.add(-Boost::DRAIN_RATE * delta);
+            } else {
+                boost.add(Boost::RECHARGE_RATE * delta);
+            }
+            if is_drifting {
+                boost.add(Boost::DRIFT_RECHARGE_RATE * delta);
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn detect_stuck_cars(
+        mut cars: Query<
+            (
+                Entity,
+                &Transform,
+                &mut StuckDetector,
+                Has<AccelerateAction>,
+                Has<ResetAction>,
+            ),
+            Without<Parked>,
+        >,
+        mut resets: EventWriter<CarResetRequested>,
+        time: Res<Time>,
+    ) {
+        for (car, transform, mut detector, is_throttling, wants_reset) in &mut cars {
+            let is_stuck = detector.update(transform.translation.xy(), is_throttling, time.delta());
+            if is_stuck || wants_reset {
+                resets.send(CarResetRequested { car, is_stuck });
+            }
+        }
+    }
+
+    fn park_cars(
+        mut commands: Commands,
+        parked_cars: Query<(Entity, &CarParts), Added<Parked>>,
+        mut unparked_cars: RemovedComponents<Parked>,
+        cars: Query<&CarParts, With<Car>>,
+    ) {
+        for (car, parts) in &parked_cars {
+            for entity in std::iter::once(car).chain(parts.wheels()) {
+                commands
+                    .entity(entity)
+                    .insert((RigidBody::Static, Visibility::Hidden));
+            }
+        }
+        for car in unparked_cars.read() {
+            let Ok(parts) = cars.get(car) else {
+                continue;
+            };
+            for entity in std::iter::once(car).chain(parts.wheels()) {
+                commands
+                    .entity(entity)
+                    .insert((RigidBody::Dynamic, Visibility::Inherited));
+            }
+        }
+    }
+
+    fn fade_ghosts(
+        mut commands: Commands,
+        mut ghosts: Query<(Entity, &mut Ghost)>,
+        time: Res<Time>,
+    ) {
+        for (car, mut ghost) in &mut ghosts {
+            if ghost.fade(time.delta()) {
+                commands.entity(car).remove::<Ghost>();
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn update_collision_layers(
+        mut commands: Commands,
+        new_states: Query<Entity, (With<Car>, Or<(Added<Parked>, Added<Ghost>)>)>,
+        mut unparked_cars: RemovedComponents<Parked>,
+        mut unghosted_cars: RemovedComponents<Ghost>,
+        cars: Query<(Has<Parked>, Has<Ghost>), With<Car>>,
+    ) {
+        let changed_cars = new_states
+            .iter()
+            .chain(unparked_cars.read())
+            .chain(unghosted_cars.read())
+            .collect::<EntityHashSet<_>>();
+        for car in changed_cars {
+            let Ok((is_parked, is_ghost)) = cars.get(car) else {
+                continue;
+            };
+            let layers = if is_parked {
+                CollisionLayers::NONE
+            } else if is_ghost {
+                CarPhysicsBundle::ghost_collision_layers()
+            } else {
+                CarPhysicsBundle::collision_layers()
+            };
+            commands.entity(car).insert(layers);
         }
     }
 
@@ -307,3 +595,37 @@ pub enum AccelerateAction {
 #[derive(Clone, Copy, Debug)]
 #[derive(Component, Deref, Reflect)]
 pub struct SteerAction(pub f32);
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Component, Reflect)]
+pub struct HandbrakeAction;
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Component, Reflect)]
+pub struct BoostAction;
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Component, Reflect)]
+pub struct ResetAction;
+
+/// Sent when a car should be put back on the track, either because it is stuck or because its
+/// driver asked for it.
+#[derive(Clone, Copy, Debug)]
+#[derive(Event, Reflect)]
+pub struct CarResetRequested {
+    pub car: Entity,
+    /// Whether the car was put back for being stuck, rather than by its driver.
+    pub is_stuck: bool,
+}
+
+/// The angle in radians between where a body points and where it is actually going, ignoring
+/// whether it is rolling forwards or backwards.
+pub fn slip_angle(velocity: Vec2, rotation: &Rotation) -> f32 {
+    if velocity.length_squared() <= f32::EPSILON {
+        return 0.;
+    }
+    let angle = velocity
+        .angle_between(Vec2::from_angle(rotation.as_radians()))
+        .abs();
+    angle.min(std::f32::consts::PI - angle)
+}
@@ -1,11 +1,11 @@
 use avian2d::prelude::{
     Collider, CollisionLayers, FixedJoint, Joint, LayerMask, Mass, RevoluteJoint, RigidBody,
-    Sleeping,
+    Rotation, Sleeping,
 };
 use bevy::prelude::*;
 use bevy_reactive_blueprints::Blueprint;
 
-use crate::Car;
+use crate::{Car, DriveForce};
 
 #[derive(Clone, Copy, Debug, Default)]
 #[derive(Component, Reflect)]
@@ -20,6 +20,48 @@ impl Wheel {
         y: Car::WIDTH / 2. + Wheel::WIDTH,
     };
     pub const COLLISION_LAYER: LayerMask = LayerMask(1 << 2);
+    /// Sideways grip while the tire is still gripping, as a multiple of the wheel's weight.
+    pub const CROSS_AXIS_GRIP: f32 = 4.;
+    /// Slip angle at which the tire starts to let go.
+    pub const PEAK_SLIP_ANGLE: f32 = 8. * std::f32::consts::PI / 180.;
+    /// Slip angle past which the tire is fully sliding.
+    pub const SLIDING_SLIP_ANGLE: f32 = 25. * std::f32::consts::PI / 180.;
+    /// Fraction of the peak grip left once the tire is sliding.
+    pub const SLIDING_GRIP: f32 = 0.7;
+    /// Fraction of the sideways grip left on rear wheels while the handbrake is pulled.
+    pub const HANDBRAKE_GRIP: f32 = 0.4;
+    /// Friction along the rolling direction for a wheel rolling forwards.
+    pub const ROLLING_FRICTION: f32 = 0.8;
+    /// Friction along the rolling direction for a wheel rolling backwards.
+    pub const REVERSE_ROLLING_FRICTION: f32 = 0.3;
+    /// Friction along the rolling direction for a wheel locked by the handbrake, which slides
+    /// instead of rolling.
+    pub const LOCKED_FRICTION: f32 = 2.4;
+    /// Fraction of sideways grip lost when a wheel is putting down full power.
+    pub const POWER_GRIP_LOSS: f32 = 0.5;
+
+    /// The component of a wheel's velocity across its rolling direction, i.e. how fast it slides.
+    pub fn cross_axis_velocity(velocity: Vec2, rotation: &Rotation) -> Vec2 {
+        velocity.reject_from(Vec2::from_angle(rotation.as_radians()))
+    }
+
+    /// How much sideways grip is left after `drive_force` of it is spent on putting power down.
+    pub fn traction_grip(drive_force: f32) -> f32 {
+        let usage = (drive_force.abs() / (Car::ENGINE_POWER * 2.)).min(1.);
+        1. - Self::POWER_GRIP_LOSS * usage * usage
+    }
+
+    /// How much sideways grip a wheel has when sliding at `slip_angle` radians.
+    ///
+    /// Grip holds steady up to [`Wheel::PEAK_SLIP_ANGLE`] and then falls off towards
+    /// [`Wheel::SLIDING_GRIP`], so a car that is pushed past the limit keeps sliding until it
+    /// is straightened out again.
+    pub fn lateral_grip(slip_angle: f32) -> f32 {
+        let slide = ((slip_angle - Self::PEAK_SLIP_ANGLE)
+            / (Self::SLIDING_SLIP_ANGLE - Self::PEAK_SLIP_ANGLE))
+            .clamp(0., 1.);
+        Self::CROSS_AXIS_GRIP * (1. - slide * (1. - Self::SLIDING_GRIP))
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -45,6 +87,7 @@ pub struct WheelBundle {
     layer: CollisionLayers,
     mass: Mass,
     sleeping: Sleeping,
+    drive_force: DriveForce,
 }
 
 impl WheelBundle {
@@ -60,6 +103,7 @@ impl WheelBundle {
             layer: CollisionLayers::new(Wheel::COLLISION_LAYER, LayerMask::NONE),
             mass: Wheel::MASS,
             sleeping: Sleeping,
+            drive_force: DriveForce::default(),
         }
     }
 }
@@ -82,8 +126,8 @@ impl FrontWheelJointBundle {
             joint: RevoluteJoint::new(car, wheel)
                 .with_local_anchor_1(offset)
                 .with_angle_limits(
-                    -Car::MAX_STEERING_DEG.to_radians(),
-                    Car::MAX_STEERING_DEG.to_radians(),
+                    -Car::MAX_WHEEL_ANGLE_DEG.to_radians(),
+                    Car::MAX_WHEEL_ANGLE_DEG.to_radians(),
                 ),
             car: PartOfCar(car),
         }
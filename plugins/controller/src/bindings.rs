@@ -0,0 +1,277 @@
+use std::{collections::HashMap, fmt};
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{CarControl, Controller};
+
+/// A single input that can trigger a [`CarControl`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Reflect, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    GamepadButton(GamepadButtonType),
+    /// Pushing a stick or trigger one way along an axis.
+    GamepadAxis {
+        axis: GamepadAxisType,
+        positive: bool,
+    },
+}
+
+impl Binding {
+    fn insert_into(&self, input_map: &mut InputMap<CarControl>, action: CarControl) {
+        match *self {
+            Binding::Key(key) => {
+                input_map.insert(action, key);
+            }
+            Binding::GamepadButton(button) => {
+                input_map.insert(action, button);
+            }
+            Binding::GamepadAxis { axis, positive } => {
+                let direction = if positive {
+                    GamepadControlDirection::positive(axis)
+                } else {
+                    GamepadControlDirection::negative(axis)
+                };
+                input_map.insert(action, direction);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{key:?}"),
+            Binding::GamepadButton(button) => write!(f, "Pad {button:?}"),
+            Binding::GamepadAxis { axis, positive } => {
+                write!(f, "Pad {axis:?}{}", if *positive { "+" } else { "-" })
+            }
+        }
+    }
+}
+
+/// Two local players that would both be driven by the same input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BindingConflict {
+    pub binding: Binding,
+    pub first: (Controller, CarControl),
+    pub second: (Controller, CarControl),
+}
+
+/// Which inputs drive each [`CarControl`] for each [`Controller`].
+///
+/// Changing this resource updates the input maps of every car already on the track.
+#[derive(Clone, Debug, Default)]
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlBindings {
+    // anything missing falls back to the controller's default layout
+    overrides: HashMap<Controller, HashMap<CarControl, Vec<Binding>>>,
+    /// Which gamepad each controller listens to, by id.
+    gamepads: HashMap<Controller, usize>,
+}
+
+impl ControlBindings {
+    pub fn bindings(&self, controller: Controller, action: CarControl) -> Vec<Binding> {
+        self.overrides
+            .get(&controller)
+            .and_then(|actions| actions.get(&action))
+            .cloned()
+            .unwrap_or_else(|| controller.default_bindings(action))
+    }
+
+    /// Replaces the bindings for an action, unless another controller is already using one of
+    /// them, in which case that conflict is returned and nothing changes.
+    pub fn rebind(
+        &mut self,
+        controller: Controller,
+        action: CarControl,
+        bindings: Vec<Binding>,
+    ) -> Result<(), BindingConflict> {
+        for binding in &bindings {
+            if let Some(conflict) = self.find_conflict(controller, action, *binding) {
+                return Err(conflict);
+            }
+        }
+        self.overrides
+            .entry(controller)
+            .or_default()
+            .insert(action, bindings);
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.overrides.clear();
+        self.gamepads.clear();
+    }
+
+    /// The gamepad whose buttons and sticks drive this controller, which is the first pad for the
+    /// first controller, the second for the second and so on unless picked otherwise.
+    pub fn gamepad(&self, controller: Controller) -> Gamepad {
+        let id = self.gamepads.get(&controller).copied().unwrap_or_else(|| {
+            Controller::ALL
+                .iter()
+                .position(|other| *other == controller)
+                .unwrap_or_default()
+        });
+        Gamepad::new(id)
+    }
+
+    /// Moves a controller onto another gamepad, unless a gamepad input it is bound to is already
+    /// used by another controller on that pad, in which case that conflict is returned and
+    /// nothing changes.
+    pub fn set_gamepad(
+        &mut self,
+        controller: Controller,
+        gamepad: Gamepad,
+    ) -> Result<(), BindingConflict> {
+        let previous = self.gamepad(controller);
+        self.gamepads.insert(controller, gamepad.id);
+        match self
+            .conflicts()
+            .into_iter()
+            .find(|conflict| conflict.first.0 == controller || conflict.second.0 == controller)
+        {
+            Some(conflict) => {
+                self.gamepads.insert(controller, previous.id);
+                Err(conflict)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Every input that is bound for more than one local player.
+    pub fn conflicts(&self) -> Vec<BindingConflict> {
+        let mut conflicts = vec![];
+        for controller in Controller::ALL {
+            for action in CarControl::ALL {
+                for binding in self.bindings(controller, action) {
+                    let Some(conflict) = self.find_conflict(controller, action, binding) else {
+                        continue;
+                    };
+                    // each pair would otherwise be found from both sides
+                    let is_new = !conflicts.iter().any(|known: &BindingConflict| {
+                        known.binding == conflict.binding && known.second == conflict.first
+                    });
+                    if is_new {
+                        conflicts.push(conflict);
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+
+    fn find_conflict(
+        &self,
+        controller: Controller,
+        action: CarControl,
+        binding: Binding,
+    ) -> Option<BindingConflict> {
+        // gamepad inputs are only shared when both controllers listen to the same pad
+        let is_shared = |other: Controller| match binding {
+            Binding::Key(_) => true,
+            _ => self.gamepad(other) == self.gamepad(controller),
+        };
+        Controller::ALL
+            .into_iter()
+            .filter(|other| *other != controller && is_shared(*other))
+            .flat_map(|other| CarControl::ALL.map(|other_action| (other, other_action)))
+            .find(|(other, other_action)| self.bindings(*other, *other_action).contains(&binding))
+            .map(|second| BindingConflict {
+                binding,
+                first: (controller, action),
+                second,
+            })
+    }
+
+    pub fn input_map(&self, controller: Controller) -> InputMap<CarControl> {
+        let mut input_map = InputMap::default();
+        input_map.set_gamepad(self.gamepad(controller));
+        for action in CarControl::ALL {
+            for binding in self.bindings(controller, action) {
+                binding.insert_into(&mut input_map, action);
+            }
+        }
+        input_map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebind_conflicts() {
+        let mut bindings = ControlBindings::default();
+        assert!(bindings.conflicts().is_empty());
+
+        // taking another player's key is refused and leaves everything as it was
+        let taken = Binding::Key(KeyCode::KeyW);
+        let conflict = bindings
+            .rebind(Controller::ArrowKeys, CarControl::Boost, vec![taken])
+            .unwrap_err();
+        assert_eq!(conflict.binding, taken);
+        assert_eq!(conflict.first, (Controller::ArrowKeys, CarControl::Boost));
+        assert_eq!(
+            conflict.second,
+            (Controller::WASDKeys, CarControl::Accelerate)
+        );
+        assert_eq!(
+            bindings.bindings(Controller::ArrowKeys, CarControl::Boost),
+            vec![Binding::Key(KeyCode::ShiftRight)]
+        );
+
+        let free = Binding::Key(KeyCode::KeyB);
+        bindings
+            .rebind(Controller::ArrowKeys, CarControl::Boost, vec![free])
+            .unwrap();
+        assert_eq!(
+            bindings.bindings(Controller::ArrowKeys, CarControl::Boost),
+            vec![free]
+        );
+        assert!(bindings.conflicts().is_empty());
+    }
+
+    #[test]
+    fn test_gamepad_conflicts() {
+        let mut bindings = ControlBindings::default();
+        assert_ne!(
+            bindings.gamepad(Controller::ArrowKeys),
+            bindings.gamepad(Controller::WASDKeys)
+        );
+
+        // the same button on two different pads belongs to two different players
+        let button = Binding::GamepadButton(GamepadButtonType::South);
+        for controller in Controller::ALL {
+            bindings
+                .rebind(controller, CarControl::Accelerate, vec![button])
+                .unwrap();
+        }
+        assert!(bindings.conflicts().is_empty());
+
+        // sharing a pad makes it one input for both, which is found once
+        let conflict = bindings
+            .set_gamepad(
+                Controller::WASDKeys,
+                bindings.gamepad(Controller::ArrowKeys),
+            )
+            .unwrap_err();
+        assert_eq!(conflict.binding, button);
+        assert_eq!(bindings.gamepad(Controller::WASDKeys), Gamepad::new(1));
+
+        bindings.gamepads.insert(Controller::WASDKeys, 0);
+        let conflicts = bindings.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].first,
+            (Controller::ArrowKeys, CarControl::Accelerate)
+        );
+        assert_eq!(
+            conflicts[0].second,
+            (Controller::WASDKeys, CarControl::Accelerate)
+        );
+    }
+}
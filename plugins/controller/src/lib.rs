@@ -1,27 +1,61 @@
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use car::{AccelerateAction, Car, DrivingSystems, SteerAction};
+use car::{
+    AccelerateAction, BoostAction, Car, DrivingSystems, HandbrakeAction, ResetAction, SteerAction,
+};
+
+mod bindings;
+pub use bindings::*;
+
+#[cfg(feature = "touch")]
+mod touch;
+#[cfg(feature = "touch")]
+pub use touch::*;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-#[derive(Reflect)]
+#[derive(Reflect, Serialize, Deserialize)]
 #[derive(Actionlike)]
 pub enum CarControl {
     Accelerate,
     Brake,
     TurnLeft,
     TurnRight,
+    Handbrake,
+    Boost,
+    Reset,
+}
+
+impl CarControl {
+    pub const ALL: [CarControl; 7] = [
+        CarControl::Accelerate,
+        CarControl::Brake,
+        CarControl::TurnLeft,
+        CarControl::TurnRight,
+        CarControl::Handbrake,
+        CarControl::Boost,
+        CarControl::Reset,
+    ];
 }
 
 pub struct CarControllerPlugin;
 
 impl Plugin for CarControllerPlugin {
     fn build(&self, app: &mut App) {
+        #[cfg(feature = "touch")]
+        app.add_plugins(touch::TouchControlsPlugin);
+
         app.add_plugins(InputManagerPlugin::<CarControl>::default())
+            .init_resource::<ControlBindings>()
             .configure_sets(Update, CarControlSystems.before(DrivingSystems))
             .add_systems(
                 Update,
-                (Self::add_controller, Self::handle_controls)
+                (
+                    Self::add_controller,
+                    Self::apply_bindings,
+                    Self::handle_controls,
+                )
                     .chain()
                     .in_set(CarControlSystems),
             );
@@ -32,14 +66,27 @@ impl CarControllerPlugin {
     fn add_controller(
         mut commands: Commands,
         car_query: Query<(Entity, &Controller), Without<InputMap<CarControl>>>,
+        bindings: Res<ControlBindings>,
     ) {
         for (car, controller) in &car_query {
             commands.entity(car).insert(InputManagerBundle::with_map(
-                controller.leafwing_input_map(),
+                bindings.input_map(*controller),
             ));
         }
     }
 
+    fn apply_bindings(
+        bindings: Res<ControlBindings>,
+        mut car_query: Query<(&Controller, &mut InputMap<CarControl>)>,
+    ) {
+        if !bindings.is_changed() {
+            return;
+        }
+        for (controller, mut input_map) in &mut car_query {
+            *input_map = bindings.input_map(*controller);
+        }
+    }
+
     fn handle_controls(
         mut commands: Commands,
         car_query: Query<(Entity, &ActionState<CarControl>), With<Car>>,
@@ -57,6 +104,18 @@ impl CarControllerPlugin {
                     .insert(AccelerateAction::Backward);
             }
 
+            if action_state.pressed(&CarControl::Handbrake) {
+                commands.entity(car_entity).insert(HandbrakeAction);
+            }
+
+            if action_state.pressed(&CarControl::Boost) {
+                commands.entity(car_entity).insert(BoostAction);
+            }
+
+            if action_state.just_pressed(&CarControl::Reset) {
+                commands.entity(car_entity).insert(ResetAction);
+            }
+
             let mut steering_angle: f32 = 0.;
             if action_state.pressed(&CarControl::TurnLeft) {
                 steering_angle += 1.;
@@ -76,28 +135,33 @@ impl CarControllerPlugin {
 #[derive(SystemSet)]
 pub struct CarControlSystems;
 
-#[derive(Clone, Copy, Debug)]
-#[derive(Component, Reflect)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Component, Reflect, Serialize, Deserialize)]
 pub enum Controller {
     ArrowKeys,
     WASDKeys,
 }
 
 impl Controller {
-    fn leafwing_input_map(&self) -> InputMap<CarControl> {
-        match self {
-            Controller::WASDKeys => InputMap::new([
-                (CarControl::Accelerate, KeyCode::KeyW),
-                (CarControl::Brake, KeyCode::KeyS),
-                (CarControl::TurnLeft, KeyCode::KeyA),
-                (CarControl::TurnRight, KeyCode::KeyD),
-            ]),
-            Controller::ArrowKeys => InputMap::new([
-                (CarControl::Accelerate, KeyCode::ArrowUp),
-                (CarControl::Brake, KeyCode::ArrowDown),
-                (CarControl::TurnLeft, KeyCode::ArrowLeft),
-                (CarControl::TurnRight, KeyCode::ArrowRight),
-            ]),
-        }
+    pub const ALL: [Controller; 2] = [Controller::ArrowKeys, Controller::WASDKeys];
+
+    pub fn default_bindings(&self, action: CarControl) -> Vec<Binding> {
+        let key = match (self, action) {
+            (Controller::WASDKeys, CarControl::Accelerate) => KeyCode::KeyW,
+            (Controller::WASDKeys, CarControl::Brake) => KeyCode::KeyS,
+            (Controller::WASDKeys, CarControl::TurnLeft) => KeyCode::KeyA,
+            (Controller::WASDKeys, CarControl::TurnRight) => KeyCode::KeyD,
+            (Controller::WASDKeys, CarControl::Handbrake) => KeyCode::ShiftLeft,
+            (Controller::WASDKeys, CarControl::Boost) => KeyCode::KeyE,
+            (Controller::WASDKeys, CarControl::Reset) => KeyCode::KeyR,
+            (Controller::ArrowKeys, CarControl::Accelerate) => KeyCode::ArrowUp,
+            (Controller::ArrowKeys, CarControl::Brake) => KeyCode::ArrowDown,
+            (Controller::ArrowKeys, CarControl::TurnLeft) => KeyCode::ArrowLeft,
+            (Controller::ArrowKeys, CarControl::TurnRight) => KeyCode::ArrowRight,
+            (Controller::ArrowKeys, CarControl::Handbrake) => KeyCode::Space,
+            (Controller::ArrowKeys, CarControl::Boost) => KeyCode::ShiftRight,
+            (Controller::ArrowKeys, CarControl::Reset) => KeyCode::Enter,
+        };
+        vec![Binding::Key(key)]
     }
 }
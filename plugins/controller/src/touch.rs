@@ -0,0 +1,211 @@
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use crate::{CarControl, CarControlSystems, Controller};
+
+/// On-screen buttons that drive a car with touch input.
+///
+/// The buttons stay hidden until the first touch, and every finger on the screen counts, so
+/// steering and the pedals can be held at the same time.
+pub struct TouchControlsPlugin;
+
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchControls>()
+            .add_systems(Startup, Self::spawn_touch_zones)
+            .add_systems(
+                Update,
+                (Self::show_touch_zones, Self::press_touched_zones)
+                    .chain()
+                    .in_set(CarControlSystems)
+                    .before(crate::CarControllerPlugin::handle_controls),
+            );
+        app.register_type::<TouchControls>();
+    }
+}
+
+impl TouchControlsPlugin {
+    const SIZE: f32 = 96.;
+    const MARGIN: f32 = 24.;
+
+    fn spawn_touch_zones(mut commands: Commands) {
+        let zone = |action: CarControl,
+                    label: &str,
+                    left: Option<f32>,
+                    right: Option<f32>,
+                    bottom: f32| {
+            (
+                TouchZone(action),
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: left.map_or(Val::Auto, Val::Px),
+                        right: right.map_or(Val::Auto, Val::Px),
+                        bottom: Val::Px(bottom),
+                        width: Val::Px(Self::SIZE),
+                        height: Val::Px(Self::SIZE),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        border: UiRect::all(Val::Px(3.)),
+                        ..Default::default()
+                    },
+                    border_color: Color::srgba(1., 1., 1., 0.5).into(),
+                    background_color: TouchZone::IDLE.into(),
+                    ..Default::default()
+                },
+                Name::new(format!("Touch {label}")),
+            )
+        };
+        let label = |text: &str| {
+            TextBundle::from_section(
+                text,
+                TextStyle {
+                    font_size: 24.,
+                    color: Color::WHITE,
+                    ..Default::default()
+                },
+            )
+        };
+        let step = Self::SIZE + Self::MARGIN;
+        let zones = [
+            (
+                CarControl::TurnLeft,
+                "<",
+                Some(Self::MARGIN),
+                None,
+                Self::MARGIN,
+            ),
+            (
+                CarControl::TurnRight,
+                ">",
+                Some(Self::MARGIN + step),
+                None,
+                Self::MARGIN,
+            ),
+            (
+                CarControl::Accelerate,
+                "GAS",
+                None,
+                Some(Self::MARGIN),
+                Self::MARGIN,
+            ),
+            (
+                CarControl::Brake,
+                "BRAKE",
+                None,
+                Some(Self::MARGIN + step),
+                Self::MARGIN,
+            ),
+            (
+                CarControl::Boost,
+                "BOOST",
+                None,
+                Some(Self::MARGIN),
+                Self::MARGIN + step,
+            ),
+            (
+                CarControl::Handbrake,
+                "DRIFT",
+                None,
+                Some(Self::MARGIN + step),
+                Self::MARGIN + step,
+            ),
+        ];
+        commands
+            .spawn((
+                Name::new("Touch Controls"),
+                TouchControlsRoot,
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                        ..Default::default()
+                    },
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                },
+            ))
+            .with_children(|builder| {
+                for (action, text, left, right, bottom) in zones {
+                    builder
+                        .spawn(zone(action, text, left, right, bottom))
+                        .with_children(|builder| {
+                            builder.spawn(label(text));
+                        });
+                }
+            });
+    }
+
+    fn show_touch_zones(
+        touches: Res<Touches>,
+        mut roots: Query<&mut Visibility, With<TouchControlsRoot>>,
+    ) {
+        if touches.any_just_pressed() {
+            for mut visibility in &mut roots {
+                *visibility = Visibility::Inherited;
+            }
+        }
+    }
+
+    fn press_touched_zones(
+        touches: Res<Touches>,
+        settings: Res<TouchControls>,
+        mut zones: Query<(&TouchZone, &Node, &GlobalTransform, &mut BackgroundColor)>,
+        mut cars: Query<(&Controller, &mut ActionState<CarControl>)>,
+    ) {
+        let mut pressed = vec![];
+        for (zone, node, transform, mut color) in &mut zones {
+            let rect = node.logical_rect(transform);
+            let is_touched = touches.iter().any(|touch| rect.contains(touch.position()));
+            *color = if is_touched {
+                TouchZone::PRESSED
+            } else {
+                TouchZone::IDLE
+            }
+            .into();
+            if is_touched {
+                pressed.push(zone.0);
+            }
+        }
+        if pressed.is_empty() {
+            return;
+        }
+        for (controller, mut action_state) in &mut cars {
+            if *controller != settings.controller {
+                continue;
+            }
+            for action in &pressed {
+                action_state.press(action);
+            }
+        }
+    }
+}
+
+/// Which local player the touch buttons drive.
+#[derive(Clone, Copy, Debug)]
+#[derive(Resource, Reflect)]
+pub struct TouchControls {
+    pub controller: Controller,
+}
+
+impl Default for TouchControls {
+    fn default() -> Self {
+        Self {
+            controller: Controller::ArrowKeys,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct TouchControlsRoot;
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Component)]
+struct TouchZone(CarControl);
+
+impl TouchZone {
+    const IDLE: Color = Color::srgba(0.2, 0.2, 0.2, 0.35);
+    const PRESSED: Color = Color::srgba(0.9, 0.9, 0.9, 0.5);
+}
@@ -7,11 +7,21 @@ pub use bevy_rand;
 pub use bevy_rand::prelude::ForkableRng;
 pub use rand_core::RngCore;
 
-pub struct EntropyPlugin;
+#[derive(Default)]
+pub struct EntropyPlugin {
+    /// Seeds the global source so every run plays out the same, rather than seeding it from the
+    /// OS.
+    pub seed: Option<u64>,
+}
 
 impl Plugin for EntropyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(RandEntropyPlugin::<WyRand>::default());
+        match self.seed {
+            Some(seed) => {
+                app.add_plugins(RandEntropyPlugin::<WyRand>::with_seed(seed.to_le_bytes()))
+            }
+            None => app.add_plugins(RandEntropyPlugin::<WyRand>::default()),
+        };
     }
 }
 
@@ -0,0 +1,214 @@
+use std::time::Duration;
+
+use avian2d::prelude::{ExternalImpulse, Physics};
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+
+use crate::{BombTagIt, CanBeIt, LapTagSystems, TagCourier, TagEvent, TagIt, TagPlugin};
+
+/// Gives the bomb a countdown that eliminates its holder when it runs out.
+///
+/// Only active while a [`BombFuseRules`] resource exists.
+pub struct BombFusePlugin {
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for BombFusePlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
+
+impl Plugin for BombFusePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FuseTick>()
+            .add_event::<BombExploded>()
+            .add_systems(
+                self.schedule,
+                (Self::light_fuses, Self::burn_fuses)
+                    .chain()
+                    .after(TagPlugin::<BombTagIt>::transfer_tag)
+                    .in_set(LapTagSystems)
+                    .run_if(resource_exists::<BombFuseRules>),
+            );
+        app.register_type::<BombFuse>()
+            .register_type::<BombFuseRules>()
+            .register_type::<FuseTransfer>()
+            .register_type::<FuseTick>()
+            .register_type::<BombExploded>();
+    }
+}
+
+impl BombFusePlugin {
+    fn light_fuses(
+        mut commands: Commands,
+        rules: Res<BombFuseRules>,
+        new_bombs: Query<Entity, Added<BombTagIt>>,
+        fuses: Query<&BombFuse>,
+        mut tags: EventReader<TagEvent>,
+        mut removed_bombs: RemovedComponents<BombTagIt>,
+    ) {
+        let transfers = tags.read().copied().collect::<Vec<_>>();
+        for entity in &new_bombs {
+            let inherited_fuse = match rules.on_transfer {
+                FuseTransfer::Reset => None,
+                FuseTransfer::Continue => transfers
+                    .iter()
+                    .filter(|tag| tag.next_it == entity)
+                    .find_map(|tag| fuses.get(tag.prev_it).ok()),
+            };
+            commands.entity(entity).insert(
+                inherited_fuse
+                    .copied()
+                    .unwrap_or_else(|| BombFuse::new(rules.duration)),
+            );
+        }
+        for entity in removed_bombs.read() {
+            if let Some(mut entity) = commands.get_entity(entity) {
+                entity.remove::<BombFuse>();
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn burn_fuses(
+        mut commands: Commands,
+        rules: Res<BombFuseRules>,
+        // a courier only carries the bomb along, so the fuse waits for the next car
+        mut fuses: Query<
+            (Entity, &mut BombFuse, &Transform),
+            (With<BombTagIt>, Without<TagCourier>),
+        >,
+        mut cars: Query<(Entity, &Transform, &mut ExternalImpulse), With<CanBeIt>>,
+        mut ticks: EventWriter<FuseTick>,
+        mut explosions: EventWriter<BombExploded>,
+        time: Res<Time<Physics>>,
+    ) {
+        for (holder, mut fuse, transform) in &mut fuses {
+            match fuse.burn(time.delta()) {
+                FuseState::Burning => {}
+                FuseState::Ticked => {
+                    ticks.send(FuseTick { holder });
+                }
+                FuseState::Exploded => {
+                    let position = transform.translation.xy();
+                    for (car, car_transform, mut impulse) in &mut cars {
+                        if car == holder {
+                            continue;
+                        }
+                        let offset = car_transform.translation.xy() - position;
+                        let distance = offset.length();
+                        if distance >= rules.blast_radius {
+                            continue;
+                        }
+                        let falloff = 1. - distance / rules.blast_radius;
+                        **impulse += offset.normalize_or_zero() * rules.blast_impulse * falloff;
+                    }
+                    commands.entity(holder).add(BombTagIt::finish_lap());
+                    explosions.send(BombExploded { holder, position });
+                }
+            }
+        }
+    }
+}
+
+/// Whether a bomb's countdown carries over when it changes hands.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Reflect)]
+pub enum FuseTransfer {
+    /// Every new holder gets a freshly lit fuse.
+    Reset,
+    /// The fuse keeps burning down as the bomb is passed around.
+    #[default]
+    Continue,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Resource, Reflect)]
+pub struct BombFuseRules {
+    pub duration: Duration,
+    pub on_transfer: FuseTransfer,
+    pub blast_radius: f32,
+    pub blast_impulse: f32,
+}
+
+impl Default for BombFuseRules {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(30),
+            on_transfer: FuseTransfer::default(),
+            blast_radius: 300.,
+            blast_impulse: 1.2e5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Component, Reflect)]
+pub struct BombFuse {
+    remaining: Duration,
+    duration: Duration,
+    until_tick: Duration,
+}
+
+impl BombFuse {
+    const SLOWEST_TICK: Duration = Duration::from_millis(1000);
+    const FASTEST_TICK: Duration = Duration::from_millis(100);
+
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            remaining: duration,
+            duration,
+            until_tick: Duration::ZERO,
+        }
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.remaining
+    }
+
+    pub fn fraction_remaining(&self) -> f32 {
+        if self.duration.is_zero() {
+            return 0.;
+        }
+        self.remaining.as_secs_f32() / self.duration.as_secs_f32()
+    }
+
+    fn burn(&mut self, delta: Duration) -> FuseState {
+        self.remaining = self.remaining.saturating_sub(delta);
+        if self.remaining.is_zero() {
+            return FuseState::Exploded;
+        }
+        self.until_tick = self.until_tick.saturating_sub(delta);
+        if !self.until_tick.is_zero() {
+            return FuseState::Burning;
+        }
+        // ticks speed up as the fuse burns down
+        self.until_tick = Self::FASTEST_TICK
+            + (Self::SLOWEST_TICK - Self::FASTEST_TICK).mul_f32(self.fraction_remaining());
+        FuseState::Ticked
+    }
+}
+
+enum FuseState {
+    Burning,
+    Ticked,
+    Exploded,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Event, Reflect)]
+pub struct FuseTick {
+    pub holder: Entity,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Event, Reflect)]
+pub struct BombExploded {
+    pub holder: Entity,
+    pub position: Vec2,
+}
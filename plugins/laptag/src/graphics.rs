@@ -7,7 +7,7 @@ use bevy_asset_loader::prelude::{
 };
 use bevy_reactive_blueprints::{Blueprint, BlueprintPlugin, FromBlueprint};
 
-use crate::{BombTagIt, LapTagIt, LapTagSystems};
+use crate::{BombFuse, BombTagIt, HeldTags, LapTagIt, LapTagSystems, TagKindRegistry, TagKinds};
 
 pub struct GraphicsPlugin;
 
@@ -23,10 +23,19 @@ impl Plugin for GraphicsPlugin {
             )
             .add_systems(
                 Update,
-                (Self::track_score_tags, Self::track_bomb_tags).after(LapTagSystems),
+                (
+                    Self::track_score_tags,
+                    Self::track_bomb_tags,
+                    (Self::track_fuse_gauges, Self::update_fuse_gauges).chain(),
+                    Self::track_held_tags.run_if(resource_exists::<TagKindRegistry>),
+                )
+                    .after(LapTagSystems),
             );
         app.register_type::<Bomb>()
             .register_type::<BombGraphic>()
+            .register_type::<FuseGauge>()
+            .register_type::<FuseGaugeGraphic>()
+            .register_type::<HeldTagIcons>()
             .register_type::<Flag>()
             .register_type::<FlagGraphic>();
     }
@@ -76,6 +85,112 @@ impl GraphicsPlugin {
                 .add_child(graphic);
         }
     }
+
+    fn track_fuse_gauges(
+        mut commands: Commands,
+        new_fuses: Query<Entity, Added<BombFuse>>,
+        gauges: Query<&FuseGaugeGraphic>,
+        mut removed_fuses: RemovedComponents<BombFuse>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        for entity in removed_fuses.read() {
+            let Ok(FuseGaugeGraphic(graphic)) = gauges.get(entity) else {
+                continue;
+            };
+            commands.entity(*graphic).despawn_recursive();
+            commands.entity(entity).remove::<FuseGaugeGraphic>();
+        }
+        for entity in &new_fuses {
+            let graphic = commands
+                .spawn((
+                    FuseGauge,
+                    ColorMesh2dBundle {
+                        material: materials.add(FuseGauge::color(1.)),
+                        mesh: meshes.add(Rectangle::new(36., 6.).mesh()).into(),
+                        transform: Transform::from_xyz(0., 0., 2.),
+                        ..Default::default()
+                    },
+                ))
+                .id();
+            commands
+                .entity(entity)
+                .insert(FuseGaugeGraphic(graphic))
+                .add_child(graphic);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn track_held_tags(
+        mut commands: Commands,
+        holders: Query<(Entity, &HeldTags, Option<&HeldTagIcons>), Changed<HeldTags>>,
+        registry: Res<TagKindRegistry>,
+        kinds: Res<Assets<TagKinds>>,
+        server: Res<AssetServer>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        let Some(kinds) = kinds.get(&registry.0) else {
+            return;
+        };
+        for (entity, held, icons) in &holders {
+            for icon in icons.iter().flat_map(|icons| icons.0.iter()) {
+                commands.entity(*icon).despawn_recursive();
+            }
+            let paths = held
+                .iter()
+                .filter_map(|id| kinds.get(id))
+                .filter_map(|kind| kind.icon.clone())
+                .collect::<Vec<_>>();
+            // line the icons up across the back of the car
+            let first_offset = (paths.len() as f32 - 1.) / 2. * HeldTagIcons::SPACING;
+            let icons = paths
+                .into_iter()
+                .enumerate()
+                .map(|(index, path)| {
+                    commands
+                        .spawn(ColorMesh2dBundle {
+                            material: materials.add(ColorMaterial {
+                                color: Color::WHITE,
+                                texture: Some(server.load(path)),
+                            }),
+                            mesh: meshes
+                                .add(Rectangle::new(HeldTagIcons::SIZE, HeldTagIcons::SIZE).mesh())
+                                .into(),
+                            transform: Transform::from_xyz(
+                                -24.,
+                                first_offset - index as f32 * HeldTagIcons::SPACING,
+                                1.,
+                            )
+                            .with_rotation(Quat::from_rotation_z(std::f32::consts::PI)),
+                            ..Default::default()
+                        })
+                        .id()
+                })
+                .collect::<Vec<_>>();
+            commands
+                .entity(entity)
+                .push_children(&icons)
+                .insert(HeldTagIcons(icons));
+        }
+    }
+
+    fn update_fuse_gauges(
+        fuses: Query<(&BombFuse, &FuseGaugeGraphic)>,
+        mut gauges: Query<(&mut Transform, &Handle<ColorMaterial>), With<FuseGauge>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        for (fuse, FuseGaugeGraphic(graphic)) in &fuses {
+            let Ok((mut transform, material)) = gauges.get_mut(*graphic) else {
+                continue;
+            };
+            let fraction = fuse.fraction_remaining();
+            transform.scale.x = fraction.max(0.01);
+            if let Some(material) = materials.get_mut(material) {
+                material.color = FuseGauge::color(fraction);
+            }
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
@@ -166,3 +281,27 @@ impl FromBlueprint<Bomb> for BombGraphicsBundle {
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Reflect)]
+struct FuseGauge;
+
+impl FuseGauge {
+    // burns from yellow to red
+    fn color(fraction_remaining: f32) -> Color {
+        Color::srgb(1., 0.9 * fraction_remaining, 0.)
+    }
+}
+
+#[derive(Clone, Debug)]
+#[derive(Component, Reflect)]
+struct FuseGaugeGraphic(Entity);
+
+#[derive(Clone, Debug, Default)]
+#[derive(Component, Reflect)]
+struct HeldTagIcons(Vec<Entity>);
+
+impl HeldTagIcons {
+    const SIZE: f32 = 20.;
+    const SPACING: f32 = 22.;
+}
@@ -0,0 +1,430 @@
+use std::time::Duration;
+
+use avian2d::prelude::{CollisionStarted, LinearVelocity};
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    ecs::{
+        query::QueryData,
+        schedule::{InternedScheduleLabel, ScheduleLabel},
+        system::EntityCommand,
+    },
+    prelude::*,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use entropy::{GlobalEntropy, RngCore};
+use track::{CheckpointTracker, LapComplete};
+
+use crate::{
+    eliminate, BombTagIt, CanBeIt, Eliminated, LapTagIt, LapTagSystems, RespawnRequested, Score,
+    Shielded, TagCourier, TagEvent, TagImmunity, Team,
+};
+
+/// Loads tags described in a `.tags.ron` file and plays them alongside the built-in flag and bomb.
+pub struct TagKindsPlugin {
+    pub path: String,
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for TagKindsPlugin {
+    fn default() -> Self {
+        Self {
+            path: "tags/default.tags.ron".to_string(),
+            schedule: Update.intern(),
+        }
+    }
+}
+
+impl Plugin for TagKindsPlugin {
+    fn build(&self, app: &mut App) {
+        let path = self.path.clone();
+        app.init_asset::<TagKinds>()
+            .init_asset_loader::<TagKindsLoader>()
+            .add_systems(
+                Startup,
+                move |mut commands: Commands, server: Res<AssetServer>| {
+                    commands.insert_resource(TagKindRegistry(server.load(path.clone())));
+                },
+            )
+            .add_systems(
+                self.schedule,
+                (
+                    Self::equip_cars,
+                    Self::seat_tags,
+                    Self::transfer_tags,
+                    Self::complete_laps,
+                )
+                    .chain()
+                    .in_set(LapTagSystems)
+                    .run_if(resource_exists::<TagKindRegistry>),
+            );
+        app.register_type::<HeldTags>()
+            .register_type::<TagKindId>()
+            .register_type::<TagKind>()
+            .register_type::<TagTransfer>()
+            .register_type::<LapEffect>();
+    }
+}
+
+impl TagKindsPlugin {
+    fn equip_cars(mut commands: Commands, cars: Query<Entity, (With<CanBeIt>, Without<HeldTags>)>) {
+        for car in &cars {
+            commands.entity(car).insert(HeldTags::default());
+        }
+    }
+
+    // hand out tags until each kind is held by as many cars as it allows
+    fn seat_tags(
+        mut commands: Commands,
+        registry: Res<TagKindRegistry>,
+        kinds: Res<Assets<TagKinds>>,
+        mut cars: Query<(Entity, &mut HeldTags), (With<CanBeIt>, With<Score>, Without<Eliminated>)>,
+        mut entropy: ResMut<GlobalEntropy>,
+    ) {
+        let Some(kinds) = kinds.get(&registry.0) else {
+            return;
+        };
+        for (id, kind) in kinds.iter() {
+            let holders = cars.iter().filter(|(_, held)| held.contains(id)).count();
+            for _ in holders..kind.max_holders {
+                let candidates = cars
+                    .iter()
+                    .filter(|(_, held)| !held.contains(id))
+                    .map(|(car, _)| car)
+                    .collect::<Vec<_>>();
+                if candidates.is_empty() {
+                    break;
+                }
+                let car = candidates[entropy.next_u32() as usize % candidates.len()];
+                let Ok((_, mut held)) = cars.get_mut(car) else {
+                    continue;
+                };
+                held.insert(id);
+                commands.entity(car).insert(TagImmunity::default());
+                if !kind.on_lap.is_empty() {
+                    commands.entity(car).insert(CheckpointTracker::default());
+                }
+            }
+        }
+    }
+
+    fn transfer_tags(
+        mut commands: Commands,
+        registry: Res<TagKindRegistry>,
+        kinds: Res<Assets<TagKinds>>,
+        mut collisions: EventReader<CollisionStarted>,
+        mut carriers: Query<TagCarrier, With<CanBeIt>>,
+        mut tags: EventWriter<TagEvent>,
+    ) {
+        let Some(kinds) = kinds.get(&registry.0) else {
+            return;
+        };
+        for CollisionStarted(entity1, entity2) in collisions.read() {
+            let Ok([mut carrier1, mut carrier2]) = carriers.get_many_mut([*entity1, *entity2])
+            else {
+                continue;
+            };
+            // decide both directions before moving anything, so tags don't bounce straight back
+            let passed_forward = kinds.passable(&carrier1, &carrier2);
+            let passed_back = kinds.passable(&carrier2, &carrier1);
+            let mut pass = |giver: &mut TagCarrierItem,
+                            receiver: &mut TagCarrierItem,
+                            passed: Vec<TagKindId>| {
+                if passed.is_empty() {
+                    return;
+                }
+                // only tags that do something on a lap start the receiver's lap over
+                let counts_laps = passed
+                    .iter()
+                    .filter_map(|id| kinds.get(*id))
+                    .any(|kind| !kind.on_lap.is_empty());
+                for id in passed {
+                    giver.held.remove(id);
+                    receiver.held.insert(id);
+                }
+                commands
+                    .entity(receiver.entity)
+                    .insert(TagImmunity::default());
+                if counts_laps && !receiver.courier {
+                    commands
+                        .entity(receiver.entity)
+                        .insert(CheckpointTracker::default());
+                }
+                if giver.held.is_empty() && !giver.lap_tag && !giver.bomb_tag && !giver.courier {
+                    commands.entity(giver.entity).remove::<CheckpointTracker>();
+                }
+                tags.send(TagEvent {
+                    prev_it: giver.entity,
+                    next_it: receiver.entity,
+                });
+            };
+            pass(&mut carrier1, &mut carrier2, passed_forward);
+            pass(&mut carrier2, &mut carrier1, passed_back);
+        }
+    }
+
+    fn complete_laps(
+        mut commands: Commands,
+        registry: Res<TagKindRegistry>,
+        kinds: Res<Assets<TagKinds>>,
+        mut completed_laps: EventReader<LapComplete>,
+        racers: Query<&HeldTags, (With<CheckpointTracker>, Without<TagCourier>)>,
+    ) {
+        let Some(kinds) = kinds.get(&registry.0) else {
+            return;
+        };
+        for lap in completed_laps.read() {
+            let Ok(held) = racers.get(lap.racer) else {
+                continue;
+            };
+            for kind in held.iter().filter_map(|id| kinds.get(id)) {
+                for effect in kind.on_lap.iter() {
+                    commands.entity(lap.racer).add(effect.command());
+                }
+            }
+        }
+    }
+}
+
+#[derive(QueryData)]
+#[query_data(mutable)]
+struct TagCarrier {
+    entity: Entity,
+    held: &'static mut HeldTags,
+    immune: Has<TagImmunity>,
+    shielded: Has<Shielded>,
+    lap_tag: Has<LapTagIt>,
+    bomb_tag: Has<BombTagIt>,
+    courier: Has<TagCourier>,
+    team: Option<&'static Team>,
+    transform: &'static Transform,
+    velocity: Option<&'static LinearVelocity>,
+}
+
+impl TagCarrierItem<'_> {
+    fn is_teammate(&self, other: &Self) -> bool {
+        matches!((self.team, other.team), (Some(team1), Some(team2)) if team1 == team2)
+    }
+
+    fn rams_from_behind(&self, other: &Self) -> bool {
+        rams_from_behind(
+            (self.transform, self.velocity),
+            (other.transform, other.velocity),
+        )
+    }
+}
+
+/// Whether the first body comes up behind the second one and closes in on it.
+pub(crate) fn rams_from_behind(
+    (transform, velocity): (&Transform, Option<&LinearVelocity>),
+    (other_transform, other_velocity): (&Transform, Option<&LinearVelocity>),
+) -> bool {
+    let heading = other_transform.local_x().xy();
+    let offset = other_transform.translation.xy() - transform.translation.xy();
+    let closing_velocity = velocity.map_or(Vec2::ZERO, |velocity| **velocity)
+        - other_velocity.map_or(Vec2::ZERO, |velocity| **velocity);
+    offset.dot(heading) > 0. && closing_velocity.dot(offset) > 0.
+}
+
+/// Holds the handle to the loaded [`TagKinds`].
+#[derive(Clone, Debug)]
+#[derive(Resource)]
+pub struct TagKindRegistry(pub Handle<TagKinds>);
+
+/// Every tag declared in a `.tags.ron` file, in the order they appear.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Asset, TypePath)]
+pub struct TagKinds {
+    kinds: Vec<TagKind>,
+}
+
+impl TagKinds {
+    pub fn get(&self, id: TagKindId) -> Option<&TagKind> {
+        self.kinds.get(id.0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TagKindId, &TagKind)> + '_ {
+        self.kinds
+            .iter()
+            .enumerate()
+            .map(|(index, kind)| (TagKindId(index), kind))
+    }
+
+    fn passable(&self, giver: &TagCarrierItem, receiver: &TagCarrierItem) -> Vec<TagKindId> {
+        if giver.immune || receiver.shielded {
+            return vec![];
+        }
+        giver
+            .held
+            .iter()
+            .filter(|id| !receiver.held.contains(*id))
+            .filter(|id| {
+                let Some(kind) = self.get(*id) else {
+                    return false;
+                };
+                if kind.opponents_only && giver.is_teammate(receiver) {
+                    return false;
+                }
+                match kind.transfer {
+                    TagTransfer::OnContact => true,
+                    TagTransfer::OnRamFromBehind => giver.rams_from_behind(receiver),
+                    TagTransfer::Never => false,
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[derive(Reflect)]
+pub struct TagKind {
+    pub name: String,
+    #[serde(default)]
+    pub transfer: TagTransfer,
+    /// Applied in order whenever a holder completes a lap.
+    #[serde(default)]
+    pub on_lap: Vec<LapEffect>,
+    /// Path to an image drawn over each holder.
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// The tag is kept in play on this many cars at once.
+    #[serde(default = "TagKind::default_max_holders")]
+    pub max_holders: usize,
+    /// Whether the tag can only be passed to cars on another [`Team`].
+    #[serde(default)]
+    pub opponents_only: bool,
+}
+
+impl TagKind {
+    fn default_max_holders() -> usize {
+        1
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[derive(Reflect)]
+pub enum TagTransfer {
+    /// Passed to any car the holder touches.
+    #[default]
+    OnContact,
+    /// Passed only when the holder runs into the back of another car.
+    OnRamFromBehind,
+    /// Stays with its holder until they lose it some other way.
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[derive(Reflect)]
+pub enum LapEffect {
+    /// Adds to (or takes from) the holder's [`Score`].
+    Score(i32),
+    /// Removes the holder from the match.
+    Eliminate,
+    /// Sends the holder back to the start of the track.
+    Respawn,
+    /// Keeps the holder from passing its tags on for this many seconds.
+    Immunity(f32),
+    /// Keeps the holder from being handed any more tags for this many seconds.
+    Shield(f32),
+}
+
+impl LapEffect {
+    pub(crate) fn command(self) -> impl EntityCommand {
+        move |entity: Entity, world: &mut World| {
+            if world.get_entity(entity).is_none() {
+                return;
+            }
+            match self {
+                LapEffect::Score(points) => {
+                    if let Some(mut score) = world.get_mut::<Score>(entity) {
+                        **score = score.saturating_add_signed(points);
+                    }
+                }
+                LapEffect::Eliminate => eliminate().apply(entity, world),
+                LapEffect::Respawn => {
+                    world.entity_mut(entity).insert(RespawnRequested);
+                }
+                LapEffect::Immunity(seconds) => {
+                    world
+                        .entity_mut(entity)
+                        .insert(TagImmunity(Duration::from_secs_f32(seconds.max(0.))));
+                }
+                LapEffect::Shield(seconds) => {
+                    world
+                        .entity_mut(entity)
+                        .insert(Shielded::new(Duration::from_secs_f32(seconds.max(0.))));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Reflect)]
+pub struct TagKindId(pub usize);
+
+/// The data-defined tags a car is currently holding.
+#[derive(Clone, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct HeldTags(Vec<TagKindId>);
+
+impl HeldTags {
+    pub fn contains(&self, id: TagKindId) -> bool {
+        self.0.contains(&id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = TagKindId> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub(crate) fn insert(&mut self, id: TagKindId) {
+        if !self.contains(id) {
+            self.0.push(id);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, id: TagKindId) {
+        self.0.retain(|held| *held != id);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[derive(Default)]
+struct TagKindsLoader;
+
+#[derive(Debug, Error)]
+pub enum TagKindsLoaderError {
+    #[error("Could not read tag kinds: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse tag kinds: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for TagKindsLoader {
+    type Asset = TagKinds;
+    type Settings = ();
+    type Error = TagKindsLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _: &'a (),
+        _: &'a mut LoadContext<'_>,
+    ) -> Result<TagKinds, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tags.ron"]
+    }
+}
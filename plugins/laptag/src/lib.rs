@@ -1,34 +1,93 @@
 use std::{marker::PhantomData, time::Duration};
 
-use avian2d::prelude::{CollisionStarted, Physics};
+use avian2d::prelude::{CollisionStarted, LinearVelocity, Physics};
 use bevy::{
-    app::PluginGroupBuilder, ecs::system::EntityCommand, prelude::*, reflect::GetTypeRegistration,
+    app::PluginGroupBuilder,
+    ecs::{
+        schedule::{InternedScheduleLabel, ScheduleLabel},
+        system::EntityCommand,
+    },
+    prelude::*,
+    reflect::GetTypeRegistration,
     utils::EntityHashSet,
 };
 
 use track::{CheckpointTracker, LapComplete};
 
+mod fuse;
+pub use fuse::*;
+mod kinds;
+use kinds::rams_from_behind;
+pub use kinds::*;
+
 #[cfg(feature = "graphics")]
 mod graphics;
 #[cfg(feature = "graphics")]
 pub use graphics::*;
 
 pub trait TagIt {
-    fn finish_lap() -> impl EntityCommand;
+    /// The rules this tag plays by, in the same terms as the tags read from a `.tags.ron` file.
+    ///
+    /// How many cars hold it is left to whoever hands it out.
+    fn kind() -> TagKind;
+
+    fn finish_lap() -> impl EntityCommand {
+        let effects = Self::kind().on_lap;
+        move |entity: Entity, world: &mut World| {
+            for effect in effects {
+                effect.command().apply(entity, world);
+            }
+        }
+    }
+
+    /// Whether this tag can be passed between cars on the same [`Team`].
+    fn transfers_between_teammates() -> bool {
+        !Self::kind().opponents_only
+    }
+}
+
+pub struct LapTagPlugins {
+    /// The schedule tags change hands in, which should be the one laps are counted in.
+    pub schedule: InternedScheduleLabel,
 }
 
-pub struct LapTagPlugins;
+impl Default for LapTagPlugins {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
 
 impl PluginGroup for LapTagPlugins {
     fn build(self) -> PluginGroupBuilder {
         PluginGroupBuilder::start::<Self>()
-            .add(LapsPlugin)
-            .add(TagPlugin::<LapTagIt>::default())
-            .add(TagPlugin::<BombTagIt>::default())
+            .add(LapsPlugin {
+                schedule: self.schedule,
+            })
+            .add(TagPlugin::<LapTagIt>::new(self.schedule))
+            .add(TagPlugin::<BombTagIt>::new(self.schedule))
+            .add(BombFusePlugin {
+                schedule: self.schedule,
+            })
+            .add(TagKindsPlugin {
+                schedule: self.schedule,
+                ..Default::default()
+            })
     }
 }
 
-pub struct LapsPlugin;
+pub struct LapsPlugin {
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for LapsPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
 
 impl Plugin for LapsPlugin {
     fn build(&self, app: &mut App) {
@@ -36,14 +95,20 @@ impl Plugin for LapsPlugin {
         app.add_plugins(graphics::GraphicsPlugin);
 
         app.add_systems(
-            Update,
+            self.schedule,
             (Self::tick_immunity, Self::handle_tags)
                 .chain()
                 .in_set(LapTagSystems),
         );
         app.register_type::<Score>()
             .register_type::<TagImmunity>()
-            .register_type::<CanBeIt>();
+            .register_type::<Shielded>()
+            .register_type::<CanBeIt>()
+            .register_type::<TagCourier>()
+            .register_type::<RespawnRequested>()
+            .register_type::<Lives>()
+            .register_type::<Eliminated>()
+            .register_type::<Team>();
     }
 }
 
@@ -52,6 +117,8 @@ impl LapsPlugin {
     fn handle_tags(
         mut commands: Commands,
         mut lap_trackers: Query<&mut CheckpointTracker>,
+        holders: Query<(Has<LapTagIt>, Has<BombTagIt>, Option<&HeldTags>)>,
+        couriers: Query<(), With<TagCourier>>,
         new_tag_its: Query<Entity, Or<(Added<LapTagIt>, Added<BombTagIt>)>>,
         mut removed_lap_tag_its: RemovedComponents<LapTagIt>,
         mut removed_bomb_tag_its: RemovedComponents<BombTagIt>,
@@ -68,6 +135,10 @@ impl LapsPlugin {
             entities_to_remove.remove(&entity);
             // also attach some immunity
             commands.entity(entity).insert(TagImmunity::default());
+            // couriers keep whatever tracker they drive by
+            if couriers.contains(entity) {
+                continue;
+            }
             // now clear or insert a new tracker
             if let Ok(mut tracker) = lap_trackers.get_mut(entity) {
                 tracker.clear();
@@ -78,7 +149,11 @@ impl LapsPlugin {
 
         // now cleanup the remaining entities to remove
         for entity in entities_to_remove {
-            if lap_trackers.contains(entity) {
+            // keep tracking laps for entities that are still holding some other tag
+            let still_holding = holders.get(entity).is_ok_and(|(lap_tag, bomb_tag, held)| {
+                lap_tag || bomb_tag || held.is_some_and(|held| !held.is_empty())
+            });
+            if lap_trackers.contains(entity) && !still_holding && !couriers.contains(entity) {
                 commands.entity(entity).remove::<CheckpointTracker>();
             }
         }
@@ -87,6 +162,7 @@ impl LapsPlugin {
     fn tick_immunity(
         mut commands: Commands,
         mut timers: Query<(Entity, &mut TagImmunity)>,
+        mut shields: Query<(Entity, &mut Shielded)>,
         time: Res<Time<Physics>>,
     ) {
         for (entity, mut timer) in &mut timers {
@@ -94,21 +170,41 @@ impl LapsPlugin {
                 commands.entity(entity).remove::<TagImmunity>();
             }
         }
+        for (entity, mut shield) in &mut shields {
+            if shield.tick(time.delta()) {
+                commands.entity(entity).remove::<Shielded>();
+            }
+        }
     }
 }
 
-#[derive(Default)]
 pub struct TagPlugin<Tag> {
+    schedule: InternedScheduleLabel,
     marker: PhantomData<Tag>,
 }
 
+impl<Tag> TagPlugin<Tag> {
+    pub fn new(schedule: impl ScheduleLabel) -> Self {
+        Self {
+            schedule: schedule.intern(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<Tag> Default for TagPlugin<Tag> {
+    fn default() -> Self {
+        Self::new(Update)
+    }
+}
+
 impl<Tag> Plugin for TagPlugin<Tag>
 where
     Tag: Component + Default + GetTypeRegistration + TagIt + Send + Sync + 'static,
 {
     fn build(&self, app: &mut App) {
         app.add_event::<TagEvent>().add_systems(
-            Update,
+            self.schedule,
             (Self::transfer_tag, Self::complete_laps)
                 .chain()
                 .in_set(LapTagSystems),
@@ -121,15 +217,19 @@ impl<Tag> TagPlugin<Tag>
 where
     Tag: TagIt + Component,
 {
+    #[allow(clippy::type_complexity)]
     fn transfer_tag(
         mut commands: Commands,
         mut collisions: EventReader<CollisionStarted>,
         tag_its: Query<Entity, (With<Tag>, Without<TagImmunity>)>,
-        can_be_its: Query<Entity, (With<CanBeIt>, Without<Tag>)>,
+        can_be_its: Query<Entity, (With<CanBeIt>, Without<Tag>, Without<Shielded>)>,
+        teams: Query<&Team>,
+        bodies: Query<(&Transform, Option<&LinearVelocity>)>,
         mut tags: EventWriter<TagEvent>,
     ) where
         Tag: Default,
     {
+        let transfer = Tag::kind().transfer;
         for CollisionStarted(entity1, entity2) in collisions.read() {
             let entity1_is_it = tag_its.contains(*entity1);
             let entity2_is_it = tag_its.contains(*entity2);
@@ -143,6 +243,28 @@ where
             } else {
                 continue;
             };
+            let are_teammates = matches!(
+                (teams.get(it_entity), teams.get(tagged_entity)),
+                (Ok(team1), Ok(team2)) if team1 == team2
+            );
+            if are_teammates && !Tag::transfers_between_teammates() {
+                continue;
+            }
+            let passes = match transfer {
+                TagTransfer::OnContact => true,
+                TagTransfer::OnRamFromBehind => {
+                    let (Ok(giver), Ok(receiver)) =
+                        (bodies.get(it_entity), bodies.get(tagged_entity))
+                    else {
+                        continue;
+                    };
+                    rams_from_behind(giver, receiver)
+                }
+                TagTransfer::Never => false,
+            };
+            if !passes {
+                continue;
+            }
             commands.entity(it_entity).remove::<Tag>();
             commands.entity(tagged_entity).insert(Tag::default());
             tags.send(TagEvent {
@@ -155,7 +277,7 @@ where
     fn complete_laps(
         mut commands: Commands,
         mut completed_laps: EventReader<LapComplete>,
-        racers: Query<Entity, (With<Tag>, With<CheckpointTracker>)>,
+        racers: Query<Entity, (With<Tag>, With<CheckpointTracker>, Without<TagCourier>)>,
     ) {
         for lap in completed_laps.read() {
             if racers.contains(lap.racer) {
@@ -177,6 +299,34 @@ pub struct Score(u32);
 #[derive(Component, Reflect)]
 pub struct CanBeIt;
 
+/// Marks a [`CanBeIt`] entity that carries tags from one car to the next without racing for
+/// them: it never finishes a lap with a tag, and its [`CheckpointTracker`] is left alone.
+#[derive(Clone, Copy, Debug)]
+#[derive(Component, Reflect)]
+pub struct TagCourier;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Component, Reflect)]
+pub struct Team(pub usize);
+
+impl Team {
+    const NAMES: [&'static str; 4] = ["Red", "Blue", "Green", "Gold"];
+    const COLORS: [Color; 4] = [
+        Color::srgb(0.86, 0.24, 0.22),
+        Color::srgb(0.25, 0.47, 0.93),
+        Color::srgb(0.3, 0.72, 0.33),
+        Color::srgb(0.93, 0.74, 0.2),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        Self::NAMES[self.0 % Self::NAMES.len()]
+    }
+
+    pub fn color(&self) -> Color {
+        Self::COLORS[self.0 % Self::COLORS.len()]
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 #[derive(Component, Deref, Reflect)]
 pub struct TagImmunity(Duration);
@@ -194,17 +344,39 @@ impl TagImmunity {
     }
 }
 
+/// Keeps an entity from being handed any tags until it wears off.
+///
+/// Unlike [`TagImmunity`], which stops a holder passing its tags on, this protects the entity
+/// from receiving them.
+#[derive(Clone, Copy, Debug)]
+#[derive(Component, Deref, Reflect)]
+pub struct Shielded(Duration);
+
+impl Shielded {
+    pub fn new(duration: Duration) -> Self {
+        Self(duration)
+    }
+
+    fn tick(&mut self, delta: Duration) -> bool {
+        self.0 = self.0.saturating_sub(delta);
+        self.0.is_zero()
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default)]
 #[derive(Component, Reflect)]
 pub struct LapTagIt;
 
 impl TagIt for LapTagIt {
-    fn finish_lap() -> impl EntityCommand {
-        |entity: Entity, world: &mut World| {
-            let mut score = world
-                .get_mut::<Score>(entity)
-                .expect("LapTagIt command should only be fired for valid `Score`ing entities");
-            **score += 1;
+    // flags can only be stolen by the opposing team
+    fn kind() -> TagKind {
+        TagKind {
+            name: "Flag".to_string(),
+            transfer: TagTransfer::OnContact,
+            on_lap: vec![LapEffect::Score(1)],
+            icon: None,
+            max_holders: 1,
+            opponents_only: true,
         }
     }
 }
@@ -214,13 +386,180 @@ impl TagIt for LapTagIt {
 pub struct BombTagIt;
 
 impl TagIt for BombTagIt {
-    fn finish_lap() -> impl EntityCommand {
-        |entity: Entity, world: &mut World| {
+    fn kind() -> TagKind {
+        TagKind {
+            name: "Bomb".to_string(),
+            transfer: TagTransfer::OnContact,
+            on_lap: vec![LapEffect::Eliminate],
+            icon: None,
+            max_holders: 1,
+            opponents_only: false,
+        }
+    }
+}
+
+/// Removes a car from the match, or takes one of its [`Lives`] if it has any left.
+///
+/// A car that loses a life drops its tags on the nearest cars still racing, as [`DropTags`]
+/// does, and is marked [`Eliminated`] until it is brought back.
+pub fn eliminate() -> impl EntityCommand {
+    |entity: Entity, world: &mut World| {
+        let has_spare_life = world
+            .get_mut::<Lives>(entity)
+            .is_some_and(|mut lives| lives.lose());
+        if !has_spare_life {
             world.entity_mut(entity).despawn_recursive();
+            return;
         }
+        let candidates = world
+            .query_filtered::<Entity, (With<CanBeIt>, Without<Eliminated>, Without<TagCourier>)>()
+            .iter(world)
+            .collect();
+        DropTags { candidates }.apply(entity, world);
+        // whatever had nowhere to go leaves the match with the car
+        if let Some(mut held) = world.get_mut::<HeldTags>(entity) {
+            held.clear();
+        }
+        world
+            .entity_mut(entity)
+            .remove::<(BombTagIt, BombFuse)>()
+            .insert(Eliminated);
     }
 }
 
+/// Hands each tag an entity is holding to the nearest of `candidates` that the tag is allowed
+/// to go to, keeping flags away from teammates.
+///
+/// Tags with nowhere to go stay where they are.
+#[derive(Clone, Debug)]
+pub struct DropTags {
+    pub candidates: Vec<Entity>,
+}
+
+impl EntityCommand for DropTags {
+    fn apply(self, from: Entity, world: &mut World) {
+        let Some(position) = world
+            .get::<Transform>(from)
+            .map(|transform| transform.translation)
+        else {
+            return;
+        };
+        let team = world.get::<Team>(from).copied();
+        let candidates = self
+            .candidates
+            .into_iter()
+            .filter(|candidate| *candidate != from)
+            .filter_map(|candidate| {
+                let distance = world
+                    .get::<Transform>(candidate)?
+                    .translation
+                    .distance_squared(position);
+                Some((candidate, distance, world.get::<Team>(candidate).copied()))
+            })
+            .collect::<Vec<_>>();
+        let nearest = |opponents_only: bool| {
+            candidates
+                .iter()
+                .filter(|(_, _, candidate_team)| {
+                    !opponents_only || team.is_none() || *candidate_team != team
+                })
+                .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b))
+                .map(|(candidate, _, _)| *candidate)
+        };
+
+        let mut receivers = Vec::new();
+        if world.entity(from).contains::<LapTagIt>() {
+            if let Some(to) = nearest(!LapTagIt::transfers_between_teammates()) {
+                world.entity_mut(from).remove::<LapTagIt>();
+                world.entity_mut(to).insert(LapTagIt);
+                receivers.push(to);
+            }
+        }
+        if world.entity(from).contains::<BombTagIt>() {
+            if let Some(to) = nearest(!BombTagIt::transfers_between_teammates()) {
+                world.entity_mut(from).remove::<BombTagIt>();
+                world.entity_mut(to).insert(BombTagIt);
+                receivers.push(to);
+            }
+        }
+        let held = world
+            .get::<HeldTags>(from)
+            .map(|held| held.iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let kinds = world
+            .get_resource::<TagKindRegistry>()
+            .and_then(|registry| world.resource::<Assets<TagKinds>>().get(&registry.0))
+            .cloned()
+            .unwrap_or_default();
+        for id in held {
+            let Some(kind) = kinds.get(id) else {
+                continue;
+            };
+            let Some(to) = nearest(kind.opponents_only) else {
+                continue;
+            };
+            let Some(mut receiver) = world.get_mut::<HeldTags>(to) else {
+                continue;
+            };
+            receiver.insert(id);
+            if let Some(mut giver) = world.get_mut::<HeldTags>(from) {
+                giver.remove(id);
+            }
+            world.entity_mut(to).insert(TagImmunity::default());
+            if !kind.on_lap.is_empty() && !world.entity(to).contains::<TagCourier>() {
+                world.entity_mut(to).insert(CheckpointTracker::default());
+            }
+            receivers.push(to);
+        }
+
+        let still_holding = world.entity(from).contains::<LapTagIt>()
+            || world.entity(from).contains::<BombTagIt>()
+            || world
+                .get::<HeldTags>(from)
+                .is_some_and(|held| !held.is_empty());
+        if !still_holding && !world.entity(from).contains::<TagCourier>() {
+            world.entity_mut(from).remove::<CheckpointTracker>();
+        }
+        receivers.sort();
+        receivers.dedup();
+        for next_it in receivers {
+            world.send_event(TagEvent {
+                prev_it: from,
+                next_it,
+            });
+        }
+    }
+}
+
+/// How many more times a car can be eliminated before it is out of the match.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Deref, Reflect)]
+pub struct Lives(u32);
+
+impl Lives {
+    pub fn new(lives: u32) -> Self {
+        Self(lives)
+    }
+
+    fn lose(&mut self) -> bool {
+        if self.0 == 0 {
+            return false;
+        }
+        self.0 -= 1;
+        true
+    }
+}
+
+/// Marks a car that lost a life and is waiting to come back.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct Eliminated;
+
+/// Asks for a car to be put back at the start of the track.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct RespawnRequested;
+
 #[derive(Clone, Copy, Debug)]
 #[derive(Event, Reflect)]
 pub struct TagEvent {
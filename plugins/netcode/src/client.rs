@@ -0,0 +1,546 @@
+use std::{
+    collections::VecDeque,
+    f32::consts::{PI, TAU},
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use bevy::{app::AppExit, prelude::*};
+use leafwing_input_manager::prelude::ActionState;
+
+use controller::{CarControl, Controller};
+use laptag::{BombTagIt, LapTagIt, Score};
+
+use crate::{
+    CarSnapshot, ClientId, ClientMessage, InputFrame, LobbyPlayer, NetId, NetSocket,
+    NetcodeSystems, PropKind, ServerMessage,
+};
+
+/// Joins a server, sends it the local player's controls and shows the cars it sends back.
+///
+/// Cars from the server are spawned as [`NetCar`]s without any physics, and are drawn a little
+/// in the past so they can move smoothly between snapshots. The resurfacer and its pegs come
+/// along as [`NetProp`]s the same way.
+pub struct NetClientPlugin {
+    pub server: SocketAddr,
+    pub name: String,
+}
+
+impl Plugin for NetClientPlugin {
+    fn build(&self, app: &mut App) {
+        let socket = NetSocket::bind((Ipv4Addr::UNSPECIFIED, 0).into())
+            .unwrap_or_else(|error| panic!("Failed to open a socket: {error}"));
+        app.insert_resource(socket)
+            .insert_resource(ClientSession::new(self.server, self.name.clone()))
+            .add_event::<ReadyUp>()
+            .add_systems(
+                Update,
+                (
+                    Self::send_join,
+                    Self::receive_server_messages,
+                    Self::keep_connection,
+                    Self::send_ready,
+                    Self::send_input,
+                    Self::interpolate_net_cars,
+                    Self::interpolate_net_props,
+                )
+                    .chain()
+                    .in_set(NetcodeSystems),
+            )
+            .add_systems(Last, Self::leave_on_exit);
+    }
+}
+
+impl NetClientPlugin {
+    const JOIN_INTERVAL: Duration = Duration::from_secs(1);
+    const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+    /// The server is given up on after this many seconds without a word from it.
+    const SERVER_TIMEOUT: f32 = 5.;
+    /// How far behind the newest snapshot cars are drawn.
+    const INTERPOLATION_DELAY: f32 = 0.1;
+    /// How quickly the estimate of the server's clock follows new snapshots.
+    const CLOCK_SMOOTHING: f32 = 0.05;
+
+    fn send_join(socket: Res<NetSocket>, mut session: ResMut<ClientSession>, time: Res<Time>) {
+        if session.client.is_some() || !session.join_timer.tick(time.delta()).just_finished() {
+            return;
+        }
+        info!("Joining {}", session.server);
+        let message = ClientMessage::Join {
+            name: session.name.clone(),
+        };
+        socket.send(&message, session.server);
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn receive_server_messages(
+        mut commands: Commands,
+        socket: Res<NetSocket>,
+        mut session: ResMut<ClientSession>,
+        mut net_cars: Query<(
+            Entity,
+            &mut NetCar,
+            Option<&mut Score>,
+            Has<LapTagIt>,
+            Has<BombTagIt>,
+        )>,
+        mut net_props: Query<(Entity, &mut NetProp)>,
+        time: Res<Time>,
+    ) {
+        for (address, message) in socket.receive::<ServerMessage>() {
+            if address != session.server {
+                continue;
+            }
+            session.last_heard = time.elapsed_seconds();
+            match message {
+                ServerMessage::Welcome { client } => {
+                    info!("Joined as {client:?}");
+                    session.client = Some(client);
+                }
+                ServerMessage::Lobby { players } => {
+                    if players.is_empty() && session.client.is_some() {
+                        info!("The server closed");
+                        session.disconnect();
+                        for (entity, ..) in &net_cars {
+                            commands.entity(entity).despawn_recursive();
+                        }
+                        continue;
+                    }
+                    session.lobby = players;
+                }
+                ServerMessage::MatchStarted { car } => {
+                    session.car = car;
+                    // the first snapshot may have beaten this here
+                    for (entity, net_car, ..) in &net_cars {
+                        if Some(net_car.id) == car {
+                            commands
+                                .entity(entity)
+                                .insert((LocalNetCar, Controller::ArrowKeys));
+                        }
+                    }
+                }
+                ServerMessage::MatchEnded => {
+                    session.car = None;
+                    for (entity, ..) in &net_cars {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+                ServerMessage::Snapshot(snapshot) => {
+                    let now = time.elapsed_seconds();
+                    let offset = snapshot.time - now;
+                    session.clock_offset = Some(session.clock_offset.map_or(offset, |current| {
+                        current + (offset - current) * Self::CLOCK_SMOOTHING
+                    }));
+
+                    let mut seen = vec![];
+                    for (entity, mut net_car, score, has_lap_tag, has_bomb_tag) in &mut net_cars {
+                        let Some(car) = snapshot.cars.iter().find(|car| car.id == net_car.id)
+                        else {
+                            // the car is gone on the server
+                            commands.entity(entity).despawn_recursive();
+                            continue;
+                        };
+                        seen.push(car.id);
+                        net_car.push(snapshot.time, car);
+                        if let Some(mut score) = score {
+                            if **score != car.score {
+                                **score = car.score;
+                            }
+                        }
+                        sync_marker(
+                            &mut commands,
+                            entity,
+                            LapTagIt,
+                            has_lap_tag,
+                            car.has_lap_tag,
+                        );
+                        sync_marker(
+                            &mut commands,
+                            entity,
+                            BombTagIt,
+                            has_bomb_tag,
+                            car.has_bomb_tag,
+                        );
+                    }
+                    for car in snapshot.cars.iter().filter(|car| !seen.contains(&car.id)) {
+                        let mut net_car = NetCar::new(car.id);
+                        net_car.push(snapshot.time, car);
+                        let position = Vec2::from_array(car.position);
+                        let mut score = Score::default();
+                        **score = car.score;
+                        let mut entity = commands.spawn((
+                            Name::new("Net Car"),
+                            net_car,
+                            score,
+                            SpatialBundle::from_transform(
+                                Transform::from_translation(position.extend(0.))
+                                    .with_rotation(Quat::from_rotation_z(car.angle)),
+                            ),
+                        ));
+                        if car.has_lap_tag {
+                            entity.insert(LapTagIt);
+                        }
+                        if car.has_bomb_tag {
+                            entity.insert(BombTagIt);
+                        }
+                        if session.car == Some(car.id) {
+                            entity.insert((LocalNetCar, Controller::ArrowKeys));
+                        }
+                    }
+
+                    let mut seen = vec![];
+                    for (entity, mut net_prop) in &mut net_props {
+                        let Some(prop) = snapshot.props.iter().find(|prop| prop.id == net_prop.id)
+                        else {
+                            commands.entity(entity).despawn_recursive();
+                            continue;
+                        };
+                        seen.push(prop.id);
+                        net_prop.poses.push(
+                            snapshot.time,
+                            Vec2::from_array(prop.position),
+                            prop.angle,
+                        );
+                    }
+                    for prop in snapshot
+                        .props
+                        .iter()
+                        .filter(|prop| !seen.contains(&prop.id))
+                    {
+                        let mut net_prop = NetProp {
+                            id: prop.id,
+                            kind: prop.kind,
+                            poses: NetPoses::default(),
+                        };
+                        let position = Vec2::from_array(prop.position);
+                        net_prop.poses.push(snapshot.time, position, prop.angle);
+                        commands.spawn((
+                            Name::new(format!("Net {:?}", prop.kind)),
+                            net_prop,
+                            SpatialBundle::from_transform(
+                                Transform::from_translation(position.extend(0.))
+                                    .with_rotation(Quat::from_rotation_z(prop.angle)),
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lets the server know this client is still around while it has no inputs to send, and
+    /// starts over when the server has gone quiet, which it does after dropping this client.
+    fn keep_connection(
+        mut commands: Commands,
+        socket: Res<NetSocket>,
+        mut session: ResMut<ClientSession>,
+        net_cars: Query<Entity, With<NetCar>>,
+        time: Res<Time>,
+    ) {
+        if session.client.is_none() {
+            return;
+        }
+        if time.elapsed_seconds() - session.last_heard > Self::SERVER_TIMEOUT {
+            info!("Lost the server, joining again");
+            session.disconnect();
+            for entity in &net_cars {
+                commands.entity(entity).despawn_recursive();
+            }
+            return;
+        }
+        if session.heartbeat_timer.tick(time.delta()).just_finished() {
+            socket.send(&ClientMessage::Heartbeat, session.server);
+        }
+    }
+
+    fn send_ready(
+        socket: Res<NetSocket>,
+        session: Res<ClientSession>,
+        mut readies: EventReader<ReadyUp>,
+    ) {
+        if readies.read().count() > 0 && session.client.is_some() {
+            socket.send(&ClientMessage::Ready, session.server);
+        }
+    }
+
+    fn send_input(
+        socket: Res<NetSocket>,
+        session: Res<ClientSession>,
+        local_cars: Query<&ActionState<CarControl>, With<LocalNetCar>>,
+    ) {
+        let Ok(action_state) = local_cars.get_single() else {
+            return;
+        };
+        let input = InputFrame::from_action_state(action_state);
+        socket.send(&ClientMessage::Input(input), session.server);
+    }
+
+    fn interpolate_net_cars(
+        session: Res<ClientSession>,
+        mut net_cars: Query<(&mut NetCar, &mut Transform)>,
+        time: Res<Time>,
+    ) {
+        let Some(offset) = session.clock_offset else {
+            return;
+        };
+        let render_time = time.elapsed_seconds() + offset - Self::INTERPOLATION_DELAY;
+        for (mut net_car, mut transform) in &mut net_cars {
+            let Some((position, angle)) = net_car.pose_at(render_time) else {
+                continue;
+            };
+            transform.translation = position.extend(transform.translation.z);
+            transform.rotation = Quat::from_rotation_z(angle);
+        }
+    }
+
+    fn interpolate_net_props(
+        session: Res<ClientSession>,
+        mut net_props: Query<(&mut NetProp, &mut Transform)>,
+        time: Res<Time>,
+    ) {
+        let Some(offset) = session.clock_offset else {
+            return;
+        };
+        let render_time = time.elapsed_seconds() + offset - Self::INTERPOLATION_DELAY;
+        for (mut net_prop, mut transform) in &mut net_props {
+            let Some((position, angle)) = net_prop.poses.pose_at(render_time) else {
+                continue;
+            };
+            transform.translation = position.extend(transform.translation.z);
+            transform.rotation = Quat::from_rotation_z(angle);
+        }
+    }
+
+    fn leave_on_exit(
+        mut exits: EventReader<AppExit>,
+        socket: Res<NetSocket>,
+        session: Res<ClientSession>,
+    ) {
+        if exits.read().count() > 0 && session.client.is_some() {
+            socket.send(&ClientMessage::Leave, session.server);
+        }
+    }
+}
+
+fn sync_marker<M: Component>(
+    commands: &mut Commands,
+    entity: Entity,
+    marker: M,
+    has_marker: bool,
+    should_have_marker: bool,
+) {
+    match (has_marker, should_have_marker) {
+        (false, true) => {
+            commands.entity(entity).insert(marker);
+        }
+        (true, false) => {
+            commands.entity(entity).remove::<M>();
+        }
+        _ => {}
+    }
+}
+
+/// Tells the server the local player is ready to start.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Event)]
+pub struct ReadyUp;
+
+/// Where this client is connected, and what it knows about the match.
+#[derive(Debug)]
+#[derive(Resource)]
+pub struct ClientSession {
+    server: SocketAddr,
+    name: String,
+    client: Option<ClientId>,
+    lobby: Vec<LobbyPlayer>,
+    car: Option<NetId>,
+    join_timer: Timer,
+    heartbeat_timer: Timer,
+    /// When anything last arrived from the server, on our clock.
+    last_heard: f32,
+    /// The server's clock minus ours.
+    clock_offset: Option<f32>,
+}
+
+impl ClientSession {
+    fn new(server: SocketAddr, name: String) -> Self {
+        let mut join_timer = Timer::new(NetClientPlugin::JOIN_INTERVAL, TimerMode::Repeating);
+        // try right away
+        join_timer.set_elapsed(NetClientPlugin::JOIN_INTERVAL);
+        Self {
+            server,
+            name,
+            client: None,
+            lobby: vec![],
+            car: None,
+            join_timer,
+            heartbeat_timer: Timer::new(NetClientPlugin::HEARTBEAT_INTERVAL, TimerMode::Repeating),
+            last_heard: 0.,
+            clock_offset: None,
+        }
+    }
+
+    /// Forgets the server's lobby and match, to join again from scratch.
+    fn disconnect(&mut self) {
+        self.client = None;
+        self.lobby.clear();
+        self.car = None;
+        self.clock_offset = None;
+        self.join_timer.set_elapsed(NetClientPlugin::JOIN_INTERVAL);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    pub fn client(&self) -> Option<ClientId> {
+        self.client
+    }
+
+    pub fn lobby(&self) -> &[LobbyPlayer] {
+        &self.lobby
+    }
+
+    /// The car this client drives, once the match has started.
+    pub fn car(&self) -> Option<NetId> {
+        self.car
+    }
+}
+
+/// A car simulated on the server.
+#[derive(Clone, Debug)]
+#[derive(Component)]
+pub struct NetCar {
+    pub id: NetId,
+    poses: NetPoses,
+}
+
+impl NetCar {
+    fn new(id: NetId) -> Self {
+        Self {
+            id,
+            poses: NetPoses::default(),
+        }
+    }
+
+    fn push(&mut self, time: f32, car: &CarSnapshot) {
+        self.poses
+            .push(time, Vec2::from_array(car.position), car.angle);
+    }
+
+    /// Where the car was at `time` on the server's clock, as close as the snapshots allow.
+    fn pose_at(&mut self, time: f32) -> Option<(Vec2, f32)> {
+        self.poses.pose_at(time)
+    }
+}
+
+/// Something other than a car simulated on the server, such as the resurfacer or a peg.
+#[derive(Clone, Debug)]
+#[derive(Component)]
+pub struct NetProp {
+    pub id: NetId,
+    pub kind: PropKind,
+    poses: NetPoses,
+}
+
+/// The latest places the server has put something, to move it smoothly between them.
+#[derive(Clone, Debug, Default)]
+struct NetPoses {
+    // oldest first, as (server time, position, angle)
+    snapshots: VecDeque<(f32, Vec2, f32)>,
+}
+
+impl NetPoses {
+    const MAX_SNAPSHOTS: usize = 32;
+
+    fn push(&mut self, time: f32, position: Vec2, angle: f32) {
+        // packets can arrive out of order
+        if self
+            .snapshots
+            .back()
+            .is_some_and(|(last, ..)| *last >= time)
+        {
+            return;
+        }
+        self.snapshots.push_back((time, position, angle));
+        if self.snapshots.len() > Self::MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    fn pose_at(&mut self, time: f32) -> Option<(Vec2, f32)> {
+        // keep one snapshot from before `time` to interpolate from
+        while self.snapshots.len() > 2 && self.snapshots[1].0 <= time {
+            self.snapshots.pop_front();
+        }
+        let (from_time, from_position, from_angle) = *self.snapshots.front()?;
+        let Some(&(to_time, to_position, to_angle)) = self.snapshots.get(1) else {
+            return Some((from_position, from_angle));
+        };
+        let t = ((time - from_time) / (to_time - from_time).max(f32::EPSILON)).clamp(0., 1.);
+        let turn = (to_angle - from_angle + PI).rem_euclid(TAU) - PI;
+        Some((from_position.lerp(to_position, t), from_angle + turn * t))
+    }
+}
+
+/// Marks the [`NetCar`] that this client drives.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+pub struct LocalNetCar;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(position: Vec2, angle: f32) -> CarSnapshot {
+        CarSnapshot {
+            id: NetId(0),
+            position: position.to_array(),
+            angle,
+            has_lap_tag: false,
+            has_bomb_tag: false,
+            score: 0,
+        }
+    }
+
+    #[test]
+    fn test_pose_interpolation() {
+        let mut net_car = NetCar::new(NetId(0));
+        assert_eq!(net_car.pose_at(0.), None);
+
+        net_car.push(1., &snapshot(Vec2::ZERO, 0.));
+        assert_eq!(net_car.pose_at(0.), Some((Vec2::ZERO, 0.)));
+        net_car.push(2., &snapshot(Vec2::new(10., 0.), 1.));
+        // a late packet from before the newest one is ignored
+        net_car.push(1.5, &snapshot(Vec2::new(-100., 0.), 0.));
+
+        let (position, angle) = net_car.pose_at(1.5).unwrap();
+        assert!(position.distance(Vec2::new(5., 0.)) < 1e-4);
+        assert!((angle - 0.5).abs() < 1e-4);
+        // it holds still rather than guessing past the newest snapshot
+        let (position, _) = net_car.pose_at(3.).unwrap();
+        assert!(position.distance(Vec2::new(10., 0.)) < 1e-4);
+    }
+
+    #[test]
+    fn test_pose_turns_the_short_way() {
+        let mut net_car = NetCar::new(NetId(0));
+        net_car.push(0., &snapshot(Vec2::ZERO, PI - 0.1));
+        net_car.push(1., &snapshot(Vec2::ZERO, -PI + 0.1));
+
+        // halfway between just under a half turn either way is facing straight back
+        let (_, angle) = net_car.pose_at(0.5).unwrap();
+        assert!((angle.rem_euclid(TAU) - PI).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_pose_drops_old_snapshots() {
+        let mut net_car = NetCar::new(NetId(0));
+        for step in 0..4 {
+            net_car.push(step as f32, &snapshot(Vec2::splat(step as f32), 0.));
+        }
+        let (position, _) = net_car.pose_at(2.5).unwrap();
+        assert!(position.distance(Vec2::splat(2.5)) < 1e-4);
+        assert_eq!(net_car.poses.snapshots.len(), 2);
+    }
+}
@@ -0,0 +1,19 @@
+use bevy::prelude::*;
+
+mod client;
+pub use client::*;
+mod protocol;
+pub use protocol::*;
+mod rollback;
+pub use rollback::*;
+mod server;
+pub use server::*;
+mod socket;
+pub use socket::*;
+
+/// The port servers listen on unless told otherwise.
+pub const DEFAULT_PORT: u16 = 5000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(SystemSet)]
+pub struct NetcodeSystems;
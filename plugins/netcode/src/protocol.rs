@@ -0,0 +1,145 @@
+use bevy::ecs::system::EntityCommands;
+use leafwing_input_manager::prelude::ActionState;
+use serde::{Deserialize, Serialize};
+
+use car::{AccelerateAction, BoostAction, HandbrakeAction, ResetAction, SteerAction};
+use controller::CarControl;
+
+/// Identifies a connected client for as long as the server is running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct ClientId(pub u32);
+
+/// Identifies a car on the server, and the copy of it on every client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct NetId(pub u64);
+
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub enum ClientMessage {
+    Join {
+        name: String,
+    },
+    Ready,
+    Input(InputFrame),
+    /// Sent every so often to stay in the lobby while there are no inputs to send.
+    Heartbeat,
+    Leave,
+}
+
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub enum ServerMessage {
+    Welcome { client: ClientId },
+    Lobby { players: Vec<LobbyPlayer> },
+    MatchStarted { car: Option<NetId> },
+    Snapshot(Snapshot),
+    MatchEnded,
+}
+
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct LobbyPlayer {
+    pub client: ClientId,
+    pub name: String,
+    pub is_ready: bool,
+}
+
+/// What a player is doing with their controls this frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct InputFrame {
+    /// 1 to accelerate, -1 to brake or reverse.
+    pub throttle: i8,
+    pub steer: f32,
+    pub handbrake: bool,
+    pub boost: bool,
+    pub reset: bool,
+}
+
+impl InputFrame {
+    pub fn from_action_state(action_state: &ActionState<CarControl>) -> Self {
+        let mut input = InputFrame {
+            throttle: 0,
+            steer: 0.,
+            handbrake: action_state.pressed(&CarControl::Handbrake),
+            boost: action_state.pressed(&CarControl::Boost),
+            reset: action_state.just_pressed(&CarControl::Reset),
+        };
+        if action_state.pressed(&CarControl::Accelerate) {
+            input.throttle += 1;
+        }
+        if action_state.pressed(&CarControl::Brake) {
+            input.throttle -= 1;
+        }
+        if action_state.pressed(&CarControl::TurnLeft) {
+            input.steer += 1.;
+        }
+        if action_state.pressed(&CarControl::TurnRight) {
+            input.steer -= 1.;
+        }
+        input
+    }
+
+    /// Drives a car with these inputs for one frame.
+    pub fn apply(&self, car: &mut EntityCommands) {
+        match self.throttle.signum() {
+            1 => {
+                car.insert(AccelerateAction::Forward);
+            }
+            -1 => {
+                car.insert(AccelerateAction::Backward);
+            }
+            _ => {}
+        }
+        if self.handbrake {
+            car.insert(HandbrakeAction);
+        }
+        if self.boost {
+            car.insert(BoostAction);
+        }
+        if self.reset {
+            car.insert(ResetAction);
+        }
+        car.insert(SteerAction(self.steer.clamp(-1., 1.)));
+    }
+}
+
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Seconds since the server started simulating.
+    pub time: f32,
+    pub cars: Vec<CarSnapshot>,
+    pub props: Vec<PropSnapshot>,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct CarSnapshot {
+    pub id: NetId,
+    pub position: [f32; 2],
+    pub angle: f32,
+    pub has_lap_tag: bool,
+    pub has_bomb_tag: bool,
+    pub score: u32,
+}
+
+/// What kind of [`PropSnapshot`] to show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum PropKind {
+    Resurfacer,
+    Peg,
+}
+
+/// Something other than a car that the server simulates.
+#[derive(Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct PropSnapshot {
+    pub id: NetId,
+    pub kind: PropKind,
+    pub position: [f32; 2],
+    pub angle: f32,
+}
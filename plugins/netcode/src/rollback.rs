@@ -0,0 +1,334 @@
+use std::{
+    collections::VecDeque,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use avian2d::prelude::{
+    AngularVelocity, CollidingEntities, CollisionLayers, Collisions, ExternalAngularImpulse,
+    ExternalForce, ExternalImpulse, ExternalTorque, LinearVelocity, Physics, PhysicsSet, Position,
+    RigidBody, Rotation, Sleeping, TimeSleeping,
+};
+use bevy::{ecs::schedule::ExecutorKind, prelude::*};
+use bevy_ggrs::{
+    ggrs::{
+        self, DesyncDetection, GgrsError, GgrsEvent, Message, NonBlockingSocket, PlayerType,
+        SessionBuilder, UdpNonBlockingSocket,
+    },
+    prelude::*,
+    ComponentMapEntitiesPlugin, LocalInputs, LocalPlayers,
+};
+use leafwing_input_manager::prelude::ActionState;
+use thiserror::Error;
+
+use car::{
+    Boost, Car, Damage, Drifting, DriveForce, DrivingSystems, Ghost, Parked, Steering,
+    StuckDetector, Wrecked,
+};
+use controller::CarControl;
+use entropy::{Entropy, GlobalEntropy};
+use laptag::{
+    BombFuse, BombTagIt, CanBeIt, Eliminated, HeldTags, LapTagIt, LapTagSystems, Lives,
+    RespawnRequested, Score, Shielded, TagCourier, TagImmunity,
+};
+use resurfacer::{CheckpointObstacles, Resurfacer};
+use track::{CheckpointTracker, LastCheckpoint, TrackSystems};
+
+use crate::InputFrame;
+
+/// How many frames a second the rollback simulation runs at.
+pub const ROLLBACK_FPS: usize = 60;
+
+pub struct RollbackConfig;
+
+impl ggrs::Config for RollbackConfig {
+    type Input = InputFrame;
+    type State = u8;
+    type Address = SocketAddr;
+}
+
+/// Plays a match between peers by predicting each other's inputs and rolling back to fix any
+/// mistakes once the real ones arrive.
+///
+/// Physics, driving, tagging and laps have to be added to [`GgrsSchedule`] for this to work, and
+/// every entity with a [`RigidBody`] is rolled back, along with the contacts between them. Start
+/// a match by inserting the [`Session`] from [`start_p2p_session`], and mark each car with the
+/// [`RollbackDriver`] that drives it.
+pub struct RollbackPlugin;
+
+impl Plugin for RollbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(GgrsPlugin::<RollbackConfig>::default())
+            .set_rollback_schedule_fps(ROLLBACK_FPS)
+            // physics
+            .rollback_component_with_clone::<Transform>()
+            .rollback_component_with_clone::<Position>()
+            .rollback_component_with_clone::<Rotation>()
+            .rollback_component_with_clone::<LinearVelocity>()
+            .rollback_component_with_clone::<AngularVelocity>()
+            .rollback_component_with_clone::<ExternalForce>()
+            .rollback_component_with_clone::<ExternalTorque>()
+            .rollback_component_with_clone::<ExternalImpulse>()
+            .rollback_component_with_clone::<ExternalAngularImpulse>()
+            .rollback_component_with_clone::<CollisionLayers>()
+            .rollback_component_with_clone::<Sleeping>()
+            .rollback_component_with_clone::<TimeSleeping>()
+            // contacts, so the ones that start during a resimulated frame are noticed again
+            .rollback_component_with_clone::<CollidingEntities>()
+            .rollback_resource_with_clone::<Collisions>()
+            // driving
+            .rollback_component_with_clone::<Steering>()
+            .rollback_component_with_clone::<Boost>()
+            .rollback_component_with_clone::<DriveForce>()
+            .rollback_component_with_clone::<Drifting>()
+            .rollback_component_with_clone::<Damage>()
+            .rollback_component_with_clone::<Wrecked>()
+            .rollback_component_with_clone::<StuckDetector>()
+            .rollback_component_with_clone::<Parked>()
+            .rollback_component_with_clone::<Ghost>()
+            // tagging
+            .rollback_component_with_clone::<CanBeIt>()
+            .rollback_component_with_clone::<TagCourier>()
+            .rollback_component_with_clone::<LapTagIt>()
+            .rollback_component_with_clone::<BombTagIt>()
+            .rollback_component_with_clone::<BombFuse>()
+            .rollback_component_with_clone::<HeldTags>()
+            .rollback_component_with_clone::<TagImmunity>()
+            .rollback_component_with_clone::<Shielded>()
+            .rollback_component_with_clone::<Score>()
+            .rollback_component_with_clone::<Lives>()
+            .rollback_component_with_clone::<Eliminated>()
+            .rollback_component_with_clone::<RespawnRequested>()
+            // laps
+            .rollback_component_with_clone::<CheckpointTracker>()
+            .rollback_component_with_clone::<LastCheckpoint>()
+            // obstacles, so every peer steers the resurfacer and swaps out pegs the same way
+            .rollback_component_with_clone::<Resurfacer>()
+            .rollback_component_with_clone::<CheckpointObstacles>()
+            .add_plugins(ComponentMapEntitiesPlugin::<CheckpointObstacles>::default())
+            // randomness and time
+            .rollback_component_with_clone::<Entropy>()
+            .rollback_resource_with_clone::<GlobalEntropy>()
+            .rollback_resource_with_clone::<Time<RollbackClock>>()
+            .rollback_resource_with_clone::<Time<Physics>>()
+            .checksum_component::<Position>(checksum_position)
+            .checksum_component::<Score>(|score| **score as u64)
+            .checksum_component::<Lives>(|lives| **lives as u64)
+            // which car holds what is already told apart by the entity it is on
+            .checksum_component::<LapTagIt>(|_| 0)
+            .checksum_component::<BombTagIt>(|_| 0)
+            .checksum_component::<HeldTags>(checksum_held_tags)
+            .init_resource::<Time<RollbackClock>>()
+            // systems that aren't ordered against each other must still run in the same order
+            // on every peer
+            .edit_schedule(GgrsSchedule, |schedule| {
+                schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+            })
+            // tagging and laps read the contacts that start in the same frame's physics step, as
+            // contact events left over for the next frame would outlive a rollback to it
+            .configure_sets(
+                GgrsSchedule,
+                (
+                    RollbackSystems::Prepare.before(DrivingSystems),
+                    PhysicsSet::Prepare.after(DrivingSystems),
+                    TrackSystems.after(PhysicsSet::Sync),
+                    LapTagSystems.after(PhysicsSet::Sync),
+                    RollbackSystems::Finish
+                        .after(TrackSystems)
+                        .after(LapTagSystems),
+                ),
+            )
+            .add_systems(ReadInputs, Self::read_local_inputs)
+            .add_systems(
+                GgrsSchedule,
+                (
+                    (Self::use_rollback_clock, Self::drive_rollback_cars)
+                        .chain()
+                        .in_set(RollbackSystems::Prepare),
+                    (Self::use_virtual_clock, Self::roll_back_bodies)
+                        .in_set(RollbackSystems::Finish),
+                ),
+            )
+            .add_systems(
+                Update,
+                Self::report_session_events.run_if(resource_exists::<Session<RollbackConfig>>),
+            );
+    }
+}
+
+impl RollbackPlugin {
+    fn read_local_inputs(
+        mut commands: Commands,
+        local_players: Res<LocalPlayers>,
+        action_states: Query<&ActionState<CarControl>, With<LocalRollbackInput>>,
+    ) {
+        let input = action_states
+            .get_single()
+            .map(InputFrame::from_action_state)
+            .unwrap_or_default();
+        let inputs = local_players
+            .0
+            .iter()
+            .map(|handle| (*handle, input))
+            .collect();
+        commands.insert_resource(LocalInputs::<RollbackConfig>(inputs));
+    }
+
+    // like `FixedMain`, everything in the rollback schedule sees a clock that ticks exactly
+    // once per rollback frame
+    fn use_rollback_clock(world: &mut World) {
+        let mut clock = world.resource_mut::<Time<RollbackClock>>();
+        clock.advance_by(Duration::from_secs_f64(1. / ROLLBACK_FPS as f64));
+        let clock = clock.as_generic();
+        *world.resource_mut::<Time>() = clock;
+    }
+
+    fn use_virtual_clock(world: &mut World) {
+        let clock = world.resource::<Time<Virtual>>().as_generic();
+        *world.resource_mut::<Time>() = clock;
+    }
+
+    fn drive_rollback_cars(
+        mut commands: Commands,
+        inputs: Res<PlayerInputs<RollbackConfig>>,
+        cars: Query<(Entity, &RollbackDriver), With<Car>>,
+    ) {
+        for (car, driver) in &cars {
+            let Some((input, _)) = inputs.get(driver.0) else {
+                continue;
+            };
+            input.apply(&mut commands.entity(car));
+        }
+    }
+
+    fn roll_back_bodies(
+        mut commands: Commands,
+        new_bodies: Query<Entity, (Added<RigidBody>, Without<Rollback>)>,
+    ) {
+        for entity in &new_bodies {
+            commands.entity(entity).add_rollback();
+        }
+    }
+
+    fn report_session_events(mut session: ResMut<Session<RollbackConfig>>) {
+        let Session::P2P(session) = session.as_mut() else {
+            return;
+        };
+        for event in session.events() {
+            match event {
+                GgrsEvent::DesyncDetected {
+                    frame,
+                    local_checksum,
+                    remote_checksum,
+                    addr,
+                } => error!(
+                    "Desynced from {addr} on frame {frame}: \
+                    {local_checksum:x} here, {remote_checksum:x} there"
+                ),
+                event => info!("{event:?}"),
+            }
+        }
+    }
+}
+
+fn checksum_position(position: &Position) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    position.x.to_bits().hash(&mut hasher);
+    position.y.to_bits().hash(&mut hasher);
+    hasher.finish()
+}
+
+fn checksum_held_tags(tags: &HeldTags) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for tag in tags.iter() {
+        tag.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(SystemSet)]
+pub enum RollbackSystems {
+    /// Ticks the clock and drives the cars before anything else in a rollback frame.
+    Prepare,
+    /// Runs once the physics of a rollback frame are done.
+    Finish,
+}
+
+/// The clock the rollback simulation keeps, which is saved and restored along with it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RollbackClock;
+
+/// Marks a car that is driven by the inputs of the player with this handle.
+#[derive(Clone, Copy, Debug)]
+#[derive(Component)]
+pub struct RollbackDriver(pub usize);
+
+/// Marks the entity whose controls are sent as this peer's inputs.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+pub struct LocalRollbackInput;
+
+#[derive(Debug, Error)]
+pub enum RollbackError {
+    #[error("Failed to open a socket: {0}")]
+    Socket(#[from] io::Error),
+    #[error("Failed to start the session: {0}")]
+    Session(#[from] GgrsError),
+}
+
+/// Starts a two player match against the peer at `remote`, with every packet held back by
+/// `latency` to try out rollback on a single machine.
+pub fn start_p2p_session(
+    local_port: u16,
+    local_player: usize,
+    remote: SocketAddr,
+    latency: Duration,
+) -> Result<Session<RollbackConfig>, RollbackError> {
+    let socket = LaggySocket {
+        socket: UdpNonBlockingSocket::bind_to_port(local_port)?,
+        latency,
+        outbox: VecDeque::new(),
+    };
+    let session = SessionBuilder::<RollbackConfig>::new()
+        .with_num_players(2)
+        .with_input_delay(2)
+        .with_desync_detection_mode(DesyncDetection::On { interval: 10 })
+        .add_player(PlayerType::Local, local_player)?
+        .add_player(PlayerType::Remote(remote), 1 - local_player)?
+        .start_p2p_session(socket)?;
+    Ok(Session::P2P(session))
+}
+
+/// A UDP socket that sends every message a while after it was asked to.
+struct LaggySocket {
+    socket: UdpNonBlockingSocket,
+    latency: Duration,
+    // oldest first
+    outbox: VecDeque<(Instant, Message, SocketAddr)>,
+}
+
+impl LaggySocket {
+    fn flush(&mut self) {
+        let now = Instant::now();
+        while self.outbox.front().is_some_and(|(due, ..)| *due <= now) {
+            let (_, message, address) = self.outbox.pop_front().unwrap();
+            self.socket.send_to(&message, &address);
+        }
+    }
+}
+
+impl NonBlockingSocket<SocketAddr> for LaggySocket {
+    fn send_to(&mut self, message: &Message, address: &SocketAddr) {
+        self.outbox
+            .push_back((Instant::now() + self.latency, message.clone(), *address));
+        self.flush();
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(SocketAddr, Message)> {
+        self.flush();
+        self.socket.receive_all_messages()
+    }
+}
@@ -0,0 +1,432 @@
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+use avian2d::prelude::{Position, Rotation};
+use bevy::{app::AppExit, prelude::*};
+
+use car::{Car, DrivingSystems};
+use laptag::{BombTagIt, LapTagIt, Score};
+use resurfacer::{Peg, Resurfacer};
+
+use crate::{
+    CarSnapshot, ClientId, ClientMessage, InputFrame, LobbyPlayer, NetId, NetSocket,
+    NetcodeSystems, PropKind, PropSnapshot, ServerMessage, Snapshot,
+};
+
+/// Runs the authoritative simulation for remote players.
+///
+/// Clients join a lobby, and once every one of them is ready a [`StartNetworkMatch`] event is
+/// sent so the game can spawn a car with a [`RemoteDriver`] for each of them. From then on their
+/// inputs drive those cars and every car is sent back to them in snapshots.
+pub struct NetServerPlugin {
+    pub address: SocketAddr,
+}
+
+impl Plugin for NetServerPlugin {
+    fn build(&self, app: &mut App) {
+        let socket = NetSocket::bind(self.address)
+            .unwrap_or_else(|error| panic!("Failed to listen on {}: {error}", self.address));
+        info!("Listening for players on {}", self.address);
+        app.insert_resource(socket)
+            .init_resource::<ServerLobby>()
+            .add_event::<StartNetworkMatch>()
+            .configure_sets(Update, NetcodeSystems.before(DrivingSystems))
+            .add_systems(
+                Update,
+                (
+                    Self::receive_client_messages,
+                    Self::drop_silent_clients,
+                    Self::start_when_ready,
+                    Self::broadcast_lobby,
+                    Self::announce_cars,
+                    Self::drive_remote_cars,
+                )
+                    .chain()
+                    .in_set(NetcodeSystems),
+            )
+            .add_systems(PostUpdate, Self::broadcast_snapshots)
+            .add_systems(Last, Self::say_goodbye);
+    }
+}
+
+impl NetServerPlugin {
+    /// Clients that haven't been heard from for this long are dropped.
+    const TIMEOUT: f32 = 5.;
+    const LOBBY_INTERVAL: Duration = Duration::from_millis(500);
+    const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(50);
+
+    fn receive_client_messages(
+        socket: Res<NetSocket>,
+        mut lobby: ResMut<ServerLobby>,
+        drivers: Query<(Entity, &RemoteDriver)>,
+        mut commands: Commands,
+        time: Res<Time>,
+    ) {
+        let now = time.elapsed_seconds();
+        for (address, message) in socket.receive::<ClientMessage>() {
+            if let ClientMessage::Join { name } = &message {
+                let client = lobby.join(address, name.clone(), now);
+                socket.send(&ServerMessage::Welcome { client }, address);
+                continue;
+            }
+            let Some(client) = lobby.hear_from(address, now) else {
+                continue;
+            };
+            let Some(remote) = lobby.clients.get_mut(&client) else {
+                continue;
+            };
+            match message {
+                ClientMessage::Join { .. } | ClientMessage::Heartbeat => {}
+                ClientMessage::Ready => remote.is_ready = true,
+                ClientMessage::Input(input) => {
+                    // a reset is only sent once, so don't let a later frame drop it
+                    let reset = remote.input.reset || input.reset;
+                    remote.input = InputFrame { reset, ..input };
+                }
+                ClientMessage::Leave => {
+                    lobby.leave(client);
+                    for (car, driver) in &drivers {
+                        if driver.0 == client {
+                            commands.entity(car).remove::<RemoteDriver>();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn drop_silent_clients(
+        mut commands: Commands,
+        mut lobby: ResMut<ServerLobby>,
+        drivers: Query<(Entity, &RemoteDriver)>,
+        time: Res<Time>,
+    ) {
+        let silent = lobby.silent_since(time.elapsed_seconds() - Self::TIMEOUT);
+        for client in silent {
+            info!("Dropping {client:?} after {} silent seconds", Self::TIMEOUT);
+            lobby.leave(client);
+            for (car, driver) in &drivers {
+                if driver.0 == client {
+                    commands.entity(car).remove::<RemoteDriver>();
+                }
+            }
+        }
+    }
+
+    fn start_when_ready(
+        mut lobby: ResMut<ServerLobby>,
+        mut starts: EventWriter<StartNetworkMatch>,
+    ) {
+        if lobby.is_running
+            || lobby.clients.is_empty()
+            || lobby.clients.values().any(|remote| !remote.is_ready)
+        {
+            return;
+        }
+        lobby.is_running = true;
+        starts.send(StartNetworkMatch {
+            players: lobby.players(),
+        });
+    }
+
+    fn broadcast_lobby(
+        socket: Res<NetSocket>,
+        lobby: Res<ServerLobby>,
+        time: Res<Time>,
+        mut timer: Local<Timer>,
+        mut was_running: Local<bool>,
+    ) {
+        if *was_running && !lobby.is_running {
+            for remote in lobby.clients.values() {
+                socket.send(&ServerMessage::MatchEnded, remote.address);
+            }
+        }
+        *was_running = lobby.is_running;
+        timer.set_mode(TimerMode::Repeating);
+        timer.set_duration(Self::LOBBY_INTERVAL);
+        if !timer.tick(time.delta()).just_finished() && !lobby.is_changed() {
+            return;
+        }
+        let message = ServerMessage::Lobby {
+            players: lobby.players(),
+        };
+        for remote in lobby.clients.values() {
+            socket.send(&message, remote.address);
+        }
+    }
+
+    fn announce_cars(
+        socket: Res<NetSocket>,
+        lobby: Res<ServerLobby>,
+        new_drivers: Query<(Entity, &RemoteDriver), Added<RemoteDriver>>,
+    ) {
+        for (car, driver) in &new_drivers {
+            if let Some(remote) = lobby.clients.get(&driver.0) {
+                let message = ServerMessage::MatchStarted {
+                    car: Some(NetId::from(car)),
+                };
+                socket.send(&message, remote.address);
+            }
+        }
+    }
+
+    fn drive_remote_cars(
+        mut commands: Commands,
+        mut lobby: ResMut<ServerLobby>,
+        drivers: Query<(Entity, &RemoteDriver), With<Car>>,
+    ) {
+        for (car, driver) in &drivers {
+            let Some(remote) = lobby.clients.get_mut(&driver.0) else {
+                continue;
+            };
+            // resets only happen once per press
+            let reset = std::mem::take(&mut remote.input.reset);
+            let frame = InputFrame {
+                reset,
+                ..remote.input
+            };
+            frame.apply(&mut commands.entity(car));
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn broadcast_snapshots(
+        socket: Res<NetSocket>,
+        lobby: Res<ServerLobby>,
+        cars: Query<
+            (
+                Entity,
+                &Position,
+                &Rotation,
+                Has<LapTagIt>,
+                Has<BombTagIt>,
+                Option<&Score>,
+            ),
+            With<Car>,
+        >,
+        resurfacers: Query<(Entity, &Position, &Rotation), With<Resurfacer>>,
+        pegs: Query<(Entity, &Transform), With<Peg>>,
+        time: Res<Time>,
+        mut timer: Local<Timer>,
+    ) {
+        timer.set_mode(TimerMode::Repeating);
+        timer.set_duration(Self::SNAPSHOT_INTERVAL);
+        if !lobby.is_running || !timer.tick(time.delta()).just_finished() {
+            return;
+        }
+        let snapshot = ServerMessage::Snapshot(Snapshot {
+            time: time.elapsed_seconds(),
+            cars: cars
+                .iter()
+                .map(
+                    |(car, position, rotation, has_lap_tag, has_bomb_tag, score)| CarSnapshot {
+                        id: NetId::from(car),
+                        position: position.to_array(),
+                        angle: rotation.as_radians(),
+                        has_lap_tag,
+                        has_bomb_tag,
+                        score: score.map_or(0, |score| **score),
+                    },
+                )
+                .collect(),
+            props: resurfacers
+                .iter()
+                .map(|(resurfacer, position, rotation)| PropSnapshot {
+                    id: NetId::from(resurfacer),
+                    kind: PropKind::Resurfacer,
+                    position: position.to_array(),
+                    angle: rotation.as_radians(),
+                })
+                .chain(pegs.iter().map(|(peg, transform)| PropSnapshot {
+                    id: NetId::from(peg),
+                    kind: PropKind::Peg,
+                    position: transform.translation.xy().to_array(),
+                    angle: 0.,
+                }))
+                .collect(),
+        });
+        for remote in lobby.clients.values() {
+            socket.send(&snapshot, remote.address);
+        }
+    }
+
+    fn say_goodbye(
+        mut exits: EventReader<AppExit>,
+        socket: Res<NetSocket>,
+        lobby: Res<ServerLobby>,
+    ) {
+        if exits.read().count() == 0 {
+            return;
+        }
+        // an empty lobby tells clients the server is gone
+        let message = ServerMessage::Lobby { players: vec![] };
+        for remote in lobby.clients.values() {
+            socket.send(&message, remote.address);
+        }
+    }
+}
+
+/// Asks the game to spawn a car for each of these players.
+#[derive(Clone, Debug)]
+#[derive(Event)]
+pub struct StartNetworkMatch {
+    pub players: Vec<LobbyPlayer>,
+}
+
+/// Marks a car that is driven by the inputs of a remote client.
+#[derive(Clone, Copy, Debug)]
+#[derive(Component)]
+pub struct RemoteDriver(pub ClientId);
+
+impl From<Entity> for NetId {
+    fn from(entity: Entity) -> Self {
+        NetId(entity.to_bits())
+    }
+}
+
+#[derive(Debug)]
+struct RemoteClient {
+    address: SocketAddr,
+    name: String,
+    is_ready: bool,
+    input: InputFrame,
+    last_heard: f32,
+}
+
+/// Everyone connected to the server.
+#[derive(Debug, Default)]
+#[derive(Resource)]
+pub struct ServerLobby {
+    clients: HashMap<ClientId, RemoteClient>,
+    by_address: HashMap<SocketAddr, ClientId>,
+    next_client: u32,
+    is_running: bool,
+}
+
+impl ServerLobby {
+    pub fn is_running(&self) -> bool {
+        self.is_running
+    }
+
+    /// Goes back to waiting for everyone to be ready, for example after a match ends.
+    pub fn reopen(&mut self) {
+        self.is_running = false;
+        for remote in self.clients.values_mut() {
+            remote.is_ready = false;
+        }
+    }
+
+    pub fn players(&self) -> Vec<LobbyPlayer> {
+        let mut players = self
+            .clients
+            .iter()
+            .map(|(client, remote)| LobbyPlayer {
+                client: *client,
+                name: remote.name.clone(),
+                is_ready: remote.is_ready,
+            })
+            .collect::<Vec<_>>();
+        players.sort_by_key(|player| player.client.0);
+        players
+    }
+
+    fn join(&mut self, address: SocketAddr, name: String, now: f32) -> ClientId {
+        // a client that didn't hear its welcome asks again
+        if let Some(client) = self.hear_from(address, now) {
+            return client;
+        }
+        let client = ClientId(self.next_client);
+        self.next_client += 1;
+        info!("{name} joined from {address} as {client:?}");
+        self.by_address.insert(address, client);
+        self.clients.insert(
+            client,
+            RemoteClient {
+                address,
+                name,
+                is_ready: false,
+                input: InputFrame::default(),
+                last_heard: now,
+            },
+        );
+        client
+    }
+
+    /// Notes that a joined client was heard from, returning who it is.
+    fn hear_from(&mut self, address: SocketAddr, now: f32) -> Option<ClientId> {
+        let client = self.by_address.get(&address).copied()?;
+        self.clients.get_mut(&client)?.last_heard = now;
+        Some(client)
+    }
+
+    /// Every client that hasn't been heard from since `time`.
+    fn silent_since(&self, time: f32) -> Vec<ClientId> {
+        self.clients
+            .iter()
+            .filter(|(_, remote)| remote.last_heard < time)
+            .map(|(client, _)| *client)
+            .collect()
+    }
+
+    fn leave(&mut self, client: ClientId) {
+        if let Some(remote) = self.clients.remove(&client) {
+            info!("{} left", remote.name);
+            self.by_address.remove(&remote.address);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn test_lobby_joins_and_leaves() {
+        let mut lobby = ServerLobby::default();
+        let first = lobby.join(address(7000), "First".to_string(), 0.);
+        let second = lobby.join(address(7001), "Second".to_string(), 0.);
+        assert_ne!(first, second);
+        // asking again from the same address doesn't join twice
+        assert_eq!(lobby.join(address(7000), "First".to_string(), 1.), first);
+
+        let players = lobby.players();
+        assert_eq!(players.len(), 2);
+        assert_eq!(players[0].client, first);
+        assert_eq!(players[1].client, second);
+        assert!(players.iter().all(|player| !player.is_ready));
+
+        lobby.leave(first);
+        assert_eq!(lobby.hear_from(address(7000), 2.), None);
+        assert_eq!(lobby.players().len(), 1);
+        assert_eq!(lobby.players()[0].name, "Second");
+        // a client that comes back is someone new
+        assert_ne!(lobby.join(address(7000), "First".to_string(), 3.), first);
+    }
+
+    #[test]
+    fn test_lobby_drops_silent_clients() {
+        let mut lobby = ServerLobby::default();
+        let chatty = lobby.join(address(7000), "Chatty".to_string(), 0.);
+        let quiet = lobby.join(address(7001), "Quiet".to_string(), 0.);
+        assert!(lobby.silent_since(0.).is_empty());
+
+        assert_eq!(lobby.hear_from(address(7000), 4.), Some(chatty));
+        assert_eq!(lobby.silent_since(3.), vec![quiet]);
+        assert_eq!(lobby.hear_from(address(7002), 4.), None);
+    }
+
+    #[test]
+    fn test_lobby_reopens() {
+        let mut lobby = ServerLobby::default();
+        let client = lobby.join(address(7000), "Player".to_string(), 0.);
+        lobby.clients.get_mut(&client).unwrap().is_ready = true;
+        lobby.is_running = true;
+
+        lobby.reopen();
+        assert!(!lobby.is_running());
+        assert!(lobby.players().iter().all(|player| !player.is_ready));
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+};
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Packets larger than this are dropped.
+const MAX_PACKET_SIZE: usize = 16 * 1024;
+
+/// A non-blocking UDP socket that sends and receives whole messages.
+#[derive(Debug)]
+#[derive(Resource)]
+pub struct NetSocket {
+    socket: UdpSocket,
+}
+
+impl NetSocket {
+    pub fn bind(address: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket })
+    }
+
+    pub fn send(&self, message: &impl Serialize, to: SocketAddr) {
+        let bytes = match bincode::serialize(message) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                error!("Failed to encode a message: {error}");
+                return;
+            }
+        };
+        if let Err(error) = self.socket.send_to(&bytes, to) {
+            // a full buffer just loses the packet, like the network would
+            if error.kind() != io::ErrorKind::WouldBlock {
+                warn!("Failed to send to {to}: {error}");
+            }
+        }
+    }
+
+    /// Every message that has arrived since the last call.
+    pub fn receive<T: DeserializeOwned>(&self) -> Vec<(SocketAddr, T)> {
+        let mut messages = vec![];
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((size, from)) => match bincode::deserialize(&buffer[..size]) {
+                    Ok(message) => messages.push((from, message)),
+                    Err(error) => warn!("Ignoring a bad packet from {from}: {error}"),
+                },
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                // windows reports an unreachable peer here, which is not worth stopping for
+                Err(error) if error.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(error) => {
+                    warn!("Failed to receive: {error}");
+                    break;
+                }
+            }
+        }
+        messages
+    }
+}
@@ -70,19 +70,27 @@ pub struct PegGraphicsBundle {
     sprite: ColorMesh2dBundle,
 }
 
-impl FromBlueprint<Peg> for PegGraphicsBundle {
-    type Params<'w, 's> = GraphicsAssetsParams<'w>;
-
-    fn from_blueprint(_: &Peg, params: &mut StaticSystemParam<Self::Params<'_, '_>>) -> Self {
-        let params = params.deref_mut();
+impl PegGraphicsBundle {
+    pub fn from_peg(
+        _peg: &Peg,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<ColorMaterial>,
+    ) -> Self {
         Self {
             sprite: ColorMesh2dBundle {
-                material: params
-                    .materials
-                    .add(Color::Srgba(palettes::css::SADDLE_BROWN)),
-                mesh: params.meshes.add(Circle::new(Peg::RADIUS).mesh()).into(),
+                material: materials.add(Color::Srgba(palettes::css::SADDLE_BROWN)),
+                mesh: meshes.add(Circle::new(Peg::RADIUS).mesh()).into(),
                 ..Default::default()
             },
         }
     }
 }
+
+impl FromBlueprint<Peg> for PegGraphicsBundle {
+    type Params<'w, 's> = GraphicsAssetsParams<'w>;
+
+    fn from_blueprint(peg: &Peg, params: &mut StaticSystemParam<Self::Params<'_, '_>>) -> Self {
+        let params = params.deref_mut();
+        Self::from_peg(peg, params.meshes.as_mut(), params.materials.as_mut())
+    }
+}
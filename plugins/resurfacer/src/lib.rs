@@ -1,7 +1,13 @@
 use bevy_reactive_blueprints::Blueprint;
 
-use avian2d::prelude::{Collider, LinearVelocity, RigidBody, Sensor};
-use bevy::prelude::*;
+use avian2d::prelude::{Collider, LinearVelocity, RigidBody, Rotation, Sensor};
+use bevy::{
+    ecs::{
+        entity::{EntityMapper, MapEntities},
+        schedule::{InternedScheduleLabel, ScheduleLabel},
+    },
+    prelude::*,
+};
 
 use entropy::{Entropy, ForkableRng, GlobalEntropy, RngCore};
 use track::{Checkpoint, CheckpointTracker, Track, TrackInterior, Wall};
@@ -11,15 +17,28 @@ mod graphics;
 #[cfg(feature = "graphics")]
 pub use graphics::*;
 
-pub struct ResurfacerPlugin;
+pub struct ResurfacerPlugin {
+    /// The schedule the resurfacer drives and lays down pegs in.
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for ResurfacerPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
 
 impl Plugin for ResurfacerPlugin {
     fn build(&self, app: &mut App) {
         #[cfg(feature = "graphics")]
         app.add_plugins(GraphicsPlugin);
+        app.init_resource::<ResurfacerSettings>();
         app.add_systems(
-            Update,
+            self.schedule,
             (
+                Self::apply_resurfacer_mode,
                 Self::track_last_checkpoint,
                 Self::drive_resurfacer,
                 Self::resurface_track,
@@ -29,11 +48,36 @@ impl Plugin for ResurfacerPlugin {
                 .in_set(ResurfacerSystems),
         );
         app.register_type::<Resurfacer>()
-            .register_type::<TrackResurfacer>();
+            .register_type::<TrackResurfacer>()
+            .register_type::<ResurfacerSettings>()
+            .register_type::<ResurfacerMode>();
     }
 }
 
 impl ResurfacerPlugin {
+    fn apply_resurfacer_mode(
+        mut commands: Commands,
+        settings: Res<ResurfacerSettings>,
+        resurfacers: Query<Entity, With<Resurfacer>>,
+        new_resurfacers: Query<Entity, Added<Resurfacer>>,
+    ) {
+        let targets = if settings.is_changed() {
+            resurfacers.iter().collect::<Vec<_>>()
+        } else {
+            new_resurfacers.iter().collect::<Vec<_>>()
+        };
+        for entity in targets {
+            match settings.mode {
+                ResurfacerMode::Ghost => {
+                    commands.entity(entity).insert(Sensor);
+                }
+                ResurfacerMode::Solid => {
+                    commands.entity(entity).remove::<Sensor>();
+                }
+            }
+        }
+    }
+
     #[allow(clippy::type_complexity)]
     fn track_last_checkpoint(
         tracks: Query<&TrackResurfacer, With<Track>>,
@@ -62,10 +106,10 @@ impl ResurfacerPlugin {
 
     fn drive_resurfacer(
         tracks: Query<(&Track, &TrackResurfacer)>,
-        mut resurfacers: Query<(&mut LinearVelocity, &mut Transform, &Resurfacer)>,
+        mut resurfacers: Query<(&mut LinearVelocity, &mut Rotation, &Transform, &Resurfacer)>,
     ) {
         for (track, resurfacer) in &tracks {
-            let (mut velocity, mut transform, resurfacer) = resurfacers
+            let (mut velocity, mut rotation, transform, resurfacer) = resurfacers
                 .get_mut(**resurfacer)
                 .expect("TrackResurfacer to be a valid Resurfacer entity");
             let chunks = track.chunks().collect::<Vec<_>>();
@@ -74,16 +118,15 @@ impl ResurfacerPlugin {
                 .get(index)
                 .unwrap_or(chunks.first().expect("Track to have chunks"));
             let next_checkpoint_position =
-                Checkpoint::from_chunk(track, next_chunk.clone(), index).position;
-            **velocity = Resurfacer::SPEED
-                * (next_checkpoint_position - transform.translation.xy()).normalize();
-            let target = Vec3::new(
-                next_checkpoint_position.x,
-                next_checkpoint_position.y,
-                transform.translation.z,
-            );
-            // TODO: not working?
-            transform.look_at(target, Vec3::Z);
+                Checkpoint::from_chunk(next_chunk.clone(), index).position;
+            let direction =
+                (next_checkpoint_position - transform.translation.xy()).normalize_or_zero();
+            **velocity = Resurfacer::SPEED * direction;
+            // `Transform::look_at` points the local -Z axis at the target, which is meaningless
+            // for a 2d body, so face the direction of travel by rotating about Z instead
+            if direction != Vec2::ZERO {
+                *rotation = Rotation::radians(direction.to_angle());
+            }
         }
     }
 
@@ -171,7 +214,7 @@ impl ResurfacerPlugin {
                     track
                         .chunks()
                         .enumerate()
-                        .map(|(index, chunk)| Checkpoint::from_chunk(track, chunk, index))
+                        .map(|(index, chunk)| Checkpoint::from_chunk(chunk, index))
                 })
                 .nth(resurfacer.last_checkpoint_index)
                 .expect("Checkpoints iter to be a ring");
@@ -192,6 +235,25 @@ impl ResurfacerPlugin {
 #[derive(SystemSet)]
 pub struct ResurfacerSystems;
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Reflect)]
+pub enum ResurfacerMode {
+    /// The resurfacer is a sensor that cars drive straight through.
+    #[default]
+    Ghost,
+    /// The resurfacer is a solid kinematic body that shoves cars out of its way.
+    Solid,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Resource, Reflect)]
+pub struct ResurfacerSettings {
+    pub mode: ResurfacerMode,
+    /// Whether the resurfacer takes part in tag, stripping flags from cars that touch it
+    /// and passing any tag it holds to the next car it touches.
+    pub swaps_tags: bool,
+}
+
 #[derive(Clone, Debug)]
 #[derive(Component, Reflect)]
 pub struct Resurfacer {
@@ -200,8 +262,9 @@ pub struct Resurfacer {
 
 impl Resurfacer {
     const STARTING_CHECKPOINT: usize = 25;
-    const WIDTH: f32 = 30.;
-    const Z_INDEX: f32 = 25.;
+    /// How long each side of the resurfacer's square is.
+    pub const WIDTH: f32 = 30.;
+    pub const Z_INDEX: f32 = 25.;
     const SPEED: f32 = 120.;
 
     fn transform(position: Vec2, angle: f32) -> Transform {
@@ -247,8 +310,8 @@ pub enum Obstacle {
 pub struct Peg;
 
 impl Peg {
-    const Z_INDEX: f32 = 20.;
-    const RADIUS: f32 = 20.;
+    pub const Z_INDEX: f32 = 20.;
+    pub const RADIUS: f32 = 20.;
 
     pub fn bundle(self, position: Vec2) -> impl Bundle {
         (
@@ -266,10 +329,19 @@ impl Peg {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 #[derive(Component, Deref, Reflect)]
 pub struct CheckpointObstacles(Vec<Entity>);
 
+// pegs that are despawned and brought back by a rollback come back as new entities
+impl MapEntities for CheckpointObstacles {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        for obstacle in &mut self.0 {
+            *obstacle = entity_mapper.map_entity(*obstacle);
+        }
+    }
+}
+
 impl CheckpointObstacles {
     pub fn new(obstacles: Vec<Entity>) -> Self {
         Self(obstacles)
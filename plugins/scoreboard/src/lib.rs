@@ -1,5 +1,7 @@
-use bevy::prelude::*;
-use laptag::Score;
+use std::borrow::Cow;
+
+use bevy::{prelude::*, utils::HashMap};
+use laptag::{Score, Team};
 use sickle_ui::{prelude::*, ui_commands::SetTextExt, SickleUiPlugin};
 
 pub struct ScoreboardPlugin;
@@ -29,7 +31,7 @@ impl ScoreboardPlugin {
                 .right(Val::Px(10.))
                 .top(Val::Px(10.))
                 .height(Val::Auto)
-                .width(Val::Px(240.))
+                .width(Val::Px(300.))
                 .background_color(Color::srgba(0.3, 0.3, 0.3, 0.3))
                 .padding(UiRect::all(Val::Px(10.)));
         }
@@ -37,40 +39,134 @@ impl ScoreboardPlugin {
 
     fn update_scoreboard(
         mut commands: Commands,
-        scores_query: Query<(&CarName, Ref<Score>)>,
+        scores_query: Query<(
+            &CarName,
+            Option<&CarDescription>,
+            Ref<Score>,
+            Option<Ref<Team>>,
+        )>,
         scoreboards: Query<Entity, With<ScoreboardUI>>,
         added_scoreboards: Query<Entity, Added<ScoreboardUI>>,
+        mut removed_teams: RemovedComponents<Team>,
     ) {
         let Ok(entity) = scoreboards.get_single() else {
             return;
         };
 
-        if scores_query.iter().any(|(_, score)| score.is_changed())
+        let teams_changed = removed_teams.read().count() > 0
+            || scores_query
+                .iter()
+                .any(|(_, _, _, team)| team.is_some_and(|team| team.is_changed()));
+        if scores_query
+            .iter()
+            .any(|(_, _, score, _)| score.is_changed())
+            || teams_changed
             || added_scoreboards.contains(entity)
         {
             let mut scores: Vec<(String, u32)> = scores_query
                 .iter()
-                .map(|(car_name, score)| (car_name.to_string(), **score))
+                .map(|(car_name, description, score, _)| (label(car_name, description), **score))
                 .collect::<Vec<(String, u32)>>();
 
             // b.cmp(a) in order to get reverse sorting with largest scores first
             scores.sort_by(|a, b| b.1.cmp(&a.1));
 
+            let mut teams = HashMap::<Team, TeamScores>::default();
+            for (car_name, description, score, team) in &scores_query {
+                let Some(team) = team else {
+                    continue;
+                };
+                let team_scores = teams.entry(*team).or_insert_with(|| TeamScores {
+                    name: team.name().to_string(),
+                    color: team.color(),
+                    total: 0,
+                    scores: vec![],
+                });
+                team_scores.total += **score;
+                team_scores
+                    .scores
+                    .push((label(car_name, description), **score));
+            }
+
             commands.entity(entity).despawn_descendants();
-            commands
-                .ui_builder(entity)
-                .generate_scoreboard_ui(scores)
-                .style()
-                .width(Val::Percent(100.));
+            if teams.is_empty() {
+                commands
+                    .ui_builder(entity)
+                    .generate_scoreboard_ui(scores)
+                    .style()
+                    .width(Val::Percent(100.));
+            } else {
+                let mut teams = teams.into_values().collect::<Vec<_>>();
+                teams.sort_by(|a, b| b.total.cmp(&a.total));
+                for team in teams.iter_mut() {
+                    team.scores.sort_by(|a, b| b.1.cmp(&a.1));
+                }
+                commands
+                    .ui_builder(entity)
+                    .generate_team_scoreboard_ui(teams)
+                    .style()
+                    .width(Val::Percent(100.));
+            }
         }
     }
 }
 
+fn label(name: &CarName, description: Option<&CarDescription>) -> String {
+    match description {
+        Some(description) => format!("{} ({})", **name, **description),
+        None => name.to_string(),
+    }
+}
+
+pub struct TeamScores {
+    pub name: String,
+    pub color: Color,
+    pub total: u32,
+    pub scores: Vec<(String, u32)>,
+}
+
 pub trait UiScoreboardExt {
     fn generate_scoreboard_ui(&mut self, sorted_scores: Vec<(String, u32)>) -> UiBuilder<Entity>;
+
+    fn generate_team_scoreboard_ui(&mut self, sorted_teams: Vec<TeamScores>) -> UiBuilder<Entity>;
 }
 
 impl UiScoreboardExt for UiBuilder<'_, Entity> {
+    fn generate_team_scoreboard_ui(&mut self, sorted_teams: Vec<TeamScores>) -> UiBuilder<Entity> {
+        self.column(|column| {
+            for team in sorted_teams.into_iter() {
+                column
+                    .row(|row| {
+                        row.label(LabelConfig::default())
+                            .entity_commands()
+                            .set_text(format!("Team {}", team.name), None);
+                        row.label(LabelConfig::default())
+                            .entity_commands()
+                            .set_text(team.total.to_string(), None);
+                    })
+                    .style()
+                    .justify_content(JustifyContent::SpaceBetween)
+                    .width(Val::Percent(100.))
+                    .background_color(team.color.with_alpha(0.6));
+                for score in team.scores.into_iter() {
+                    column
+                        .row(|row| {
+                            row.label(LabelConfig::default())
+                                .entity_commands()
+                                .set_text(score.0, None);
+                            row.label(LabelConfig::default())
+                                .entity_commands()
+                                .set_text(score.1.to_string(), None);
+                        })
+                        .style()
+                        .justify_content(JustifyContent::SpaceBetween)
+                        .padding(UiRect::left(Val::Px(12.)))
+                        .width(Val::Percent(100.));
+                }
+            }
+        })
+    }
+
     fn generate_scoreboard_ui(&mut self, sorted_scores: Vec<(String, u32)>) -> UiBuilder<Entity> {
         self.column(|column| {
             for score in sorted_scores.into_iter() {
@@ -103,12 +199,29 @@ pub struct Scoreboard;
 #[derive(Component)]
 pub struct ScoreboardUI;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 #[derive(Component, Deref)]
-pub struct CarName(pub &'static str);
+pub struct CarName(pub Cow<'static, str>);
 
 impl CarName {
     pub const fn new(name: &'static str) -> Self {
-        CarName(name)
+        CarName(Cow::Borrowed(name))
+    }
+}
+
+impl From<String> for CarName {
+    fn from(name: String) -> Self {
+        CarName(Cow::Owned(name))
+    }
+}
+
+/// A few words about a car shown after its name, like how a bot drives.
+#[derive(Clone, Debug)]
+#[derive(Component, Deref)]
+pub struct CarDescription(pub Cow<'static, str>);
+
+impl From<String> for CarDescription {
+    fn from(description: String) -> Self {
+        CarDescription(Cow::Owned(description))
     }
 }
@@ -0,0 +1,119 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{TelemetryFormat, TelemetrySample, TelemetrySettings};
+
+type Column = (&'static str, fn(&TelemetrySample) -> f64);
+
+/// Every recorded value, in the order it is written out.
+///
+/// Flags are written as 0 or 1, and missing values as -1.
+pub const COLUMNS: [Column; 21] = [
+    ("time", |sample| sample.time as f64),
+    ("car", |sample| sample.car as f64),
+    ("x", |sample| sample.position.x as f64),
+    ("y", |sample| sample.position.y as f64),
+    ("velocity_x", |sample| sample.velocity.x as f64),
+    ("velocity_y", |sample| sample.velocity.y as f64),
+    ("speed", |sample| sample.velocity.length() as f64),
+    ("rotation", |sample| sample.rotation as f64),
+    ("slip_angle", |sample| sample.slip_angle as f64),
+    ("back_slip_angle", |sample| sample.back_slip_angle as f64),
+    ("steer", |sample| sample.steer as f64),
+    ("throttle", |sample| sample.throttle as f64),
+    ("handbrake", |sample| flag(sample.is_handbraking)),
+    ("boost", |sample| flag(sample.is_boosting)),
+    ("boost_charge", |sample| sample.boost_charge as f64),
+    ("drifting", |sample| flag(sample.is_drifting)),
+    ("lap_tag", |sample| flag(sample.has_lap_tag)),
+    ("bomb_tag", |sample| flag(sample.has_bomb_tag)),
+    ("held_tags", |sample| sample.held_tags as f64),
+    ("last_checkpoint", |sample| optional(sample.last_checkpoint)),
+    ("lap_checkpoints", |sample| optional(sample.lap_checkpoints)),
+];
+
+fn flag(value: bool) -> f64 {
+    if value {
+        1.
+    } else {
+        0.
+    }
+}
+
+fn optional(value: Option<usize>) -> f64 {
+    value.map_or(-1., |value| value as f64)
+}
+
+#[derive(Serialize)]
+struct ColumnarTable<'a> {
+    rows: usize,
+    columns: Vec<ColumnValues<'a>>,
+}
+
+#[derive(Serialize)]
+struct ColumnValues<'a> {
+    name: &'a str,
+    values: Vec<f64>,
+}
+
+/// Writes one match worth of samples to a new file and returns where it went.
+pub fn write_match(
+    settings: &TelemetrySettings,
+    match_index: usize,
+    samples: &[TelemetrySample],
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(&settings.directory)?;
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let extension = match settings.format {
+        TelemetryFormat::Csv => "csv",
+        TelemetryFormat::Columnar => "columns.ron",
+    };
+    let path = settings
+        .directory
+        .join(format!("match-{started_at}-{match_index}.{extension}"));
+    let mut file = io::BufWriter::new(fs::File::create(&path)?);
+    match settings.format {
+        TelemetryFormat::Csv => write_csv(&mut file, samples)?,
+        TelemetryFormat::Columnar => write_columnar(&mut file, samples)?,
+    }
+    file.flush()?;
+    Ok(path)
+}
+
+fn write_csv(writer: &mut impl Write, samples: &[TelemetrySample]) -> io::Result<()> {
+    let header = COLUMNS.map(|(name, _)| name).join(",");
+    writeln!(writer, "{header}")?;
+    for sample in samples {
+        let row = COLUMNS
+            .iter()
+            .map(|(_, value)| value(sample).to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        writeln!(writer, "{row}")?;
+    }
+    Ok(())
+}
+
+fn write_columnar(writer: &mut impl Write, samples: &[TelemetrySample]) -> io::Result<()> {
+    let table = ColumnarTable {
+        rows: samples.len(),
+        columns: COLUMNS
+            .iter()
+            .map(|(name, value)| ColumnValues {
+                name,
+                values: samples.iter().map(value).collect(),
+            })
+            .collect(),
+    };
+    let text =
+        ron::to_string(&table).map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+    writer.write_all(text.as_bytes())
+}
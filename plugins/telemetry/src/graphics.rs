@@ -0,0 +1,172 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+
+use crate::{TelemetryLog, TelemetrySystems, TelemetryTracked};
+
+pub(crate) struct GraphicsPlugin;
+
+impl Plugin for GraphicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (Self::spawn_overlay, Self::update_overlay)
+                .chain()
+                .after(TelemetrySystems),
+        );
+    }
+}
+
+impl GraphicsPlugin {
+    /// How many of the latest samples are shown.
+    const HISTORY: usize = 120;
+    const WIDTH: f32 = 240.;
+    const HEIGHT: f32 = 80.;
+    /// Speed at the top of the graph.
+    const TOP_SPEED: f32 = 1200.;
+
+    fn spawn_overlay(
+        mut commands: Commands,
+        new_tracked: Query<(), Added<TelemetryTracked>>,
+        overlays: Query<(), With<TelemetryOverlay>>,
+    ) {
+        if new_tracked.is_empty() || !overlays.is_empty() {
+            return;
+        }
+        let column_width = Self::WIDTH / Self::HISTORY as f32;
+        commands
+            .spawn((
+                Name::new("Telemetry Overlay"),
+                TelemetryOverlay,
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        right: Val::Px(10.),
+                        bottom: Val::Px(10.),
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(4.),
+                        padding: UiRect::all(Val::Px(6.)),
+                        ..Default::default()
+                    },
+                    background_color: Color::srgba(0.1, 0.1, 0.1, 0.6).into(),
+                    ..Default::default()
+                },
+            ))
+            .with_children(|builder| {
+                builder.spawn(TextBundle::from_sections([
+                    TextSection::new(
+                        "SPEED ",
+                        TextStyle {
+                            font_size: 16.,
+                            color: GraphSeries::Speed.color(),
+                            ..Default::default()
+                        },
+                    ),
+                    TextSection::new(
+                        "SLIP",
+                        TextStyle {
+                            font_size: 16.,
+                            color: GraphSeries::Slip.color(),
+                            ..Default::default()
+                        },
+                    ),
+                ]));
+                builder
+                    .spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(Self::WIDTH),
+                            height: Val::Px(Self::HEIGHT),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .with_children(|builder| {
+                        for index in 0..Self::HISTORY {
+                            for series in [GraphSeries::Speed, GraphSeries::Slip] {
+                                builder.spawn((
+                                    GraphPoint { index, series },
+                                    NodeBundle {
+                                        style: Style {
+                                            position_type: PositionType::Absolute,
+                                            left: Val::Px(index as f32 * column_width),
+                                            bottom: Val::Px(0.),
+                                            width: Val::Px(column_width.max(1.)),
+                                            height: Val::Px(2.),
+                                            ..Default::default()
+                                        },
+                                        background_color: series.color().into(),
+                                        visibility: Visibility::Hidden,
+                                        ..Default::default()
+                                    },
+                                ));
+                            }
+                        }
+                    });
+            });
+    }
+
+    fn update_overlay(
+        log: Res<TelemetryLog>,
+        tracked: Query<Entity, With<TelemetryTracked>>,
+        mut points: Query<(&GraphPoint, &mut Style, &mut Visibility)>,
+    ) {
+        if !log.is_changed() {
+            return;
+        }
+        let Ok(tracked) = tracked.get_single() else {
+            return;
+        };
+        // newest samples last, so the graph scrolls to the left
+        let mut history = log
+            .samples()
+            .iter()
+            .rev()
+            .filter(|sample| sample.car == tracked.index())
+            .take(Self::HISTORY)
+            .collect::<Vec<_>>();
+        history.reverse();
+        let first_index = Self::HISTORY - history.len();
+        for (point, mut style, mut visibility) in &mut points {
+            let Some(sample) = point
+                .index
+                .checked_sub(first_index)
+                .and_then(|index| history.get(index))
+            else {
+                *visibility = Visibility::Hidden;
+                continue;
+            };
+            let value = match point.series {
+                GraphSeries::Speed => sample.velocity.length() / Self::TOP_SPEED,
+                GraphSeries::Slip => sample.slip_angle.abs() / FRAC_PI_2,
+            };
+            style.bottom = Val::Px(value.clamp(0., 1.) * (Self::HEIGHT - 2.));
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct TelemetryOverlay;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GraphSeries {
+    Speed,
+    Slip,
+}
+
+impl GraphSeries {
+    fn color(&self) -> Color {
+        match self {
+            GraphSeries::Speed => Color::srgb(0.3, 0.9, 0.4),
+            GraphSeries::Slip => Color::srgb(1., 0.5, 0.2),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Component)]
+struct GraphPoint {
+    index: usize,
+    series: GraphSeries,
+}
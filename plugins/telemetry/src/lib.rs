@@ -0,0 +1,261 @@
+use std::{path::PathBuf, time::Duration};
+
+use avian2d::prelude::{LinearVelocity, Position, Rotation};
+use bevy::{app::AppExit, prelude::*};
+
+use car::{
+    slip_angle, AccelerateAction, Boost, BoostAction, Car, CarParts, Drifting, HandbrakeAction,
+    SteerAction, Wheel,
+};
+use laptag::{BombTagIt, HeldTags, LapTagIt};
+use track::{CheckpointTracker, LastCheckpoint};
+
+mod export;
+pub use export::*;
+
+#[cfg(feature = "graphics")]
+mod graphics;
+#[cfg(feature = "graphics")]
+pub use graphics::*;
+
+/// Records the state of every car at a fixed rate and writes it out at the end of each match.
+///
+/// Nothing is recorded unless this plugin is added.
+#[derive(Default)]
+pub struct TelemetryPlugin {
+    pub settings: TelemetrySettings,
+}
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(feature = "graphics")]
+        app.add_plugins(graphics::GraphicsPlugin);
+
+        app.insert_resource(self.settings.clone())
+            .insert_resource(TelemetryClock(Timer::new(
+                self.settings.interval,
+                TimerMode::Repeating,
+            )))
+            .init_resource::<TelemetryLog>()
+            .add_event::<ExportTelemetry>()
+            // actions are read before they are cleared during driving
+            .configure_sets(Update, TelemetrySystems.before(car::DrivingSystems))
+            .add_systems(
+                Update,
+                (Self::sample_cars, Self::export_telemetry)
+                    .chain()
+                    .in_set(TelemetrySystems),
+            )
+            .add_systems(Last, Self::export_on_exit);
+        app.register_type::<TelemetrySettings>()
+            .register_type::<TelemetryFormat>()
+            .register_type::<TelemetryTracked>();
+    }
+}
+
+impl TelemetryPlugin {
+    #[allow(clippy::type_complexity)]
+    fn sample_cars(
+        time: Res<Time>,
+        mut clock: ResMut<TelemetryClock>,
+        mut log: ResMut<TelemetryLog>,
+        cars: Query<
+            (
+                Entity,
+                (&Position, &LinearVelocity, &Rotation, &CarParts),
+                (
+                    Option<&SteerAction>,
+                    Option<&AccelerateAction>,
+                    Has<HandbrakeAction>,
+                    Has<BoostAction>,
+                    Option<&Boost>,
+                    Has<Drifting>,
+                ),
+                (
+                    Has<LapTagIt>,
+                    Has<BombTagIt>,
+                    Option<&HeldTags>,
+                    Option<&LastCheckpoint>,
+                    Option<&CheckpointTracker>,
+                ),
+            ),
+            With<Car>,
+        >,
+        wheels: Query<(&LinearVelocity, &Rotation), With<Wheel>>,
+    ) {
+        if !clock.tick(time.delta()).just_finished() {
+            return;
+        }
+        let time = time.elapsed_seconds();
+        for (
+            car,
+            (position, velocity, rotation, parts),
+            (steer, accelerate, is_handbraking, is_boosting, boost, is_drifting),
+            (has_lap_tag, has_bomb_tag, held_tags, last_checkpoint, tracker),
+        ) in &cars
+        {
+            // how far the back end is sliding, averaged over both back wheels
+            let back_slips = parts
+                .back_wheels()
+                .into_iter()
+                .filter_map(|wheel| wheels.get(wheel).ok())
+                .map(|(velocity, rotation)| slip_angle(**velocity, rotation))
+                .collect::<Vec<_>>();
+            let back_slip_angle = if back_slips.is_empty() {
+                0.
+            } else {
+                back_slips.iter().sum::<f32>() / back_slips.len() as f32
+            };
+            log.samples.push(TelemetrySample {
+                time,
+                car: car.index(),
+                position: **position,
+                velocity: **velocity,
+                rotation: rotation.as_radians(),
+                slip_angle: slip_angle(**velocity, rotation),
+                back_slip_angle,
+                steer: steer.map_or(0., |steer| **steer),
+                throttle: match accelerate {
+                    Some(AccelerateAction::Forward) => 1.,
+                    Some(AccelerateAction::Backward) => -1.,
+                    None => 0.,
+                },
+                is_handbraking,
+                is_boosting,
+                boost_charge: boost.map_or(0., Boost::charge),
+                is_drifting,
+                has_lap_tag,
+                has_bomb_tag,
+                held_tags: held_tags.map_or(0, |held| held.iter().count()),
+                last_checkpoint: last_checkpoint.and_then(|checkpoint| **checkpoint),
+                lap_checkpoints: tracker.map(CheckpointTracker::len),
+            });
+        }
+    }
+
+    fn export_telemetry(
+        mut exports: EventReader<ExportTelemetry>,
+        mut log: ResMut<TelemetryLog>,
+        settings: Res<TelemetrySettings>,
+    ) {
+        if exports.read().count() == 0 {
+            return;
+        }
+        log.export(&settings);
+    }
+
+    fn export_on_exit(
+        mut exits: EventReader<AppExit>,
+        mut log: ResMut<TelemetryLog>,
+        settings: Res<TelemetrySettings>,
+    ) {
+        if exits.read().count() == 0 {
+            return;
+        }
+        log.export(&settings);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(SystemSet)]
+pub struct TelemetrySystems;
+
+#[derive(Clone, Debug)]
+#[derive(Resource, Reflect)]
+pub struct TelemetrySettings {
+    /// How often every car is sampled.
+    pub interval: Duration,
+    /// Where match files are written.
+    pub directory: PathBuf,
+    pub format: TelemetryFormat,
+}
+
+impl Default for TelemetrySettings {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(50),
+            directory: PathBuf::from("telemetry"),
+            format: TelemetryFormat::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Reflect)]
+pub enum TelemetryFormat {
+    /// One row per sample, for spreadsheets and quick plots.
+    #[default]
+    Csv,
+    /// One list of values per column, which is quicker to load for analysis.
+    Columnar,
+}
+
+/// Writes out everything recorded so far as one match and starts a new one.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Event)]
+pub struct ExportTelemetry;
+
+/// Marks the car whose telemetry is graphed on screen.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct TelemetryTracked;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TelemetrySample {
+    /// Seconds since the app started.
+    pub time: f32,
+    /// Index of the car entity, which stays the same for the whole match.
+    pub car: u32,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub rotation: f32,
+    pub slip_angle: f32,
+    pub back_slip_angle: f32,
+    pub steer: f32,
+    /// 1 when accelerating, -1 when braking or reversing.
+    pub throttle: f32,
+    pub is_handbraking: bool,
+    pub is_boosting: bool,
+    pub boost_charge: f32,
+    pub is_drifting: bool,
+    pub has_lap_tag: bool,
+    pub has_bomb_tag: bool,
+    pub held_tags: usize,
+    pub last_checkpoint: Option<usize>,
+    /// Checkpoints reached this lap, for cars that are racing a lap.
+    pub lap_checkpoints: Option<usize>,
+}
+
+/// Everything recorded during the current match.
+#[derive(Debug, Default)]
+#[derive(Resource)]
+pub struct TelemetryLog {
+    samples: Vec<TelemetrySample>,
+    matches_exported: usize,
+}
+
+impl TelemetryLog {
+    pub fn samples(&self) -> &[TelemetrySample] {
+        &self.samples
+    }
+
+    fn export(&mut self, settings: &TelemetrySettings) {
+        if self.samples.is_empty() {
+            return;
+        }
+        let samples = std::mem::take(&mut self.samples);
+        match write_match(settings, self.matches_exported, &samples) {
+            Ok(path) => info!(
+                "Wrote {} telemetry samples to {}",
+                samples.len(),
+                path.display()
+            ),
+            Err(error) => error!("Failed to write telemetry: {error}"),
+        }
+        self.matches_exported += 1;
+    }
+}
+
+#[derive(Debug)]
+#[derive(Resource, Deref, DerefMut)]
+struct TelemetryClock(Timer);
@@ -0,0 +1,208 @@
+use std::{f32::consts::TAU, fmt};
+
+use bevy::prelude::*;
+use bevy_prng::WyRand;
+use rand_core::{RngCore, SeedableRng};
+
+use crate::{Track, TrackShape};
+
+/// How far apart chunks are along the middle of the road.
+const CHUNK_SPACING: f32 = 130.;
+const SAMPLES_PER_SEGMENT: usize = 32;
+
+/// Builds a random closed course, the same one every time for the same settings.
+///
+/// Control points are scattered around an ellipse and smoothed into a loop, which is rolled
+/// again until every corner is wide enough and no stretch of road runs into another. Each
+/// failed attempt is calmer than the last, ending with a plain ellipse that always fits.
+#[derive(Clone, Copy, Debug)]
+#[derive(Reflect)]
+pub struct TrackGenerator {
+    pub seed: u64,
+    /// From 0 for a gentle loop to 1 for lots of tight corners.
+    pub difficulty: f32,
+    /// The narrowest the road may be, for example to fit a starting grid across it.
+    pub min_width: f32,
+}
+
+impl TrackGenerator {
+    const HALF_EXTENTS: Vec2 = Vec2::new(2000., 1200.);
+    const MAX_ATTEMPTS: usize = 64;
+    /// How far apart the middles of two stretches of road have to be, in road widths.
+    const MIN_SEPARATION: f32 = 1.2;
+
+    pub fn new(seed: u64, difficulty: f32, min_width: f32) -> Self {
+        Self {
+            seed,
+            difficulty,
+            min_width,
+        }
+    }
+
+    pub fn generate(&self) -> Track {
+        let mut rng = WyRand::seed_from_u64(self.seed);
+        let difficulty = self.difficulty.clamp(0., 1.);
+        let width = self.min_width.max(700. - 200. * difficulty);
+        // grow the course with the road so the ellipse keeps fitting
+        let half_extents = Self::HALF_EXTENTS * (width / 600.).max(1.);
+        let min_corner_radius = (width * 0.6).max(500. - 200. * difficulty);
+        let control_point_count = 8 + (2. * difficulty).round() as usize;
+
+        for attempt in 0..Self::MAX_ATTEMPTS {
+            let roughness = difficulty * (1. - attempt as f32 / (Self::MAX_ATTEMPTS - 1) as f32);
+            let control_points =
+                Self::control_points(&mut rng, control_point_count, roughness, half_extents);
+            let centerline = resample(&smooth(&control_points));
+            if Self::find_flaw(&centerline, width, min_corner_radius).is_none() {
+                return Track::from_centerline(centerline, width);
+            }
+        }
+        warn!("No track fit seed {}, using an ellipse", self.seed);
+        let control_points = Self::control_points(&mut rng, control_point_count, 0., half_extents);
+        Track::from_control_points(&control_points, width)
+    }
+
+    // counterclockwise, since each point only strays less than half a step from its angle
+    fn control_points(
+        rng: &mut WyRand,
+        count: usize,
+        roughness: f32,
+        half_extents: Vec2,
+    ) -> Vec<Vec2> {
+        let mut signed_random = || rng.next_u32() as f32 / u32::MAX as f32 * 2. - 1.;
+        (0..count)
+            .map(|index| {
+                let angle = TAU / count as f32 * (index as f32 + 0.3 * roughness * signed_random());
+                let distance = 1. + 0.3 * roughness * signed_random();
+                Vec2::from_angle(angle) * half_extents * distance
+            })
+            .collect()
+    }
+
+    fn find_flaw(centerline: &[Vec2], width: f32, min_corner_radius: f32) -> Option<TrackFlaw> {
+        let count = centerline.len();
+        let has_wide_corners = (0..count).all(|index| {
+            let previous = centerline[(index + count - 1) % count];
+            let next = centerline[(index + 1) % count];
+            corner_radius(previous, centerline[index], next) >= min_corner_radius
+        });
+        if !has_wide_corners {
+            return Some(TrackFlaw::TightCorner);
+        }
+        // a U-turn at the tightest radius just fits within this many chunks, so anything
+        // further along has to be clear of the road
+        let neighbourhood = (width * 2. / CHUNK_SPACING).ceil() as usize;
+        let is_clear = (0..count).all(|i| {
+            (i + 1..count).all(|j| {
+                let gap = (j - i).min(count - (j - i));
+                gap <= neighbourhood
+                    || centerline[i].distance(centerline[j]) >= width * Self::MIN_SEPARATION
+            })
+        });
+        (!is_clear).then_some(TrackFlaw::Overlap)
+    }
+}
+
+/// What keeps a course from being driven.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrackFlaw {
+    /// A corner is so tight that the inside of the road folds over itself.
+    TightCorner,
+    /// Two stretches of road run into each other.
+    Overlap,
+}
+
+impl fmt::Display for TrackFlaw {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrackFlaw::TightCorner => write!(f, "a corner is too tight for the road's width"),
+            TrackFlaw::Overlap => write!(f, "the road runs into itself"),
+        }
+    }
+}
+
+impl Track {
+    /// A road `thickness` wide, smoothed through a closed loop of control points in either
+    /// direction, starting at the first one.
+    pub fn from_control_points(control_points: &[Vec2], thickness: f32) -> Self {
+        let mut centerline = resample(&smooth(control_points));
+        let double_area: f32 = centerline
+            .iter()
+            .zip(centerline.iter().cycle().skip(1))
+            .map(|(a, b)| a.perp_dot(*b))
+            .sum();
+        if double_area < 0. {
+            // turn it counterclockwise without moving the start
+            centerline[1..].reverse();
+        }
+        Track::from_centerline(centerline, thickness)
+    }
+
+    /// Checks a course the same way generated ones are, allowing any corner that keeps the
+    /// inside of the road from folding over.
+    pub fn find_flaw(&self) -> Option<TrackFlaw> {
+        match self.shape() {
+            TrackShape::Oval { .. } => None,
+            TrackShape::Path { centerline } => {
+                TrackGenerator::find_flaw(centerline, self.thickness(), self.thickness() / 2.)
+            }
+        }
+    }
+}
+
+// a closed Catmull-Rom spline through every control point
+fn smooth(control_points: &[Vec2]) -> Vec<Vec2> {
+    let count = control_points.len();
+    (0..count)
+        .flat_map(|index| {
+            let p0 = control_points[(index + count - 1) % count];
+            let p1 = control_points[index];
+            let p2 = control_points[(index + 1) % count];
+            let p3 = control_points[(index + 2) % count];
+            (0..SAMPLES_PER_SEGMENT).map(move |sample| {
+                let t = sample as f32 / SAMPLES_PER_SEGMENT as f32;
+                0.5 * (2. * p1
+                    + (p2 - p0) * t
+                    + (2. * p0 - 5. * p1 + 4. * p2 - p3) * t * t
+                    + (3. * p1 - p0 - 3. * p2 + p3) * t * t * t)
+            })
+        })
+        .collect()
+}
+
+// evenly spaced points along the closed polyline, one per chunk
+fn resample(points: &[Vec2]) -> Vec<Vec2> {
+    let segments = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(start, end)| (*start, *end))
+        .collect::<Vec<_>>();
+    let length: f32 = segments
+        .iter()
+        .map(|(start, end)| start.distance(*end))
+        .sum();
+    let count = ((length / CHUNK_SPACING).round() as usize).max(3);
+    let step = length / count as f32;
+    let mut resampled = Vec::with_capacity(count);
+    let mut travelled = 0.;
+    for (start, end) in segments {
+        let segment_length = start.distance(end);
+        while resampled.len() < count
+            && (resampled.len() as f32 * step) < travelled + segment_length
+        {
+            let t = (resampled.len() as f32 * step - travelled) / segment_length;
+            resampled.push(start.lerp(end, t));
+        }
+        travelled += segment_length;
+    }
+    resampled
+}
+
+// the radius of the circle through all three points
+fn corner_radius(a: Vec2, b: Vec2, c: Vec2) -> f32 {
+    let double_area = (b - a).perp_dot(c - a).abs();
+    if double_area <= f32::EPSILON {
+        return f32::INFINITY;
+    }
+    a.distance(b) * b.distance(c) * c.distance(a) / (2. * double_area)
+}
@@ -3,12 +3,16 @@ use std::ops::DerefMut;
 use bevy::color::palettes;
 use bevy::ecs::system::{StaticSystemParam, SystemParam};
 use bevy::prelude::*;
+use bevy::render::{
+    mesh::{Indices, PrimitiveTopology},
+    render_asset::RenderAssetUsages,
+};
 use bevy_asset_loader::prelude::{
     AssetCollection, ConfigureLoadingState, LoadingState, LoadingStateAppExt,
 };
 use bevy_reactive_blueprints::{AsChild, Blueprint, BlueprintPlugin, FromBlueprint};
 
-use crate::{Checkpoint, CheckpointTracker, Track, TrackInterior, Wall};
+use crate::{Checkpoint, CheckpointTracker, Track, TrackInterior, TrackShape, Wall};
 
 pub struct GraphicsPlugin;
 
@@ -110,16 +114,46 @@ impl TrackGraphicsBundle {
         meshes: &mut Assets<Mesh>,
         materials: &mut Assets<ColorMaterial>,
     ) -> Self {
+        let mesh = match track.shape {
+            TrackShape::Oval { .. } => Capsule2d::new(track.radius, track.half_length).mesh(),
+            TrackShape::Path { .. } => Self::road_mesh(track),
+        };
         Self {
             sprite: ColorMesh2dBundle {
                 material: materials.add(Track::ASPHALT),
-                mesh: meshes
-                    .add(Capsule2d::new(track.radius, track.half_length).mesh())
-                    .into(),
+                mesh: meshes.add(mesh).into(),
                 ..Default::default()
             },
         }
     }
+
+    // a strip of quads between the edges of each chunk and the next
+    fn road_mesh(track: &Track) -> Mesh {
+        let chunks = track.chunks().collect::<Vec<_>>();
+        let positions = chunks
+            .iter()
+            .flat_map(|chunk| [chunk.inner_edge(), chunk.outer_edge()])
+            .map(|point| [point.x, point.y, 0.])
+            .collect::<Vec<_>>();
+        let count = chunks.len() as u32;
+        let indices = (0..count)
+            .flat_map(|index| {
+                let next = (index + 1) % count;
+                let (inner, outer) = (index * 2, index * 2 + 1);
+                let (next_inner, next_outer) = (next * 2, next * 2 + 1);
+                [inner, outer, next_outer, inner, next_outer, next_inner]
+            })
+            .collect::<Vec<_>>();
+        let vertex_count = positions.len();
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; vertex_count])
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; vertex_count])
+        .with_inserted_indices(Indices::U32(indices))
+    }
 }
 
 impl FromBlueprint<Track> for TrackGraphicsBundle {
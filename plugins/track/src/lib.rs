@@ -1,17 +1,32 @@
 use std::f32::consts::{FRAC_PI_2, PI};
+use std::hash::{DefaultHasher, Hash, Hasher};
 
 use avian2d::prelude::{Collider, CollisionLayers, CollisionStarted, LayerMask, RigidBody, Sensor};
 use bevy::color::palettes;
+use bevy::ecs::schedule::{InternedScheduleLabel, ScheduleLabel};
 use bevy::prelude::*;
 use bevy::utils::EntityHashSet;
 use bevy_reactive_blueprints::Blueprint;
 
+mod generator;
+pub use generator::*;
 #[cfg(feature = "graphics")]
 mod graphics;
 #[cfg(feature = "graphics")]
 pub use graphics::*;
 
-pub struct TrackPlugin;
+pub struct TrackPlugin {
+    /// The schedule laps are counted in.
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for TrackPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
 
 impl Plugin for TrackPlugin {
     fn build(&self, app: &mut App) {
@@ -19,16 +34,22 @@ impl Plugin for TrackPlugin {
         app.add_plugins(GraphicsPlugin);
         app.add_event::<LapComplete>();
         app.add_systems(
-            Update,
-            (Self::spawn_checkpoints, Self::track_checkpoints)
+            self.schedule,
+            (
+                Self::spawn_checkpoints,
+                Self::track_checkpoints,
+                Self::record_last_checkpoints,
+            )
                 .chain()
                 .in_set(TrackSystems),
         );
         app.register_type::<Track>()
+            .register_type::<TrackShape>()
             .register_type::<TrackInterior>()
             .register_type::<Checkpoint>()
             .register_type::<Checkpoints>()
             .register_type::<CheckpointTracker>()
+            .register_type::<LastCheckpoint>()
             .register_type::<LapComplete>();
     }
 }
@@ -44,7 +65,7 @@ impl TrackPlugin {
             let mut chunks = track.chunks().collect::<Vec<_>>();
             for (index, chunk) in chunks.iter().enumerate() {
                 let checkpoint = commands
-                    .spawn(Checkpoint::from_chunk(track, chunk.clone(), index).bundle())
+                    .spawn(Checkpoint::from_chunk(chunk.clone(), index).bundle())
                     .id();
                 checkpoints.push(checkpoint);
             }
@@ -55,7 +76,7 @@ impl TrackPlugin {
                 let chunk1 = &chunk_pair[0];
                 let chunk2 = &chunk_pair[1];
                 let wall = commands
-                    .spawn(Wall::between_chunks(track, chunk1.clone(), chunk2.clone()).bundle())
+                    .spawn(Wall::between_chunks(chunk1.clone(), chunk2.clone()).bundle())
                     .id();
                 walls.push(wall);
             }
@@ -96,6 +117,25 @@ impl TrackPlugin {
             }
         }
     }
+
+    fn record_last_checkpoints(
+        mut collisions: EventReader<CollisionStarted>,
+        mut racers: Query<&mut LastCheckpoint>,
+        checkpoints: Query<&Checkpoint>,
+    ) {
+        for CollisionStarted(entity1, entity2) in collisions.read() {
+            let (racer, checkpoint) = if let Ok(checkpoint) = checkpoints.get(*entity2) {
+                (*entity1, checkpoint)
+            } else if let Ok(checkpoint) = checkpoints.get(*entity1) {
+                (*entity2, checkpoint)
+            } else {
+                continue;
+            };
+            if let Ok(mut last_checkpoint) = racers.get_mut(racer) {
+                last_checkpoint.0 = Some(checkpoint.index);
+            }
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -108,7 +148,17 @@ pub struct Track {
     half_length: f32,
     radius: f32,
     thickness: f32,
-    subdivisions_per_chunk: usize,
+    shape: TrackShape,
+}
+
+#[derive(Clone, Debug)]
+#[derive(Reflect)]
+pub enum TrackShape {
+    /// Two straights joined by half circles, each split into this many chunks.
+    Oval { subdivisions_per_chunk: usize },
+    /// A closed loop through the middle of the road, going counterclockwise with a point for
+    /// every chunk.
+    Path { centerline: Vec<Vec2> },
 }
 
 impl Default for Track {
@@ -126,25 +176,76 @@ impl Track {
             half_length,
             radius,
             thickness,
-            subdivisions_per_chunk: subdivisions,
+            shape: TrackShape::Oval {
+                subdivisions_per_chunk: subdivisions,
+            },
         }
     }
 
+    /// A road `thickness` wide around a closed, counterclockwise `centerline`.
+    pub fn from_centerline(centerline: Vec<Vec2>, thickness: f32) -> Self {
+        let mut track = Track {
+            half_length: 0.,
+            radius: 0.,
+            thickness,
+            shape: TrackShape::Path { centerline },
+        };
+        let extents = track
+            .chunks()
+            .map(|chunk| chunk.outer_edge().abs())
+            .fold(Vec2::ZERO, Vec2::max);
+        track.half_length = extents.x;
+        track.radius = extents.y;
+        track
+    }
+
     pub fn bundle(self) -> impl Bundle {
+        // the oval's capsule mesh is upright, everything else is drawn where it is
+        let rotation = match self.shape {
+            TrackShape::Oval { .. } => FRAC_PI_2,
+            TrackShape::Path { .. } => 0.,
+        };
         (
             Blueprint::new(self.clone()),
             SpatialBundle::from_transform(Transform::from_rotation(Quat::from_rotation_z(
-                FRAC_PI_2,
+                rotation,
             ))),
             Name::new("Track"),
             self,
         )
     }
 
+    /// A name that is the same for every track with the same shape, for saving records against.
+    pub fn key(&self) -> String {
+        match &self.shape {
+            TrackShape::Oval {
+                subdivisions_per_chunk,
+            } => format!(
+                "oval-{}-{}-{}-{}",
+                self.half_length, self.radius, self.thickness, subdivisions_per_chunk
+            ),
+            TrackShape::Path { centerline } => {
+                let mut hasher = DefaultHasher::new();
+                self.thickness.to_bits().hash(&mut hasher);
+                for point in centerline {
+                    point.x.to_bits().hash(&mut hasher);
+                    point.y.to_bits().hash(&mut hasher);
+                }
+                format!("path-{:016x}", hasher.finish())
+            }
+        }
+    }
+
+    pub fn shape(&self) -> &TrackShape {
+        &self.shape
+    }
+
+    /// How far the track reaches along x. On an oval, this is to the tip of either end.
     pub fn half_length(&self) -> f32 {
         self.half_length
     }
 
+    /// How far the track reaches along y. On an oval, this is also the radius of the turns.
     pub fn radius(&self) -> f32 {
         self.radius
     }
@@ -157,21 +258,62 @@ impl Track {
         self.radius - self.thickness
     }
 
+    /// The grass inside the track, for shapes that have one.
+    pub fn interior(&self) -> Option<TrackInterior> {
+        match self.shape {
+            TrackShape::Oval { .. } => Some(TrackInterior::from_track(self)),
+            TrackShape::Path { .. } => None,
+        }
+    }
+
     pub fn chunks(&self) -> impl Iterator<Item = TrackChunk> + '_ {
+        let chunks: Vec<TrackChunk> = match &self.shape {
+            TrackShape::Oval {
+                subdivisions_per_chunk,
+            } => self.oval_chunks(*subdivisions_per_chunk).collect(),
+            TrackShape::Path { centerline } => self.path_chunks(centerline).collect(),
+        };
+        chunks.into_iter()
+    }
+
+    fn path_chunks<'a>(&self, centerline: &'a [Vec2]) -> impl Iterator<Item = TrackChunk> + 'a {
+        let half_thickness = self.thickness / 2.;
+        let count = centerline.len();
+        (0..count).map(move |index| {
+            let previous = centerline[(index + count - 1) % count];
+            let next = centerline[(index + 1) % count];
+            let tangent = (next - previous).normalize_or_zero();
+            // to the right of the direction of travel is outside on a counterclockwise loop
+            let outward = Vec2::new(tangent.y, -tangent.x);
+            TrackChunk::new(
+                centerline[index],
+                outward.to_angle(),
+                -half_thickness,
+                half_thickness,
+            )
+        })
+    }
+
+    fn oval_chunks(&self, subdivisions_per_chunk: usize) -> impl Iterator<Item = TrackChunk> {
+        let (interior_radius, radius) = (self.interior_radius(), self.radius);
         // iterate through the "sides" of the track and flat_map to a list of subdivisions
         let x = self.half_length - self.radius;
-        let separation = x * 2. / self.subdivisions_per_chunk as f32;
-        let top_chunk_range = (0..=self.subdivisions_per_chunk).map(move |index| {
+        let separation = x * 2. / subdivisions_per_chunk as f32;
+        let top_chunk_range = (0..=subdivisions_per_chunk).map(move |index| {
             TrackChunk::new(
                 Vec2::new(x - index as f32 * separation, 0.),
                 std::f32::consts::FRAC_PI_2,
+                interior_radius,
+                radius,
             )
         });
 
-        let bottom_chunk_range = (0..=self.subdivisions_per_chunk).rev().map(move |index| {
+        let bottom_chunk_range = (0..=subdivisions_per_chunk).rev().map(move |index| {
             TrackChunk::new(
                 Vec2::new(x - index as f32 * separation, 0.),
                 -std::f32::consts::FRAC_PI_2,
+                interior_radius,
+                radius,
             )
         });
 
@@ -179,14 +321,14 @@ impl Track {
         // x = r * cos(theta), y = r * sin(theta)
         // on left side, theta in range (pi/2, 3pi/2)
         // I don't know why this center works
-        let left_chunk_range = (1..self.subdivisions_per_chunk).map(move |index| {
-            let theta = FRAC_PI_2 + PI * index as f32 / self.subdivisions_per_chunk as f32;
-            TrackChunk::new(Vec2::new(-x, 0.), theta)
+        let left_chunk_range = (1..subdivisions_per_chunk).map(move |index| {
+            let theta = FRAC_PI_2 + PI * index as f32 / subdivisions_per_chunk as f32;
+            TrackChunk::new(Vec2::new(-x, 0.), theta, interior_radius, radius)
         });
         // on right side, theta in range (-pi/2, pi/2)
-        let right_chunk_range = (1..self.subdivisions_per_chunk).map(move |index| {
-            let theta = -FRAC_PI_2 + PI * index as f32 / self.subdivisions_per_chunk as f32;
-            TrackChunk::new(Vec2::new(x, 0.), theta)
+        let right_chunk_range = (1..subdivisions_per_chunk).map(move |index| {
+            let theta = -FRAC_PI_2 + PI * index as f32 / subdivisions_per_chunk as f32;
+            TrackChunk::new(Vec2::new(x, 0.), theta, interior_radius, radius)
         });
         top_chunk_range
             .chain(left_chunk_range)
@@ -200,13 +342,24 @@ impl Track {
 pub struct TrackChunk {
     chunk_origin: Vec2,
     chunk_border_angle: f32,
+    /// How far along the border the inner edge of the road is from the origin.
+    inner_distance: f32,
+    /// How far along the border the outer edge of the road is from the origin.
+    outer_distance: f32,
 }
 
 impl TrackChunk {
-    pub fn new(chunk_origin: Vec2, chunk_border_angle: f32) -> Self {
+    pub fn new(
+        chunk_origin: Vec2,
+        chunk_border_angle: f32,
+        inner_distance: f32,
+        outer_distance: f32,
+    ) -> Self {
         Self {
             chunk_origin,
             chunk_border_angle,
+            inner_distance,
+            outer_distance,
         }
     }
 
@@ -217,6 +370,30 @@ impl TrackChunk {
     pub fn angle(&self) -> f32 {
         self.chunk_border_angle
     }
+
+    /// Where the border meets the inside of the road.
+    pub fn inner_edge(&self) -> Vec2 {
+        self.point_on_border(self.inner_distance)
+    }
+
+    /// Where the border meets the outside of the road.
+    pub fn outer_edge(&self) -> Vec2 {
+        self.point_on_border(self.outer_distance)
+    }
+
+    /// The middle of the road on the border.
+    pub fn center(&self) -> Vec2 {
+        self.point_on_border((self.inner_distance + self.outer_distance) / 2.)
+    }
+
+    /// How wide the road is across the border.
+    pub fn width(&self) -> f32 {
+        self.outer_distance - self.inner_distance
+    }
+
+    fn point_on_border(&self, distance: f32) -> Vec2 {
+        self.chunk_origin + Vec2::from_angle(self.chunk_border_angle) * distance
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -268,15 +445,11 @@ impl Checkpoint {
     const Z_INDEX: f32 = 10.;
     pub const COLLISION_LAYER: LayerMask = LayerMask(1 << 5);
 
-    pub fn from_chunk(track: &Track, chunk: TrackChunk, index: usize) -> Self {
-        let size = Vec2::new(track.thickness, Self::WIDTH);
-        let track_center_offset = track.radius() / 2. + track.interior_radius() / 2.;
-
+    pub fn from_chunk(chunk: TrackChunk, index: usize) -> Self {
         Checkpoint {
             index,
-            size,
-            position: chunk.chunk_origin
-                + Vec2::from_angle(chunk.chunk_border_angle) * track_center_offset,
+            size: Vec2::new(chunk.width(), Self::WIDTH),
+            position: chunk.center(),
             chunk,
         }
     }
@@ -304,7 +477,7 @@ impl Checkpoint {
 #[derive(Component, Reflect)]
 pub struct Checkpoints(Vec<Entity>);
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 #[derive(Component, Deref, Reflect)]
 pub struct CheckpointTracker {
     checkpoints: EntityHashSet<Entity>,
@@ -326,6 +499,15 @@ impl CheckpointTracker {
         }
     }
 
+    /// How many checkpoints have been reached so far this lap.
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checkpoints.is_empty()
+    }
+
     pub fn drain(&mut self) -> impl Iterator<Item = Entity> + '_ {
         self.checkpoints.drain()
     }
@@ -335,6 +517,11 @@ impl CheckpointTracker {
     }
 }
 
+/// The index of the most recent checkpoint an entity drove through, if any.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component, Deref, Reflect)]
+pub struct LastCheckpoint(Option<usize>);
+
 #[derive(Debug)]
 #[derive(Deref, Event, Reflect)]
 pub struct LapComplete {
@@ -353,17 +540,14 @@ impl Wall {
     const Z_INDEX: f32 = 15.;
     const THICKNESS: f32 = 10.;
 
-    pub fn between_chunks(track: &Track, chunk1: TrackChunk, chunk2: TrackChunk) -> Self {
-        let vertex1 =
-            chunk1.chunk_origin + Vec2::from_angle(chunk1.chunk_border_angle) * track.radius();
-        let vertex2 =
-            chunk2.chunk_origin + Vec2::from_angle(chunk2.chunk_border_angle) * track.radius();
+    pub fn between_chunks(chunk1: TrackChunk, chunk2: TrackChunk) -> Self {
+        let vertex1 = chunk1.outer_edge();
+        let vertex2 = chunk2.outer_edge();
         let size = Vec2::new(vertex1.distance(vertex2) + 0.2, Self::THICKNESS);
         Wall {
             size,
             position: (vertex1 + vertex2) / 2.,
-            angle: (chunk1.chunk_border_angle + chunk2.chunk_border_angle + std::f32::consts::PI)
-                / 2.,
+            angle: (vertex2 - vertex1).to_angle(),
         }
     }
 
@@ -390,7 +574,7 @@ pub struct Walls(Vec<Entity>);
 
 #[cfg(test)]
 mod tests {
-    use std::time::Duration;
+    use std::{f32::consts::TAU, time::Duration};
 
     use avian2d::{
         prelude::{Physics, PhysicsTime, TimestepMode},
@@ -411,7 +595,7 @@ mod tests {
         app.insert_resource(Time::<Physics>::from_timestep(TimestepMode::FixedOnce {
             delta: Duration::from_secs_f32(1. / 60.),
         }));
-        app.add_plugins(TrackPlugin);
+        app.add_plugins(TrackPlugin::default());
         let (e1, e2, e3) = app.world_mut().run_system_once(spawn_track_and_tracker);
         (app, e1, e2, e3)
     }
@@ -444,7 +628,7 @@ mod tests {
             let reached_checkpoints = app.world_mut().get::<CheckpointTracker>(tracker).unwrap();
             assert_eq!(reached_checkpoints.len(), index);
             let mut transform = app.world_mut().get_mut::<Transform>(tracker).unwrap();
-            *transform = Checkpoint::from_chunk(&track, chunk, index).transform();
+            *transform = Checkpoint::from_chunk(chunk, index).transform();
             app.update();
             app.update();
         }
@@ -455,4 +639,82 @@ mod tests {
         let reached_checkpoints = app.world_mut().get::<CheckpointTracker>(tracker).unwrap();
         assert_eq!(reached_checkpoints.len(), 0);
     }
+
+    fn segments_cross((a1, a2): (Vec2, Vec2), (b1, b2): (Vec2, Vec2)) -> bool {
+        let side = |p: Vec2, q1: Vec2, q2: Vec2| (q2 - q1).perp_dot(p - q1);
+        side(a1, b1, b2) * side(a2, b1, b2) < 0. && side(b1, a1, a2) * side(b2, a1, a2) < 0.
+    }
+
+    #[test]
+    fn test_generated_tracks() {
+        for seed in 0..16 {
+            let generator = TrackGenerator::new(seed, seed as f32 / 15., 300.);
+            let track = generator.generate();
+            assert_eq!(track.key(), generator.generate().key());
+            assert!(track.thickness() >= 300.);
+
+            // the loop closes with a gap no bigger than the others
+            let TrackShape::Path { centerline } = track.shape() else {
+                panic!("generated tracks to be paths");
+            };
+            let gaps = centerline
+                .iter()
+                .zip(centerline.iter().cycle().skip(1))
+                .map(|(a, b)| a.distance(*b))
+                .collect::<Vec<_>>();
+            let shortest = gaps.iter().copied().fold(f32::INFINITY, f32::min);
+            let longest = gaps.iter().copied().fold(0., f32::max);
+            assert!(longest < shortest * 1.5);
+
+            // neither edge of the road crosses itself or the other
+            let chunks = track.chunks().collect::<Vec<_>>();
+            let edges = chunks
+                .iter()
+                .zip(chunks.iter().cycle().skip(1))
+                .flat_map(|(a, b)| {
+                    [
+                        (a.inner_edge(), b.inner_edge()),
+                        (a.outer_edge(), b.outer_edge()),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            for (i, a) in edges.iter().enumerate() {
+                for b in &edges[i + 1..] {
+                    assert!(!segments_cross(*a, *b), "seed {seed} crosses itself");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_track_flaws() {
+        assert_eq!(Track::default().find_flaw(), None);
+        let ellipse = (0..8)
+            .map(|index| Vec2::from_angle(TAU * index as f32 / 8.) * Vec2::new(2000., 1200.))
+            .collect::<Vec<_>>();
+        assert_eq!(Track::from_control_points(&ellipse, 600.).find_flaw(), None);
+
+        // turning back on itself within a narrow strip pinches the ends
+        let hairpin = [
+            Vec2::new(-2000., -100.),
+            Vec2::new(2000., -100.),
+            Vec2::new(2000., 100.),
+            Vec2::new(-2000., 100.),
+        ];
+        assert_eq!(
+            Track::from_control_points(&hairpin, 600.).find_flaw(),
+            Some(TrackFlaw::TightCorner)
+        );
+
+        // a figure of eight crosses itself in the middle
+        let figure_eight = [
+            Vec2::new(-2000., -1200.),
+            Vec2::new(2000., 1200.),
+            Vec2::new(2000., -1200.),
+            Vec2::new(-2000., 1200.),
+        ];
+        assert!(Track::from_control_points(&figure_eight, 300.)
+            .find_flaw()
+            .is_some());
+    }
 }
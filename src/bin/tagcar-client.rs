@@ -0,0 +1,64 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+use bevy::{asset::AssetMetaCheck, prelude::*};
+
+use laptag::LapTagAssets;
+use netcode::DEFAULT_PORT;
+use resurfacer::ResurfacerPlugin;
+use track::{Track, TrackAssets};
+
+use tagcar::{spawn_arena, GameLoopPlugin, NetJoinPlugin, ProfilePlugin, TagcarPlugins};
+
+/// Joins a match hosted by `tagcar-server`.
+///
+/// Usage: `tagcar-client [server address] [name]`, joining a server on this machine by default.
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let server = args
+        .next()
+        .map(|address| {
+            address
+                .parse()
+                .expect("A socket address like 127.0.0.1:5000")
+        })
+        .unwrap_or(SocketAddr::from((Ipv4Addr::LOCALHOST, DEFAULT_PORT)));
+    let name = args.next().unwrap_or_else(|| "Player".to_string());
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(AssetPlugin {
+        meta_check: AssetMetaCheck::Never,
+        ..default()
+    }));
+    // the server runs the match, so only keep what shows it
+    app.add_plugins(
+        TagcarPlugins::default()
+            .build()
+            .disable::<GameLoopPlugin>()
+            .disable::<ProfilePlugin>()
+            .disable::<ResurfacerPlugin>(),
+    );
+    app.add_plugins(NetJoinPlugin { server, name });
+
+    app.add_systems(
+        Update,
+        spawn_client_arena.run_if(
+            resource_exists::<TrackAssets>
+                .and_then(resource_exists::<LapTagAssets>)
+                .and_then(run_once()),
+        ),
+    );
+    app.run();
+}
+
+fn spawn_client_arena(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    spawn_arena(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &Track::default(),
+    );
+}
@@ -0,0 +1,18 @@
+use bevy::{asset::AssetMetaCheck, prelude::*};
+
+use tagcar::{GameLoopPlugin, TagcarPlugins, TrackEditorPlugin};
+
+/// Opens the track editor on the last saved track.
+///
+/// Usage: `tagcar-editor`, with the controls listed on screen.
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(AssetPlugin {
+        meta_check: AssetMetaCheck::Never,
+        ..default()
+    }));
+    // the editor puts cars on the track and takes them off again itself
+    app.add_plugins(TagcarPlugins::default().build().disable::<GameLoopPlugin>());
+    app.add_plugins(TrackEditorPlugin);
+    app.run();
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use bevy::{asset::AssetMetaCheck, ecs::schedule::ScheduleLabel, prelude::*};
+use bevy_ggrs::GgrsSchedule;
+
+use entropy::EntropyPlugin;
+
+use tagcar::{GameLoopPlugin, NetPeerPlugin, ProfilePlugin, TagcarPlugins};
+
+/// Plays a two player match against another `tagcar-p2p` with rollback netcode.
+///
+/// Usage: `tagcar-p2p <local port> <player 0 or 1> <peer address> [latency ms] [seed]`. Both
+/// peers have to use the same seed.
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let [local_port, local_player, remote, rest @ ..] = args.as_slice() else {
+        eprintln!(
+            "Usage: tagcar-p2p <local port> <player 0 or 1> <peer address> [latency ms] [seed]"
+        );
+        std::process::exit(1);
+    };
+    let local_port = local_port.parse().expect("A port like 7000");
+    let local_player = local_player.parse().expect("Player 0 or 1");
+    let remote = remote
+        .parse()
+        .expect("A socket address like 127.0.0.1:7001");
+    let latency = rest.first().map_or(0, |latency| {
+        latency.parse().expect("A latency in milliseconds")
+    });
+    let seed = rest.get(1).map_or(0, |seed| {
+        seed.parse().expect("A number to seed the match with")
+    });
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(AssetPlugin {
+        meta_check: AssetMetaCheck::Never,
+        ..default()
+    }));
+    // every peer simulates the whole match, so there are no restarts or saved results
+    app.add_plugins(
+        TagcarPlugins {
+            simulation_schedule: Some(GgrsSchedule.intern()),
+        }
+        .build()
+        .set(EntropyPlugin { seed: Some(seed) })
+        .set(GameLoopPlugin {
+            schedule: GgrsSchedule.intern(),
+            allow_restarts: false,
+        })
+        .disable::<ProfilePlugin>(),
+    );
+    app.add_plugins(NetPeerPlugin {
+        local_port,
+        local_player,
+        remote,
+        latency: Duration::from_millis(latency),
+    });
+    app.run();
+}
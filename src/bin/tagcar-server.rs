@@ -0,0 +1,86 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use bevy::{
+    app::ScheduleRunnerPlugin,
+    asset::AssetMetaCheck,
+    prelude::*,
+    render::{settings::WgpuSettings, RenderPlugin},
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+
+use laptag::LapTagAssets;
+use netcode::DEFAULT_PORT;
+use track::{Track, TrackAssets};
+
+use tagcar::{spawn_arena, ControlsSettingsPlugin, NetHostPlugin, ProfilePlugin, TagcarPlugins};
+
+/// Runs the simulation without a window or a renderer and hosts matches for clients.
+///
+/// Usage: `tagcar-server [address]`, listening on every interface by default.
+fn main() {
+    let address = std::env::args()
+        .nth(1)
+        .map(|address| address.parse().expect("A socket address like 0.0.0.0:5000"))
+        .unwrap_or(SocketAddr::from((Ipv4Addr::UNSPECIFIED, DEFAULT_PORT)));
+
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins
+            .set(AssetPlugin {
+                meta_check: AssetMetaCheck::Never,
+                ..default()
+            })
+            .set(RenderPlugin {
+                render_creation: WgpuSettings {
+                    backends: None,
+                    ..default()
+                }
+                .into(),
+                ..default()
+            })
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                ..default()
+            })
+            .disable::<WinitPlugin>(),
+    )
+    .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+        1. / 60.,
+    )));
+    // nobody is playing on the server, so there is nothing to save or rebind
+    app.add_plugins(
+        TagcarPlugins::default()
+            .build()
+            .disable::<ProfilePlugin>()
+            .disable::<ControlsSettingsPlugin>(),
+    );
+    app.add_plugins(NetHostPlugin { address });
+
+    app.add_systems(
+        Update,
+        spawn_server_arena.run_if(
+            resource_exists::<TrackAssets>
+                .and_then(resource_exists::<LapTagAssets>)
+                .and_then(run_once()),
+        ),
+    );
+    app.run();
+}
+
+fn spawn_server_arena(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    spawn_arena(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &Track::default(),
+    );
+}
@@ -0,0 +1,138 @@
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+
+use car::Boost;
+use track::{LastCheckpoint, Track};
+
+use crate::Player;
+
+/// Recharges boost at checkpoints and shows the player's boost meter.
+pub struct BoostPlugin {
+    /// The schedule boost is recharged in. The meter is always drawn in `Update`.
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for BoostPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
+
+impl Plugin for BoostPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(self.schedule, Self::recharge_at_checkpoints)
+            .add_systems(
+                Update,
+                (Self::spawn_boost_gauge, Self::update_boost_gauge).chain(),
+            );
+    }
+}
+
+impl BoostPlugin {
+    /// Charge regained for every checkpoint a car drives through.
+    const CHECKPOINT_CHARGE: f32 = 0.1;
+
+    fn recharge_at_checkpoints(
+        mut cars: Query<(&mut Boost, Ref<LastCheckpoint>)>,
+        tracks: Query<&Track>,
+    ) {
+        let Ok(track) = tracks.get_single() else {
+            return;
+        };
+        let checkpoints = track.chunks().count();
+        for (mut boost, last_checkpoint) in &mut cars {
+            if !last_checkpoint.is_changed() || last_checkpoint.is_added() {
+                continue;
+            }
+            if let Some(index) = **last_checkpoint {
+                boost.add_at_checkpoint(index, checkpoints, Self::CHECKPOINT_CHARGE);
+            }
+        }
+    }
+
+    fn spawn_boost_gauge(
+        mut commands: Commands,
+        new_players: Query<(), Added<Player>>,
+        gauges: Query<(), With<BoostGauge>>,
+    ) {
+        if new_players.is_empty() || !gauges.is_empty() {
+            return;
+        }
+        commands
+            .spawn((
+                Name::new("Boost Gauge"),
+                BoostGauge,
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(10.),
+                        bottom: Val::Px(10.),
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(4.),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ))
+            .with_children(|builder| {
+                builder.spawn(TextBundle::from_section(
+                    "BOOST",
+                    TextStyle {
+                        font_size: 24.,
+                        color: Color::WHITE,
+                        ..Default::default()
+                    },
+                ));
+                builder
+                    .spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(200.),
+                            height: Val::Px(16.),
+                            border: UiRect::all(Val::Px(2.)),
+                            ..Default::default()
+                        },
+                        border_color: Color::BLACK.into(),
+                        background_color: Color::srgba(0.3, 0.3, 0.3, 0.6).into(),
+                        ..Default::default()
+                    })
+                    .with_children(|builder| {
+                        builder.spawn((
+                            BoostGaugeFill,
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Percent(0.),
+                                    height: Val::Percent(100.),
+                                    ..Default::default()
+                                },
+                                background_color: Color::srgb(0.2, 0.8, 1.).into(),
+                                ..Default::default()
+                            },
+                        ));
+                    });
+            });
+    }
+
+    fn update_boost_gauge(
+        players: Query<&Boost, With<Player>>,
+        mut fills: Query<&mut Style, With<BoostGaugeFill>>,
+    ) {
+        let Ok(boost) = players.get_single() else {
+            return;
+        };
+        for mut style in &mut fills {
+            style.width = Val::Percent(boost.charge() * 100.);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct BoostGauge;
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct BoostGaugeFill;
@@ -0,0 +1,312 @@
+use bevy::{input::gamepad::GamepadAxis, prelude::*};
+
+use controller::{Binding, CarControl, ControlBindings, Controller};
+
+use crate::storage;
+
+/// A settings screen for rebinding each player's controls, which are saved between runs.
+pub struct ControlsSettingsPlugin;
+
+impl Plugin for ControlsSettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(
+            storage::load::<ControlBindings>(ControlsSettingsPlugin::SAVE_NAME).unwrap_or_default(),
+        )
+        .init_resource::<ControlsScreen>()
+        .add_systems(
+            Update,
+            (
+                Self::toggle_screen,
+                Self::click_buttons,
+                Self::capture_binding,
+                Self::save_bindings,
+                Self::draw_screen,
+            )
+                .chain()
+                .before(controller::CarControlSystems),
+        );
+    }
+}
+
+impl ControlsSettingsPlugin {
+    const SAVE_NAME: &'static str = "controls";
+    const TOGGLE_KEY: KeyCode = KeyCode::F1;
+    /// How far a stick or trigger has to be pushed to be picked up as a binding.
+    const AXIS_THRESHOLD: f32 = 0.6;
+    const AXES: [GamepadAxisType; 6] = [
+        GamepadAxisType::LeftStickX,
+        GamepadAxisType::LeftStickY,
+        GamepadAxisType::RightStickX,
+        GamepadAxisType::RightStickY,
+        GamepadAxisType::LeftZ,
+        GamepadAxisType::RightZ,
+    ];
+
+    fn toggle_screen(inputs: Res<ButtonInput<KeyCode>>, mut screen: ResMut<ControlsScreen>) {
+        if inputs.just_pressed(Self::TOGGLE_KEY) {
+            screen.is_open = !screen.is_open;
+            screen.pending = None;
+            screen.message = None;
+        }
+    }
+
+    fn click_buttons(
+        buttons: Query<(&Interaction, &ControlsButton), Changed<Interaction>>,
+        mut screen: ResMut<ControlsScreen>,
+        mut bindings: ResMut<ControlBindings>,
+    ) {
+        for (interaction, button) in &buttons {
+            if !matches!(interaction, Interaction::Pressed) {
+                continue;
+            }
+            match *button {
+                ControlsButton::Rebind(controller, action) => {
+                    screen.pending = Some((controller, action));
+                    screen.message = None;
+                }
+                ControlsButton::ResetAll => {
+                    bindings.reset();
+                    screen.pending = None;
+                    screen.message = Some("Controls reset to the defaults".to_string());
+                }
+            }
+        }
+    }
+
+    fn capture_binding(
+        keys: Res<ButtonInput<KeyCode>>,
+        gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+        gamepads: Res<Gamepads>,
+        axes: Res<Axis<GamepadAxis>>,
+        mut screen: ResMut<ControlsScreen>,
+        mut bindings: ResMut<ControlBindings>,
+    ) {
+        let Some((controller, action)) = screen.pending else {
+            return;
+        };
+        // escape and the settings key always stay free to back out
+        if keys.just_pressed(KeyCode::Escape) {
+            screen.pending = None;
+            return;
+        }
+        let key = keys
+            .get_just_pressed()
+            .find(|key| **key != Self::TOGGLE_KEY)
+            .map(|key| (Binding::Key(*key), None));
+        let button = || {
+            gamepad_buttons.get_just_pressed().next().map(|button| {
+                (
+                    Binding::GamepadButton(button.button_type),
+                    Some(button.gamepad),
+                )
+            })
+        };
+        let axis = || {
+            gamepads.iter().find_map(|gamepad| {
+                Self::AXES.into_iter().find_map(|axis_type| {
+                    let value = axes.get(GamepadAxis::new(gamepad, axis_type))?;
+                    (value.abs() >= Self::AXIS_THRESHOLD).then_some((
+                        Binding::GamepadAxis {
+                            axis: axis_type,
+                            positive: value > 0.,
+                        },
+                        Some(gamepad),
+                    ))
+                })
+            })
+        };
+        let Some((binding, gamepad)) = key.or_else(button).or_else(axis) else {
+            return;
+        };
+        screen.pending = None;
+        // a pad input also picks which pad this player uses from now on
+        let previous_gamepad = bindings.gamepad(controller);
+        let result = gamepad
+            .map_or(Ok(()), |gamepad| bindings.set_gamepad(controller, gamepad))
+            .and_then(|()| bindings.rebind(controller, action, vec![binding]));
+        screen.message = match result {
+            Ok(()) => None,
+            Err(conflict) => {
+                let _ = bindings.set_gamepad(controller, previous_gamepad);
+                Some(format!(
+                    "{} is already {:?} for {:?}",
+                    conflict.binding, conflict.second.1, conflict.second.0
+                ))
+            }
+        };
+    }
+
+    fn save_bindings(bindings: Res<ControlBindings>) {
+        if bindings.is_changed() && !bindings.is_added() {
+            storage::save(Self::SAVE_NAME, &*bindings);
+        }
+    }
+
+    fn draw_screen(
+        mut commands: Commands,
+        screen: Res<ControlsScreen>,
+        bindings: Res<ControlBindings>,
+        roots: Query<Entity, With<ControlsScreenRoot>>,
+    ) {
+        if !screen.is_changed() && !bindings.is_changed() {
+            return;
+        }
+        for root in &roots {
+            commands.entity(root).despawn_recursive();
+        }
+        if !screen.is_open {
+            return;
+        }
+
+        let text = |text: String, font_size: f32, color: Color| {
+            TextBundle::from_section(
+                text,
+                TextStyle {
+                    font_size,
+                    color,
+                    ..Default::default()
+                },
+            )
+        };
+        let button = |marker: ControlsButton| {
+            (
+                marker,
+                ButtonBundle {
+                    style: Style {
+                        min_width: Val::Px(200.),
+                        padding: UiRect::axes(Val::Px(8.), Val::Px(2.)),
+                        border: UiRect::all(Val::Px(2.)),
+                        ..Default::default()
+                    },
+                    border_color: Color::BLACK.into(),
+                    background_color: Color::srgb(0.3, 0.3, 0.4).into(),
+                    ..Default::default()
+                },
+            )
+        };
+        let conflicts = bindings.conflicts();
+
+        commands
+            .spawn((
+                Name::new("Controls Settings"),
+                ControlsScreenRoot,
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    z_index: ZIndex::Global(10),
+                    ..Default::default()
+                },
+            ))
+            .with_children(|builder| {
+                builder
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            row_gap: Val::Px(4.),
+                            padding: UiRect::all(Val::Px(20.)),
+                            border: UiRect::all(Val::Px(4.)),
+                            ..Default::default()
+                        },
+                        border_color: Color::BLACK.into(),
+                        background_color: Color::srgba(0.2, 0.2, 0.2, 0.95).into(),
+                        ..Default::default()
+                    })
+                    .with_children(|builder| {
+                        builder.spawn(text("Controls".to_string(), 48., Color::WHITE));
+                        for controller in Controller::ALL {
+                            let gamepad = bindings.gamepad(controller).id + 1;
+                            builder.spawn(text(
+                                format!("{controller:?} (gamepad {gamepad})"),
+                                28.,
+                                Color::WHITE,
+                            ));
+                            for action in CarControl::ALL {
+                                let is_pending = screen.pending == Some((controller, action));
+                                let is_conflicted = conflicts.iter().any(|conflict| {
+                                    conflict.first == (controller, action)
+                                        || conflict.second == (controller, action)
+                                });
+                                let label = if is_pending {
+                                    "Press an input...".to_string()
+                                } else {
+                                    bindings
+                                        .bindings(controller, action)
+                                        .iter()
+                                        .map(Binding::to_string)
+                                        .collect::<Vec<_>>()
+                                        .join(", ")
+                                };
+                                let color = if is_conflicted {
+                                    Color::srgb(1., 0.4, 0.3)
+                                } else {
+                                    Color::WHITE
+                                };
+                                builder
+                                    .spawn(NodeBundle {
+                                        style: Style {
+                                            justify_content: JustifyContent::SpaceBetween,
+                                            column_gap: Val::Px(20.),
+                                            ..Default::default()
+                                        },
+                                        ..Default::default()
+                                    })
+                                    .with_children(|builder| {
+                                        builder.spawn(text(format!("{action:?}"), 20., color));
+                                        builder
+                                            .spawn(button(ControlsButton::Rebind(
+                                                controller, action,
+                                            )))
+                                            .with_children(|builder| {
+                                                builder.spawn(text(label, 20., color));
+                                            });
+                                    });
+                            }
+                        }
+                        if let Some(message) = &screen.message {
+                            builder.spawn(text(message.clone(), 18., Color::srgb(1., 0.8, 0.3)));
+                        }
+                        builder
+                            .spawn(button(ControlsButton::ResetAll))
+                            .with_children(|builder| {
+                                builder.spawn(text(
+                                    "Reset to defaults".to_string(),
+                                    20.,
+                                    Color::WHITE,
+                                ));
+                            });
+                        builder.spawn(text(
+                            "Click a control, then press a key, button or stick. F1 closes."
+                                .to_string(),
+                            16.,
+                            Color::WHITE,
+                        ));
+                    });
+            });
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+#[derive(Resource)]
+struct ControlsScreen {
+    is_open: bool,
+    /// The control waiting for a new input.
+    pending: Option<(Controller, CarControl)>,
+    message: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct ControlsScreenRoot;
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Component)]
+enum ControlsButton {
+    Rebind(Controller, CarControl),
+    ResetAll,
+}
@@ -0,0 +1,465 @@
+use bevy::{color::palettes, prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
+
+use camera::{CameraTracker, GameCamera};
+use car::Car;
+use controller::CarControlSystems;
+use entropy::GlobalEntropy;
+use laptag::LapTagAssets;
+use resurfacer::Peg;
+use track::{Track, TrackAssets};
+
+use crate::{
+    spawn_background, spawn_cars, spawn_track, storage, CarNameAssets, MatchRules, TrackPieces,
+};
+
+/// Shapes a track with the mouse, drives it straight away and saves it for next time.
+///
+/// Drag a handle to move the control point the road is smoothed through, click anywhere else to
+/// add one and right click a handle to remove it. `[` and `]` change the width, `O` places or
+/// clears a peg under the cursor, `G` starts the grid at the nearest control point, the arrow
+/// keys pan, `Tab` switches between editing and test driving, and `Ctrl+S` saves.
+pub struct TrackEditorPlugin;
+
+impl Plugin for TrackEditorPlugin {
+    fn build(&self, app: &mut App) {
+        let is_loaded = resource_exists::<TrackAssets>
+            .and_then(resource_exists::<LapTagAssets>)
+            .and_then(resource_exists::<CarNameAssets>);
+        app.insert_resource(
+            storage::load::<TrackDesign>(TrackDesign::SAVE_NAME).unwrap_or_default(),
+        )
+        .init_resource::<TrackEditor>()
+        .add_systems(Startup, Self::spawn_editor)
+        .add_systems(
+            Update,
+            (
+                Self::zoom_out_new_cameras,
+                Self::toggle_test_drive,
+                (
+                    Self::pan_view,
+                    Self::drag_control_points,
+                    Self::edit_with_keys,
+                )
+                    .run_if(|editor: Res<TrackEditor>| !editor.is_test_driving),
+                Self::rebuild_track,
+                Self::update_editor_ui,
+            )
+                .chain()
+                .before(CarControlSystems)
+                .run_if(is_loaded),
+        );
+    }
+}
+
+impl TrackEditorPlugin {
+    const HANDLE_RADIUS: f32 = 40.;
+    const HANDLE_Z_INDEX: f32 = 30.;
+    const MIN_CONTROL_POINTS: usize = 4;
+    const MAX_WIDTH: f32 = 1200.;
+    const WIDTH_STEP: f32 = 25.;
+    const PAN_SPEED: f32 = 1500.;
+    /// How far out the camera is while editing, so the whole track fits.
+    const EDITING_ZOOM: f32 = 3.;
+
+    fn spawn_editor(
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+        design: Res<TrackDesign>,
+    ) {
+        spawn_background(&mut commands, &mut meshes, &mut materials, &design.track());
+        commands.spawn((
+            Name::new("Editor View"),
+            EditorView,
+            CameraTracker::rect(Vec2::splat(-f32::MAX), Vec2::splat(f32::MAX)),
+            SpatialBundle::default(),
+        ));
+        commands
+            .spawn((
+                Name::new("Editor UI"),
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(12.),
+                        top: Val::Px(12.),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ))
+            .with_children(|builder| {
+                builder.spawn((
+                    EditorText,
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 20.,
+                            color: Color::WHITE,
+                            ..Default::default()
+                        },
+                    ),
+                ));
+            });
+    }
+
+    fn zoom_out_new_cameras(
+        mut projections: Query<&mut OrthographicProjection, Added<GameCamera>>,
+    ) {
+        for mut projection in &mut projections {
+            projection.scale = Self::EDITING_ZOOM;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn toggle_test_drive(
+        mut commands: Commands,
+        inputs: Res<ButtonInput<KeyCode>>,
+        mut editor: ResMut<TrackEditor>,
+        design: Res<TrackDesign>,
+        views: Query<Entity, With<EditorView>>,
+        cars: Query<Entity, With<Car>>,
+        mut projections: Query<&mut OrthographicProjection, With<GameCamera>>,
+        mut entropy: ResMut<GlobalEntropy>,
+        rules: Res<MatchRules>,
+    ) {
+        if !inputs.just_pressed(KeyCode::Tab) {
+            return;
+        }
+        let track = design.track();
+        if !editor.is_test_driving {
+            if track.chunks().count() < rules.grid.chunks_needed() {
+                editor.status = "Too short to fit the grid".to_string();
+                return;
+            }
+            if let Some(flaw) = track.find_flaw() {
+                editor.status = format!("Can't drive it, {flaw}");
+                return;
+            }
+        }
+        editor.is_test_driving = !editor.is_test_driving;
+        editor.dragging = None;
+        editor.status.clear();
+        // the player's car brings its own camera tracker
+        for view in &views {
+            if editor.is_test_driving {
+                commands.entity(view).remove::<CameraTracker>();
+            } else {
+                commands.entity(view).insert(CameraTracker::rect(
+                    Vec2::splat(-f32::MAX),
+                    Vec2::splat(f32::MAX),
+                ));
+            }
+        }
+        for mut projection in &mut projections {
+            projection.scale = if editor.is_test_driving {
+                1.
+            } else {
+                Self::EDITING_ZOOM
+            };
+        }
+        if editor.is_test_driving {
+            spawn_cars(&mut commands, &track, entropy.as_mut(), &rules);
+        } else {
+            for car in &cars {
+                commands.entity(car).despawn_recursive();
+            }
+        }
+    }
+
+    fn pan_view(
+        inputs: Res<ButtonInput<KeyCode>>,
+        mut views: Query<&mut Transform, With<EditorView>>,
+        time: Res<Time>,
+    ) {
+        let direction = [
+            (KeyCode::ArrowLeft, Vec2::NEG_X),
+            (KeyCode::ArrowRight, Vec2::X),
+            (KeyCode::ArrowDown, Vec2::NEG_Y),
+            (KeyCode::ArrowUp, Vec2::Y),
+        ]
+        .into_iter()
+        .filter(|(key, _)| inputs.pressed(*key))
+        .map(|(_, direction)| direction)
+        .sum::<Vec2>();
+        for mut transform in &mut views {
+            transform.translation +=
+                (direction * Self::PAN_SPEED * Self::EDITING_ZOOM * time.delta_seconds())
+                    .extend(0.);
+        }
+    }
+
+    fn drag_control_points(
+        buttons: Res<ButtonInput<MouseButton>>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+        cameras: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+        mut editor: ResMut<TrackEditor>,
+        mut design: ResMut<TrackDesign>,
+    ) {
+        if buttons.just_released(MouseButton::Left) {
+            editor.dragging = None;
+        }
+        let Some(cursor) = cursor_position(&windows, &cameras) else {
+            return;
+        };
+        let hovered = design.nearest_control_point(cursor, Self::HANDLE_RADIUS * 1.5);
+
+        if buttons.just_pressed(MouseButton::Right) {
+            if let Some(index) = hovered {
+                if design.control_points.len() > Self::MIN_CONTROL_POINTS {
+                    design.remove_control_point(index);
+                    editor.dragging = None;
+                }
+            }
+            return;
+        }
+        if buttons.just_pressed(MouseButton::Left) {
+            let index = hovered.unwrap_or_else(|| design.insert_control_point(cursor));
+            editor.dragging = Some(index);
+        }
+        if let Some(index) = editor.dragging {
+            if design.control_points[index] != cursor.to_array() {
+                design.control_points[index] = cursor.to_array();
+            }
+        }
+    }
+
+    fn edit_with_keys(
+        inputs: Res<ButtonInput<KeyCode>>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+        cameras: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+        mut editor: ResMut<TrackEditor>,
+        mut design: ResMut<TrackDesign>,
+        rules: Res<MatchRules>,
+    ) {
+        if inputs.just_pressed(KeyCode::BracketLeft) {
+            design.width = (design.width - Self::WIDTH_STEP).max(rules.grid.min_width());
+        }
+        if inputs.just_pressed(KeyCode::BracketRight) {
+            design.width = (design.width + Self::WIDTH_STEP).min(Self::MAX_WIDTH);
+        }
+        let is_ctrl_pressed = inputs.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+        if is_ctrl_pressed && inputs.just_pressed(KeyCode::KeyS) {
+            editor.status = match design.track().find_flaw() {
+                Some(flaw) => format!("Can't save it, {flaw}"),
+                None => {
+                    storage::save(TrackDesign::SAVE_NAME, &*design);
+                    "Saved".to_string()
+                }
+            };
+        }
+        let Some(cursor) = cursor_position(&windows, &cameras) else {
+            return;
+        };
+        if inputs.just_pressed(KeyCode::KeyO) {
+            design.toggle_obstacle(cursor);
+        }
+        if inputs.just_pressed(KeyCode::KeyG) {
+            if let Some(index) = design.nearest_control_point(cursor, f32::INFINITY) {
+                design.grid_start = index;
+            }
+        }
+    }
+
+    // everything is rebuilt from scratch, so checkpoints and walls come back through their
+    // blueprints just like on a fresh track
+    fn rebuild_track(
+        mut commands: Commands,
+        design: Res<TrackDesign>,
+        editor: Res<TrackEditor>,
+        track_pieces: Query<Entity, TrackPieces>,
+        handles: Query<Entity, With<EditorHandle>>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        if !design.is_changed() || editor.is_test_driving {
+            return;
+        }
+        for entity in track_pieces.iter().chain(&handles) {
+            commands.entity(entity).despawn_recursive();
+        }
+        spawn_track(&mut commands, &design.track());
+        for obstacle in &design.obstacles {
+            commands.spawn(Peg.bundle(Vec2::from_array(*obstacle)));
+        }
+
+        let mesh = meshes.add(Circle::new(Self::HANDLE_RADIUS));
+        let normal = materials.add(Color::Srgba(palettes::css::WHITE_SMOKE));
+        let grid_start = materials.add(Color::Srgba(palettes::css::SKY_BLUE));
+        for (index, point) in design.control_points.iter().enumerate() {
+            commands.spawn((
+                Name::new(format!("Control Point {index}")),
+                EditorHandle,
+                ColorMesh2dBundle {
+                    mesh: mesh.clone().into(),
+                    material: if index == design.grid_start {
+                        grid_start.clone()
+                    } else {
+                        normal.clone()
+                    },
+                    transform: Transform::from_translation(
+                        Vec2::from_array(*point).extend(Self::HANDLE_Z_INDEX),
+                    ),
+                    ..Default::default()
+                },
+            ));
+        }
+    }
+
+    fn update_editor_ui(
+        editor: Res<TrackEditor>,
+        design: Res<TrackDesign>,
+        mut texts: Query<&mut Text, With<EditorText>>,
+    ) {
+        if !editor.is_changed() && !design.is_changed() {
+            return;
+        }
+        let text = if editor.is_test_driving {
+            "TEST DRIVE\nTab to get back to editing".to_string()
+        } else {
+            format!(
+                "TRACK EDITOR - width {} - {} control points - {} pegs\n\
+                Drag to move, click to add, right click to remove\n\
+                [ ] width, O peg, G grid start, arrows pan, Tab drive, Ctrl+S save\n{}",
+                design.width,
+                design.control_points.len(),
+                design.obstacles.len(),
+                editor.status,
+            )
+        };
+        for mut editor_text in &mut texts {
+            editor_text.sections[0].value.clone_from(&text);
+        }
+    }
+}
+
+fn cursor_position(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    cameras: &Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+) -> Option<Vec2> {
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    let (camera, transform) = cameras.get_single().ok()?;
+    camera.viewport_to_world_2d(transform, cursor)
+}
+
+/// A track drawn in the editor, as it is saved.
+#[derive(Clone, Debug)]
+#[derive(Resource, Serialize, Deserialize)]
+pub struct TrackDesign {
+    /// The points the middle of the road is smoothed through, in order around the loop.
+    pub control_points: Vec<[f32; 2]>,
+    pub width: f32,
+    /// Where pegs sit for good, out of the resurfacer's reach.
+    pub obstacles: Vec<[f32; 2]>,
+    /// The control point the starting grid lines up from.
+    pub grid_start: usize,
+}
+
+impl Default for TrackDesign {
+    fn default() -> Self {
+        let count = 8;
+        let control_points = (0..count)
+            .map(|index| {
+                let direction =
+                    Vec2::from_angle(std::f32::consts::TAU * index as f32 / count as f32);
+                (direction * Vec2::new(2000., 1200.)).to_array()
+            })
+            .collect();
+        Self {
+            control_points,
+            width: 600.,
+            obstacles: vec![],
+            grid_start: 0,
+        }
+    }
+}
+
+impl TrackDesign {
+    pub const SAVE_NAME: &'static str = "custom-track";
+    /// How close the cursor has to be to a peg to clear it rather than place another.
+    const OBSTACLE_PICK_DISTANCE: f32 = 40.;
+
+    pub fn track(&self) -> Track {
+        let count = self.control_points.len();
+        let control_points = (0..count)
+            .map(|index| Vec2::from_array(self.control_points[(index + self.grid_start) % count]))
+            .collect::<Vec<_>>();
+        Track::from_control_points(&control_points, self.width)
+    }
+
+    fn nearest_control_point(&self, position: Vec2, max_distance: f32) -> Option<usize> {
+        self.control_points
+            .iter()
+            .map(|point| Vec2::from_array(*point).distance(position))
+            .enumerate()
+            .filter(|(_, distance)| *distance <= max_distance)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    /// Adds a control point on the closest side of the loop, returning where it went.
+    fn insert_control_point(&mut self, position: Vec2) -> usize {
+        let count = self.control_points.len();
+        let after = (0..count)
+            .map(|index| {
+                let start = Vec2::from_array(self.control_points[index]);
+                let end = Vec2::from_array(self.control_points[(index + 1) % count]);
+                (index, distance_to_segment(position, start, end))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(0, |(index, _)| index);
+        let index = after + 1;
+        self.control_points.insert(index, position.to_array());
+        if index <= self.grid_start {
+            self.grid_start += 1;
+        }
+        index
+    }
+
+    fn remove_control_point(&mut self, index: usize) {
+        self.control_points.remove(index);
+        if index < self.grid_start {
+            self.grid_start -= 1;
+        }
+        self.grid_start %= self.control_points.len();
+    }
+
+    fn toggle_obstacle(&mut self, position: Vec2) {
+        let count = self.obstacles.len();
+        self.obstacles.retain(|obstacle| {
+            Vec2::from_array(*obstacle).distance(position) > Self::OBSTACLE_PICK_DISTANCE
+        });
+        if self.obstacles.len() == count {
+            self.obstacles.push(position.to_array());
+        }
+    }
+}
+
+fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let segment = end - start;
+    let t =
+        ((point - start).dot(segment) / segment.length_squared().max(f32::EPSILON)).clamp(0., 1.);
+    point.distance(start + segment * t)
+}
+
+#[derive(Debug, Default)]
+#[derive(Resource)]
+struct TrackEditor {
+    is_test_driving: bool,
+    /// The control point following the mouse.
+    dragging: Option<usize>,
+    status: String,
+}
+
+/// Where the camera looks while editing.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct EditorView;
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct EditorHandle;
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct EditorText;
@@ -1,30 +1,62 @@
-use bevy::{color::palettes, prelude::*};
+use bevy::{
+    color::palettes,
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+    utils::HashMap,
+};
 use bot_controller::BotControllerSystems;
 use controller::CarControlSystems;
 use sickle_ui::prelude::*;
 
 use car::Car;
 use entropy::GlobalEntropy;
-use laptag::{LapTagSystems, Score};
+use laptag::{LapTagSystems, Score, Team};
 use track::Track;
 
-use crate::spawn_cars;
+use crate::{match_track, spawn_cars, spawn_track, MatchRules, TrackPieces};
 
-pub struct GameLoopPlugin;
+pub struct GameLoopPlugin {
+    /// The schedule tagging runs in, where the match is decided.
+    pub schedule: InternedScheduleLabel,
+    /// Whether a finished match can be restarted from the game over screen.
+    pub allow_restarts: bool,
+}
+
+impl Default for GameLoopPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+            allow_restarts: true,
+        }
+    }
+}
 
 impl Plugin for GameLoopPlugin {
     fn build(&self, app: &mut App) {
         app.configure_sets(
-            Update,
+            self.schedule,
             LapTagSystems.run_if(not(resource_exists::<GameOver>)),
         );
+        app.add_event::<GameOutcome>();
+        app.add_systems(
+            self.schedule,
+            Self::handle_gameover
+                .after(LapTagSystems)
+                .run_if(not(resource_exists::<GameOver>)),
+        );
         app.add_systems(
             Update,
-            (Self::restart_game, Self::handle_gameover)
+            (
+                Self::restart_game.run_if(resource_exists::<AllowRestarts>),
+                Self::show_gameover,
+            )
                 .chain()
                 .before(CarControlSystems)
                 .before(BotControllerSystems),
         );
+        if self.allow_restarts {
+            app.insert_resource(AllowRestarts);
+        }
     }
 }
 
@@ -34,8 +66,10 @@ impl GameLoopPlugin {
         restart_button: Query<&Interaction, With<RestartButton>>,
         cars: Query<Entity, With<Car>>,
         track: Query<&Track>,
+        track_pieces: Query<Entity, TrackPieces>,
         gameover_ui: Query<Entity, With<GameoverUI>>,
         mut entropy: ResMut<GlobalEntropy>,
+        rules: Res<MatchRules>,
     ) {
         let Ok(interaction) = restart_button.get_single() else {
             return;
@@ -49,48 +83,103 @@ impl GameLoopPlugin {
         for entity in &gameover_ui {
             commands.entity(entity).despawn_recursive();
         }
-        spawn_cars(&mut commands, track.single(), entropy.as_mut());
+        commands.remove_resource::<GameOver>();
+        if rules.track_difficulty.is_none() {
+            spawn_cars(&mut commands, track.single(), entropy.as_mut(), &rules);
+            return;
+        }
+        for entity in &track_pieces {
+            commands.entity(entity).despawn_recursive();
+        }
+        let track = match_track(&rules, entropy.as_mut());
+        spawn_track(&mut commands, &track);
+        spawn_cars(&mut commands, &track, entropy.as_mut(), &rules);
     }
 
     fn handle_gameover(
         mut commands: Commands,
-        mut destroyed_players: RemovedComponents<Player>,
-        scores: Query<(&Score, Option<&Player>)>,
+        players: Query<(), With<Player>>,
+        scores: Query<(Entity, &Score, Has<Player>, Option<&Team>)>,
+        mut had_player: Local<bool>,
     ) {
+        // a restart swaps the player's car in the same frame, so only a player that is gone
+        // without a replacement has lost
+        let has_player = !players.is_empty();
+        let player_destroyed = *had_player && !has_player;
+        *had_player = has_player;
+
         // whether the player won or not
-        let game_result = if destroyed_players.read().count() > 0 {
+        let game_result = if player_destroyed {
             Some(false)
         } else {
-            scores
-                .iter()
-                .map(|(score, player)| (**score, player.is_some()))
+            // teammates pool their points, everyone else scores on their own
+            let mut totals = HashMap::<Scorer, (u32, bool)>::default();
+            for (entity, score, is_player, team) in &scores {
+                let scorer = team.map_or(Scorer::Car(entity), |team| Scorer::Team(*team));
+                let (total, has_player) = totals.entry(scorer).or_default();
+                *total += **score;
+                *has_player |= is_player;
+            }
+            totals
+                .into_values()
                 .max_by(|(score1, _), (score2, _)| score1.cmp(score2))
                 .filter(|(score, _)| *score >= 5)
-                .map(|(_, is_player)| is_player)
+                .map(|(_, has_player)| has_player)
         };
         if let Some(is_game_won) = game_result {
-            commands
-                .ui_builder(UiRoot)
-                .column(|column| {
-                    column
-                        .column(|column| {
-                            column
-                                .label(LabelConfig {
-                                    label: "Game Over!".to_string(),
-                                    ..Default::default()
-                                })
-                                .style()
-                                .font_size(48.);
-                            column
-                                .label(LabelConfig {
-                                    label: format!(
-                                        "YOU {}",
-                                        if is_game_won { "WIN" } else { "LOSE" }
-                                    ),
-                                    ..Default::default()
-                                })
-                                .style()
-                                .font_size(96.);
+            let player_score = scores
+                .iter()
+                .find(|(_, _, is_player, _)| *is_player)
+                .map_or(0, |(_, score, ..)| **score);
+            commands.insert_resource(GameOver {
+                won: is_game_won,
+                score: player_score,
+            });
+        }
+    }
+
+    /// Shows how the match ended, and takes it down again if a rollback undoes the ending.
+    fn show_gameover(
+        mut commands: Commands,
+        game_over: Option<Res<GameOver>>,
+        gameover_ui: Query<Entity, With<GameoverUI>>,
+        allow_restarts: Option<Res<AllowRestarts>>,
+        mut outcomes: EventWriter<GameOutcome>,
+    ) {
+        let Some(game_over) = game_over else {
+            for entity in &gameover_ui {
+                commands.entity(entity).despawn_recursive();
+            }
+            return;
+        };
+        if !gameover_ui.is_empty() {
+            return;
+        }
+        let is_game_won = game_over.won;
+        outcomes.send(GameOutcome {
+            won: is_game_won,
+            score: game_over.score,
+        });
+        commands
+            .ui_builder(UiRoot)
+            .column(|column| {
+                column
+                    .column(|column| {
+                        column
+                            .label(LabelConfig {
+                                label: "Game Over!".to_string(),
+                                ..Default::default()
+                            })
+                            .style()
+                            .font_size(48.);
+                        column
+                            .label(LabelConfig {
+                                label: format!("YOU {}", if is_game_won { "WIN" } else { "LOSE" }),
+                                ..Default::default()
+                            })
+                            .style()
+                            .font_size(96.);
+                        if allow_restarts.is_some() {
                             column
                                 .container((RestartButton, ButtonBundle::default()), |builder| {
                                     builder
@@ -108,25 +197,25 @@ impl GameLoopPlugin {
                                 .border(UiRect::all(Val::Px(4.)))
                                 .border_color(Color::BLACK)
                                 .background_color(Color::Srgba(palettes::css::BLUE_VIOLET));
-                        })
-                        .style()
-                        .height(Val::Auto)
-                        .padding(UiRect::all(Val::Px(20.)))
-                        .row_gap(Val::Px(10.))
-                        .justify_content(JustifyContent::Center)
-                        .align_items(AlignItems::Center)
-                        .border(UiRect::all(Val::Px(8.)))
-                        .border_color(Color::BLACK)
-                        .background_color(Color::srgb(0.2, 0.2, 0.2));
-                })
-                .insert((GameoverUI, Name::new("Gameover UI")))
-                .style()
-                .height(Val::Percent(100.))
-                .width(Val::Percent(100.))
-                .background_color(Color::srgba(0.3, 0.3, 0.3, 0.1))
-                .justify_content(JustifyContent::Center)
-                .align_items(AlignItems::Center);
-        }
+                        }
+                    })
+                    .style()
+                    .height(Val::Auto)
+                    .padding(UiRect::all(Val::Px(20.)))
+                    .row_gap(Val::Px(10.))
+                    .justify_content(JustifyContent::Center)
+                    .align_items(AlignItems::Center)
+                    .border(UiRect::all(Val::Px(8.)))
+                    .border_color(Color::BLACK)
+                    .background_color(Color::srgb(0.2, 0.2, 0.2));
+            })
+            .insert((GameoverUI, Name::new("Gameover UI")))
+            .style()
+            .height(Val::Percent(100.))
+            .width(Val::Percent(100.))
+            .background_color(Color::srgba(0.3, 0.3, 0.3, 0.1))
+            .justify_content(JustifyContent::Center)
+            .align_items(AlignItems::Center);
     }
 }
 
@@ -134,9 +223,33 @@ impl GameLoopPlugin {
 #[derive(Component, Reflect)]
 pub struct Player;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Scorer {
+    Car(Entity),
+    Team(Team),
+}
+
+/// How the match ended for the player.
 #[derive(Clone, Copy, Debug)]
 #[derive(Resource, Reflect)]
-pub struct GameOver;
+pub struct GameOver {
+    pub won: bool,
+    pub score: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Resource)]
+struct AllowRestarts;
+
+/// Sent once when a match ends.
+#[derive(Clone, Copy, Debug)]
+#[derive(Event, Reflect)]
+pub struct GameOutcome {
+    /// Whether the player, or the player's team, won.
+    pub won: bool,
+    /// The player's own points at the end of the match.
+    pub score: u32,
+}
 
 #[derive(Clone, Copy, Debug, Default)]
 #[derive(Component)]
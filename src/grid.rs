@@ -0,0 +1,250 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext, UntypedHandle},
+    prelude::*,
+};
+use bevy_asset_loader::prelude::{
+    AssetCollection, ConfigureLoadingState, LoadingState, LoadingStateAppExt,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use car::Car;
+
+use crate::MatchRules;
+
+/// Loads the names bots race under from a `.names.ron` file into [`GridConfig::names`].
+///
+/// The names are in place by the time [`CarNameAssets`] exists, so wait for it before spawning
+/// any cars.
+pub struct CarNamesPlugin {
+    pub path: String,
+}
+
+impl Default for CarNamesPlugin {
+    fn default() -> Self {
+        Self {
+            path: "names/default.names.ron".to_string(),
+        }
+    }
+}
+
+impl Plugin for CarNamesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<CarNamePool>()
+            .init_asset_loader::<CarNamePoolLoader>()
+            .insert_resource(CarNamePoolPath(self.path.clone()))
+            .init_state::<CarNameAssetsState>()
+            .add_loading_state(
+                LoadingState::new(CarNameAssetsState::Loading)
+                    .load_collection::<CarNameAssets>()
+                    .continue_to_state(CarNameAssetsState::Loaded),
+            )
+            // before anything in `Update` can see the collection and spawn a grid
+            .add_systems(OnEnter(CarNameAssetsState::Loaded), Self::apply_name_pool)
+            .add_systems(
+                Update,
+                Self::apply_name_pool.run_if(resource_exists::<CarNameAssets>),
+            );
+    }
+}
+
+impl CarNamesPlugin {
+    fn apply_name_pool(
+        mut events: EventReader<AssetEvent<CarNamePool>>,
+        assets: Res<CarNameAssets>,
+        pools: Res<Assets<CarNamePool>>,
+        mut rules: ResMut<MatchRules>,
+    ) {
+        // once as soon as it is loaded, and again whenever the file changes
+        let is_modified = events.read().any(|event| event.is_modified(&assets.pool));
+        if !assets.is_added() && !is_modified {
+            return;
+        }
+        let Some(pool) = pools.get(&assets.pool) else {
+            return;
+        };
+        if pool.names.is_empty() {
+            warn!("Keeping the built-in car names, the name pool is empty");
+            return;
+        }
+        rules.grid.names.clone_from(&pool.names);
+    }
+}
+
+/// How the starting grid is laid out and who is on it.
+///
+/// From back to front, the bomb holder starts in the middle of the road, then come up to `rows`
+/// rows of `columns` cars with every other column staggered a chunk ahead, then the flag holder.
+#[derive(Clone, Debug)]
+#[derive(Reflect)]
+pub struct GridConfig {
+    /// How many cars start the match, counting the bomb and flag holders, who always do. The
+    /// grid fills from the back and stops once there are this many, so its front row can be
+    /// left partly empty.
+    pub cars: usize,
+    pub rows: usize,
+    pub columns: usize,
+    /// The chunk the bomb holder starts on.
+    pub bomb_holder_chunk: usize,
+    /// The chunk the back row of the grid starts on.
+    pub grid_chunk: usize,
+    /// The chunk the flag holder starts on, or just ahead of the grid when `None`.
+    pub flag_holder_chunk: Option<usize>,
+    /// The grid spot the player starts in, counting from the back left, or a random one when
+    /// `None`.
+    pub player_slot: Option<usize>,
+    /// Names handed out to bots on the grid, reused once every one has been taken.
+    pub names: Vec<String>,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self {
+            cars: 14,
+            rows: 4,
+            columns: 3,
+            bomb_holder_chunk: 0,
+            grid_chunk: 2,
+            flag_holder_chunk: None,
+            player_slot: None,
+            names: [
+                "snen",
+                "Pierre",
+                "Rodriguez",
+                "Cassandra",
+                "Samuel",
+                "Lin",
+                "Taylor",
+                "Archibald",
+                "Walter",
+                "Walter Two",
+                "Rachel",
+                "Mr Robot",
+                "Lightning McQueen",
+                "Boat",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+impl GridConfig {
+    /// The bomb and flag holders, who start off the grid.
+    pub const TAG_HOLDERS: usize = 2;
+
+    /// How many cars start on the grid, not counting the tag holders.
+    pub fn slots(&self) -> usize {
+        self.cars
+            .saturating_sub(Self::TAG_HOLDERS)
+            .min(self.rows * self.columns)
+    }
+
+    /// Whether there are enough spots on the grid for every car.
+    pub fn fits_cars(&self) -> bool {
+        self.slots() + Self::TAG_HOLDERS >= self.cars
+    }
+
+    // how many rows have a car in them
+    fn filled_rows(&self) -> usize {
+        self.slots().div_ceil(self.columns.max(1))
+    }
+
+    /// The chunk for the grid spot in `row` and `column`.
+    pub fn slot_chunk(&self, row: usize, column: usize) -> usize {
+        self.grid_chunk + row * 2 + column % 2
+    }
+
+    pub fn flag_holder_chunk(&self) -> usize {
+        self.flag_holder_chunk
+            .unwrap_or(self.grid_chunk + self.filled_rows() * 2 + 1)
+    }
+
+    /// How many chunks a track needs for every car to start on its own.
+    pub fn chunks_needed(&self) -> usize {
+        self.bomb_holder_chunk
+            .max(self.slot_chunk(self.filled_rows().saturating_sub(1), 1))
+            .max(self.flag_holder_chunk())
+            + 1
+    }
+
+    /// How wide the road has to be to fit the columns side by side, with a car's width between
+    /// each of them and at either edge.
+    pub fn min_width(&self) -> f32 {
+        Car::WIDTH * (self.columns as f32 * 2. + 2.)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[derive(States)]
+pub enum CarNameAssetsState {
+    #[default]
+    Loading,
+    Loaded,
+}
+
+#[derive(Clone, Debug)]
+#[derive(Resource)]
+struct CarNamePoolPath(String);
+
+/// The name pool, loaded from the path given to [`CarNamesPlugin`].
+#[derive(Clone, Debug)]
+#[derive(Resource)]
+pub struct CarNameAssets {
+    pub pool: Handle<CarNamePool>,
+}
+
+// written out by hand, since the derive only takes paths known at compile time
+impl AssetCollection for CarNameAssets {
+    fn create(world: &mut World) -> Self {
+        let path = world.resource::<CarNamePoolPath>().0.clone();
+        Self {
+            pool: world.resource::<AssetServer>().load(path),
+        }
+    }
+
+    fn load(world: &mut World) -> Vec<UntypedHandle> {
+        let path = world.resource::<CarNamePoolPath>().0.clone();
+        let pool = world.resource::<AssetServer>().load::<CarNamePool>(path);
+        vec![pool.untyped()]
+    }
+}
+
+/// Names read from a `.names.ron` file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Asset, TypePath)]
+pub struct CarNamePool {
+    names: Vec<String>,
+}
+
+#[derive(Default)]
+struct CarNamePoolLoader;
+
+#[derive(Debug, Error)]
+pub enum CarNamePoolLoaderError {
+    #[error("Could not read car names: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse car names: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for CarNamePoolLoader {
+    type Asset = CarNamePool;
+    type Settings = ();
+    type Error = CarNamePoolLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _: &'a (),
+        _: &'a mut LoadContext<'_>,
+    ) -> Result<CarNamePool, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["names.ron"]
+    }
+}
@@ -1,47 +1,100 @@
 #[cfg(feature = "debug-all")]
 use avian2d::prelude::PhysicsDebugPlugin;
 use avian2d::{prelude::Gravity, PhysicsPlugins};
-use bevy::{app::PluginGroupBuilder, prelude::*};
+use bevy::{
+    app::PluginGroupBuilder,
+    color::palettes,
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
 #[cfg(feature = "debug")]
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_reactive_blueprints::BlueprintsPlugin;
 
-use bot_controller::BotControllerBundle;
+use bot_controller::{BotControllerBundle, BotProfile};
 use camera::{CameraTracker, GameCamera};
-use car::{Car, CarBlueprint};
+use car::{Car, CarBlueprint, CarClass, CarPaint, Wrecked};
 use controller::Controller;
 use entropy::{EntropyPlugin, GlobalEntropy, RngCore};
-use laptag::{BombTagIt, CanBeIt, LapTagIt, Score, TagEvent};
-use scoreboard::CarName;
-use track::{CheckpointHighlightTracker, LapComplete, Track, TrackChunk};
+use laptag::{
+    BombExploded, BombFuseRules, BombTagIt, CanBeIt, DropTags, Eliminated, FuseTick, HeldTags,
+    LapTagIt, LapTagSystems, Lives, Score, TagCourier, TagEvent, Team,
+};
+use resurfacer::{Obstacle, Peg, Resurfacer, ResurfacerSettings};
+use scoreboard::{CarDescription, CarName, Scoreboard};
+use track::{
+    Checkpoint, CheckpointHighlightTracker, LapComplete, LastCheckpoint, Track, TrackChunk,
+    TrackGenerator, TrackInterior, Wall,
+};
 
+mod boost;
+pub use boost::BoostPlugin;
+mod controls;
+pub use controls::ControlsSettingsPlugin;
+mod editor;
+pub use editor::{TrackDesign, TrackEditorPlugin};
 mod game_loop;
-pub use game_loop::Player;
+pub use game_loop::{GameLoopPlugin, GameOutcome, Player};
+mod grid;
+pub use grid::{CarNameAssets, CarNameAssetsState, CarNamesPlugin, GridConfig};
+#[cfg(feature = "netcode")]
+mod net;
+#[cfg(feature = "netcode")]
+pub use net::{NetHostPlugin, NetJoinPlugin, NetPeerPlugin};
+mod respawn;
+pub use respawn::{RespawnPlugin, RespawnSystems};
+mod rules;
+pub use rules::{MatchMode, MatchRules, RespawnRules};
+mod profile;
+pub use profile::{LeaderboardEntry, Profile, ProfilePlugin};
+mod storage;
+mod time_trial;
+pub use time_trial::{BestLap, BestLapRecord, LapTimer, TimeTrialPlugin};
 
-pub struct TagcarPlugins;
+#[derive(Default)]
+pub struct TagcarPlugins {
+    /// Runs physics, driving, tagging, laps, obstacles and bots in this schedule rather than
+    /// in `Update` and `PostUpdate`.
+    pub simulation_schedule: Option<InternedScheduleLabel>,
+}
 
 impl PluginGroup for TagcarPlugins {
     fn build(self) -> PluginGroupBuilder {
+        let (physics, schedule) = match self.simulation_schedule {
+            Some(schedule) => (PhysicsPlugins::new(schedule), schedule),
+            None => (PhysicsPlugins::default(), Update.intern()),
+        };
         let builder = PluginGroupBuilder::start::<Self>();
         #[cfg(feature = "debug")]
         let builder = builder.add(WorldInspectorPlugin::default());
         #[cfg(feature = "debug-all")]
         let builder = builder.add(PhysicsDebugPlugin::default());
         let builder = builder
-            .add_group(PhysicsPlugins::default())
-            .add(EntropyPlugin)
+            .add_group(physics)
+            .add(EntropyPlugin::default())
             .add(BlueprintsPlugin)
-            .add(car::CarPlugin)
+            .add(car::CarPlugin { schedule })
             .add(controller::CarControllerPlugin)
-            .add(track::TrackPlugin)
-            .add_group(laptag::LapTagPlugins)
-            .add(resurfacer::ResurfacerPlugin)
+            .add(track::TrackPlugin { schedule })
+            .add_group(laptag::LapTagPlugins { schedule })
+            .add(resurfacer::ResurfacerPlugin { schedule })
             .add(scoreboard::ScoreboardPlugin)
             // .add(slowmo::SlowmoPlugin)
-            .add(bot_controller::BotControllerPlugin)
+            .add(bot_controller::BotControllerPlugin { schedule })
             .add(camera::GameCameraPlugin)
-            .add(IntegrationPlugin)
-            .add(game_loop::GameLoopPlugin);
+            .add(IntegrationPlugin { schedule })
+            .add(RespawnPlugin { schedule })
+            .add(BoostPlugin { schedule })
+            .add(TimeTrialPlugin)
+            .add(ProfilePlugin)
+            .add(ControlsSettingsPlugin)
+            .add(CarNamesPlugin::default())
+            .add(GameLoopPlugin {
+                schedule,
+                ..Default::default()
+            });
+        #[cfg(feature = "telemetry")]
+        let builder = builder.add(telemetry::TelemetryPlugin::default());
         #[cfg(feature = "audio")]
         let builder = builder
             .add(bevy_kira_audio::AudioPlugin)
@@ -51,14 +104,59 @@ impl PluginGroup for TagcarPlugins {
     }
 }
 
-pub struct IntegrationPlugin;
+pub struct IntegrationPlugin {
+    /// The schedule the rules that change the match are applied in.
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for IntegrationPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
 
 impl Plugin for IntegrationPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Gravity::ZERO);
+        app.init_resource::<MatchRules>();
+        app.register_type::<MatchRules>()
+            .register_type::<MatchMode>()
+            .register_type::<RespawnRules>()
+            .register_type::<GridConfig>();
+        app.add_systems(self.schedule, Self::sync_resurfacer_tag_rule);
+        app.add_systems(
+            self.schedule,
+            Self::sync_bomb_fuse_rules
+                .before(LapTagSystems)
+                .run_if(resource_changed::<MatchRules>),
+        );
+        app.add_systems(
+            self.schedule,
+            Self::drop_tags_from_wrecks
+                .in_set(LapTagSystems)
+                .run_if(|rules: Res<MatchRules>| rules.wrecks_drop_tags),
+        );
+        #[cfg(feature = "telemetry")]
         app.configure_sets(
             Update,
-            camera::GameCameraSystems::Shake.run_if(event_occurs_on_camera::<TagEvent>),
+            telemetry::TelemetrySystems
+                .after(controller::CarControlSystems)
+                .after(bot_controller::BotControllerSystems),
+        )
+        .add_systems(
+            Update,
+            (
+                Self::track_player_telemetry,
+                Self::export_telemetry_on_restart,
+            ),
+        );
+        app.configure_sets(
+            Update,
+            camera::GameCameraSystems::Shake.run_if(
+                event_occurs_on_camera::<TagEvent>.or_else(event_occurs_on_camera::<BombExploded>),
+            ),
         );
         #[cfg(feature = "audio")]
         app.configure_sets(
@@ -70,6 +168,23 @@ impl Plugin for IntegrationPlugin {
             Update,
             audio_fx::AudioFxSystems::ScoreFx.run_if(event_occurs_on_camera::<LapComplete>),
         );
+        #[cfg(feature = "audio")]
+        app.configure_sets(
+            Update,
+            audio_fx::AudioFxSystems::FuseFx.run_if(event_occurs_on_camera::<FuseTick>),
+        );
+        #[cfg(feature = "audio")]
+        app.configure_sets(
+            Update,
+            audio_fx::AudioFxSystems::ExplosionFx.run_if(event_occurs_on_camera::<BombExploded>),
+        );
+        #[cfg(feature = "audio")]
+        app.configure_sets(
+            Update,
+            audio_fx::AudioFxSystems::EngineFx
+                .after(controller::CarControlSystems)
+                .after(bot_controller::BotControllerSystems),
+        );
         // TODO: Slowmo just makes the game feel laggy. zoom in or something?
         // app.configure_sets(
         //     Update,
@@ -82,8 +197,103 @@ impl Plugin for IntegrationPlugin {
     }
 }
 
+impl IntegrationPlugin {
+    fn sync_resurfacer_tag_rule(
+        mut commands: Commands,
+        // clients leave the resurfacer to the server, so they don't have its settings
+        settings: Option<Res<ResurfacerSettings>>,
+        resurfacers: Query<Entity, With<Resurfacer>>,
+        new_resurfacers: Query<Entity, Added<Resurfacer>>,
+    ) {
+        let Some(settings) = settings else {
+            return;
+        };
+        let targets = if settings.is_changed() {
+            resurfacers.iter().collect::<Vec<_>>()
+        } else {
+            new_resurfacers.iter().collect::<Vec<_>>()
+        };
+        for entity in targets {
+            // as a `CanBeIt` entity, the resurfacer takes flags from the cars that touch it
+            // and hands whatever it is holding to the next car it runs into, but as a courier it
+            // never scores or blows up with them
+            if settings.swaps_tags {
+                commands.entity(entity).insert((CanBeIt, TagCourier));
+            } else {
+                commands
+                    .entity(entity)
+                    .remove::<(CanBeIt, TagCourier, LapTagIt, BombTagIt, HeldTags)>();
+            }
+        }
+    }
+
+    fn sync_bomb_fuse_rules(mut commands: Commands, rules: Res<MatchRules>) {
+        match rules.bomb_fuse {
+            Some(fuse) => commands.insert_resource(fuse),
+            None => commands.remove_resource::<BombFuseRules>(),
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn drop_tags_from_wrecks(
+        mut commands: Commands,
+        wrecks: Query<Entity, Added<Wrecked>>,
+        cars: Query<
+            Entity,
+            (
+                With<Car>,
+                With<CanBeIt>,
+                Without<Wrecked>,
+                Without<Eliminated>,
+            ),
+        >,
+    ) {
+        // each tag goes to the nearest car it is allowed to, so flags skip over teammates
+        let candidates = cars.iter().collect::<Vec<_>>();
+        for wreck in &wrecks {
+            commands.entity(wreck).add(DropTags {
+                candidates: candidates.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(feature = "telemetry")]
+impl IntegrationPlugin {
+    fn track_player_telemetry(mut commands: Commands, new_players: Query<Entity, Added<Player>>) {
+        for player in &new_players {
+            commands.entity(player).insert(telemetry::TelemetryTracked);
+        }
+    }
+
+    // each restart ends one match and starts another
+    fn export_telemetry_on_restart(
+        restart_buttons: Query<
+            &Interaction,
+            (With<game_loop::RestartButton>, Changed<Interaction>),
+        >,
+        mut exports: EventWriter<telemetry::ExportTelemetry>,
+    ) {
+        if restart_buttons
+            .iter()
+            .any(|interaction| matches!(interaction, Interaction::Pressed))
+        {
+            exports.send(telemetry::ExportTelemetry);
+        }
+    }
+}
+
 trait GetEntities {
     fn entities(&self) -> impl Iterator<Item = Entity> + '_;
+
+    fn positions<'a>(
+        &'a self,
+        transforms: &'a Query<&Transform>,
+    ) -> impl Iterator<Item = Vec3> + 'a {
+        self.entities()
+            .filter_map(|entity| transforms.get(entity).ok())
+            .map(|transform| transform.translation)
+    }
 }
 
 impl GetEntities for LapComplete {
@@ -98,6 +308,23 @@ impl GetEntities for TagEvent {
     }
 }
 
+impl GetEntities for FuseTick {
+    fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        std::iter::once(self.holder)
+    }
+}
+
+impl GetEntities for BombExploded {
+    fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        std::iter::once(self.holder)
+    }
+
+    // the holder is gone by the time anyone hears the blast, so use where it went off
+    fn positions<'a>(&'a self, _: &'a Query<&Transform>) -> impl Iterator<Item = Vec3> + 'a {
+        std::iter::once(self.position.extend(0.))
+    }
+}
+
 fn event_occurs_on_camera<E: GetEntities + Event>(
     mut tag_events: EventReader<E>,
     positions: Query<&Transform>,
@@ -106,13 +333,11 @@ fn event_occurs_on_camera<E: GetEntities + Event>(
     let Ok((camera, camera_transform)) = camera.get_single() else {
         return false;
     };
-    for entity in tag_events.read().flat_map(|event| event.entities()) {
-        let Ok(transform) = positions.get(entity) else {
-            continue;
-        };
-        let Some(viewport_position) =
-            camera.world_to_viewport(camera_transform, transform.translation)
-        else {
+    for position in tag_events
+        .read()
+        .flat_map(|event| event.positions(&positions))
+    {
+        let Some(viewport_position) = camera.world_to_viewport(camera_transform, position) else {
             continue;
         };
         let Some(view_rect) = camera.logical_viewport_rect() else {
@@ -125,121 +350,269 @@ fn event_occurs_on_camera<E: GetEntities + Event>(
     false
 }
 
-const ROW_COUNT: usize = 4;
-const COL_COUNT: usize = 3;
-const GRID_COUNT: usize = ROW_COUNT * COL_COUNT;
+/// Spawns the track and everything around it, but no cars.
+pub fn spawn_arena(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    track: &Track,
+) {
+    spawn_background(commands, meshes, materials, track);
+    spawn_track(commands, track);
+    commands.spawn(Scoreboard);
+}
+
+pub(crate) fn spawn_background(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    track: &Track,
+) {
+    commands.spawn((
+        Name::new("Background"),
+        ColorMesh2dBundle {
+            mesh: meshes
+                .add(Rectangle::new(track.radius(), track.half_length()))
+                .into(),
+            material: materials.add(Color::Srgba(palettes::css::DARK_SLATE_GREY)),
+            transform: Transform::from_translation(Vec3::NEG_Z).with_scale(Vec3::splat(5.)),
+            ..Default::default()
+        },
+    ));
+}
+
+/// Everything spawned for a track, to be swapped out along with it.
+pub(crate) type TrackPieces = Or<(
+    With<Track>,
+    With<TrackInterior>,
+    With<Checkpoint>,
+    With<Wall>,
+    With<Resurfacer>,
+    With<Obstacle>,
+    With<Peg>,
+)>;
+
+pub(crate) fn spawn_track(commands: &mut Commands, track: &Track) {
+    if let Some(interior) = track.interior() {
+        commands.spawn(interior.bundle());
+    }
+    commands.spawn(track.clone().bundle());
+}
+
+/// The track the next match is played on.
+pub fn match_track(rules: &MatchRules, entropy: &mut GlobalEntropy) -> Track {
+    match rules.track_difficulty {
+        Some(difficulty) => {
+            TrackGenerator::new(entropy.next_u64(), difficulty, rules.grid.min_width()).generate()
+        }
+        None => Track::default(),
+    }
+}
 
-pub fn spawn_cars(commands: &mut Commands, track: &Track, entropy: &mut GlobalEntropy) {
+pub fn spawn_cars(
+    commands: &mut Commands,
+    track: &Track,
+    entropy: &mut GlobalEntropy,
+    rules: &MatchRules,
+) {
+    if rules.mode == MatchMode::TimeTrial {
+        time_trial::spawn_time_trial_car(commands, track, rules);
+        return;
+    }
+    let bounds_max = camera_bounds(track);
+    for player in spawn_grid(commands, track, entropy, rules, 1) {
+        commands.entity(player).insert((
+            Player,
+            CarName::new("Me (You)"),
+            Controller::ArrowKeys,
+            CameraTracker::rect(-bounds_max, bounds_max),
+            CheckpointHighlightTracker,
+        ));
+    }
+}
+
+/// Spawns the tag holders and the starting grid, filling every spot but `drivers` of them with
+/// bots, and returns the cars left for someone to drive.
+pub(crate) fn spawn_grid(
+    commands: &mut Commands,
+    track: &Track,
+    entropy: &mut GlobalEntropy,
+    rules: &MatchRules,
+    drivers: usize,
+) -> Vec<Entity> {
     let chunks = track.chunks().collect::<Vec<_>>();
-    let bounds_max = Vec2::new(track.half_length() - 300., track.radius() - 200.);
+    // in team mode, deal cars out to each team in spawn order
+    let mut spawn_index = 0;
+    let mut next_team = || {
+        let team = rules.teams.map(|teams| Team(spawn_index % teams.max(1)));
+        spawn_index += 1;
+        team
+    };
 
-    // from back to front, we spawn:
-    // a bomb holder in the center of the checkpoint
-    // a COLxROW grid of not-IT players in the next ROW checkpoints
-    // a flag holder in the center of the ROW+1 checkpoint
+    let grid = &rules.grid;
+    if !grid.fits_cars() {
+        warn!(
+            "Only {} of {} cars fit on a {}x{} grid",
+            grid.slots() + GridConfig::TAG_HOLDERS,
+            grid.cars,
+            grid.rows,
+            grid.columns
+        );
+    }
+    // wrap around on tracks too short for the whole grid, which stacks cars on top of each other
+    if chunks.len() < grid.chunks_needed() {
+        warn!(
+            "The grid needs {} chunks of track but there are only {}, so some cars start on top \
+            of each other",
+            grid.chunks_needed(),
+            chunks.len()
+        );
+    }
+    let chunk = |index: usize| &chunks[index % chunks.len()];
 
     // spawn bomb holder
-    commands.spawn((
-        BotControllerBundle::new(entropy),
-        BombTagIt,
-        CAR_NAME_KENMIN,
-        car_from_track(
-            track,
-            chunks.first().expect("Cars to spawn on known checkpoints"),
-            0.5,
-            false,
-        ),
-    ));
+    let bomb_holder = commands
+        .spawn((
+            bot(entropy, rules),
+            BombTagIt,
+            CAR_NAME_KENMIN,
+            car_from_track(
+                chunk(grid.bomb_holder_chunk),
+                0.5,
+                false,
+                random_class(entropy),
+            ),
+        ))
+        .id();
+    enter_match(commands, bomb_holder, next_team(), rules);
 
-    // spawn the grid, including the player
-    let random_grid_index = entropy.next_u32() as f32 / u32::MAX as f32 * GRID_COUNT as f32;
-    let cars = (0..ROW_COUNT)
-        .flat_map(|row_index| (0..COL_COUNT).map(move |col_index| (col_index, row_index)))
+    // pick spots on the grid for the drivers, the first one fixed if the rules say so
+    let slots = grid.slots();
+    let mut driver_indices = vec![];
+    if let Some(slot) = grid.player_slot.filter(|_| drivers > 0 && slots > 0) {
+        driver_indices.push(slot.min(slots - 1));
+    }
+    while driver_indices.len() < drivers.min(slots) {
+        let index = (entropy.next_u32() as f32 / u32::MAX as f32 * slots as f32) as usize % slots;
+        if !driver_indices.contains(&index) {
+            driver_indices.push(index);
+        }
+    }
+
+    // spawn the grid, including the drivers
+    let cars = (0..grid.rows)
+        .flat_map(|row_index| (0..grid.columns).map(move |col_index| (col_index, row_index)))
+        .take(slots)
         .map(|(col_index, row_index)| {
+            let is_driven = driver_indices.contains(&(col_index + row_index * grid.columns));
             car_from_track(
-                track,
-                chunks
-                    .get(row_index * 2 + 2 + col_index % 2)
-                    .expect("Cars to spawn on known checkpoints"),
-                col_index as f32 / COL_COUNT as f32,
-                col_index + row_index * COL_COUNT == random_grid_index as usize,
+                chunk(grid.slot_chunk(row_index, col_index)),
+                col_index as f32 / grid.columns as f32,
+                is_driven,
+                if is_driven {
+                    rules.player_class
+                } else {
+                    random_class(entropy)
+                },
             )
         })
         .collect::<Vec<_>>();
 
+    let mut names = vec![];
+    let mut driven_cars = vec![None; driver_indices.len()];
     for (index, car) in cars.into_iter().enumerate() {
-        if index == random_grid_index as usize {
-            // this one is the player
-            commands.spawn((
-                car,
-                Player,
-                CarName::new("Me (You)"),
-                Controller::ArrowKeys,
-                CameraTracker::rect(-bounds_max, bounds_max),
-                CheckpointHighlightTracker,
-            ));
+        let car = if let Some(driver) = driver_indices.iter().position(|i| *i == index) {
+            let car = commands.spawn(car).id();
+            driven_cars[driver] = Some(car);
+            car
         } else {
-            let random_name_index =
-                (entropy.next_u32() as f32 / u32::MAX as f32 * GRID_COUNT as f32) as usize;
-            commands.spawn((
-                car,
-                CAR_NAMES[random_name_index],
-                BotControllerBundle::new(entropy),
-            ));
-        }
+            // draw names without repeats until the pool runs dry, then start over
+            if names.is_empty() {
+                names = grid.names.clone();
+            }
+            let name = if names.is_empty() {
+                CarName::new("Bot")
+            } else {
+                let index = entropy.next_u32() as usize % names.len();
+                CarName::from(names.swap_remove(index))
+            };
+            commands.spawn((car, name, bot(entropy, rules))).id()
+        };
+        enter_match(commands, car, next_team(), rules);
     }
 
     // spawn flag holder
-    commands.spawn((
-        BotControllerBundle::new(entropy),
-        CAR_NAME_KOOFY,
-        LapTagIt,
-        car_from_track(
-            track,
-            chunks
-                .get(ROW_COUNT * 2 + 3)
-                .expect("Cars to spawn on known checkpoints"),
-            0.5,
-            false,
-        ),
-    ));
+    let flag_holder = commands
+        .spawn((
+            bot(entropy, rules),
+            CAR_NAME_KOOFY,
+            LapTagIt,
+            car_from_track(
+                chunk(grid.flag_holder_chunk()),
+                0.5,
+                false,
+                random_class(entropy),
+            ),
+        ))
+        .id();
+    enter_match(commands, flag_holder, next_team(), rules);
+
+    driven_cars.into_iter().flatten().collect()
+}
+
+fn enter_match(commands: &mut Commands, car: Entity, team: Option<Team>, rules: &MatchRules) {
+    if let Some(team) = team {
+        commands.entity(car).insert((team, CarPaint(team.color())));
+    }
+    if let Some(respawn) = &rules.respawn {
+        commands.entity(car).insert(Lives::new(respawn.lives));
+    }
 }
 
 fn car_from_track(
-    track: &Track,
     chunk: &TrackChunk,
     offset_along_line: f32,
     is_player: bool,
+    class: CarClass,
 ) -> impl Bundle {
-    let start_offset: f32 = track.interior_radius() + Car::WIDTH;
-    let car_index_offset = offset_along_line * (track.thickness() - Car::WIDTH * 2.);
     // the car with scoring tag starts ahead
     // and the car with bomb tag starts behind
-    let spawn_angle = chunk.angle() + std::f32::consts::FRAC_PI_2;
-    let spawn_position =
-        chunk.origin() + Vec2::from_angle(chunk.angle()) * (start_offset + car_index_offset);
+    let (spawn_position, spawn_angle) = spawn_pose(chunk, offset_along_line);
     (
-        CarBlueprint::new(spawn_position, spawn_angle, is_player),
+        CarBlueprint::new(spawn_position, spawn_angle, is_player, class),
         Score::default(),
         CanBeIt,
+        LastCheckpoint::default(),
     )
 }
 
+// a bot with a random personality, introduced in the scoreboard
+fn bot(entropy: &mut GlobalEntropy, rules: &MatchRules) -> impl Bundle {
+    let profile = BotProfile::random(rules.bot_skill, entropy);
+    (
+        CarDescription::from(profile.to_string()),
+        BotControllerBundle::new(entropy, profile),
+    )
+}
+
+fn random_class(entropy: &mut GlobalEntropy) -> CarClass {
+    let index = entropy.next_u32() as usize % CarClass::ALL.len();
+    CarClass::ALL[index]
+}
+
+// how far the camera can pan from the center of the track
+fn camera_bounds(track: &Track) -> Vec2 {
+    Vec2::new(track.half_length() - 300., track.radius() - 200.)
+}
+
+// where a car sits on a checkpoint line, facing along the track
+fn spawn_pose(chunk: &TrackChunk, offset_along_line: f32) -> (Vec2, f32) {
+    let car_index_offset = offset_along_line * (chunk.width() - Car::WIDTH * 2.);
+    let spawn_angle = chunk.angle() + std::f32::consts::FRAC_PI_2;
+    let spawn_position =
+        chunk.inner_edge() + Vec2::from_angle(chunk.angle()) * (Car::WIDTH + car_index_offset);
+    (spawn_position, spawn_angle)
+}
+
 const CAR_NAME_KOOFY: CarName = CarName::new("koofy");
 const CAR_NAME_KENMIN: CarName = CarName::new("BeautifulKenmin");
-const CAR_NAMES: [CarName; GRID_COUNT + 2] = [
-    CarName::new("snen"),
-    CarName::new("Pierre"),
-    CarName::new("Rodriguez"),
-    CarName::new("Cassandra"),
-    CarName::new("Samuel"),
-    CarName::new("Lin"),
-    CarName::new("Taylor"),
-    CarName::new("Archibald"),
-    CarName::new("Walter"),
-    CarName::new("Walter Two"),
-    CarName::new("Rachel"),
-    CarName::new("Mr Robot"),
-    CarName::new("Lightning McQueen"),
-    CarName::new("Boat"),
-];
@@ -1,11 +1,12 @@
-use bevy::{asset::AssetMetaCheck, color::palettes, prelude::*};
+use bevy::{asset::AssetMetaCheck, prelude::*};
 
 use entropy::GlobalEntropy;
 use laptag::LapTagAssets;
-use scoreboard::Scoreboard;
-use track::{Track, TrackAssets, TrackInterior};
+use track::TrackAssets;
 
-use tagcar::{spawn_cars, Player, TagcarPlugins};
+use tagcar::{
+    match_track, spawn_arena, spawn_cars, CarNameAssets, MatchRules, Player, TagcarPlugins,
+};
 
 fn main() {
     let mut app = App::new();
@@ -16,12 +17,13 @@ fn main() {
         meta_check: AssetMetaCheck::Never,
         ..default()
     }));
-    app.add_plugins(TagcarPlugins);
+    app.add_plugins(TagcarPlugins::default());
 
     app.add_systems(Startup, spawn_loading_ui);
 
     let run_condition = resource_exists::<TrackAssets>
         .and_then(resource_exists::<LapTagAssets>)
+        .and_then(resource_exists::<CarNameAssets>)
         .and_then(run_once());
     #[cfg(feature = "audio")]
     let run_condition = resource_exists::<bg_music::BgMusicAssets>
@@ -68,24 +70,11 @@ fn spawn_game(
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut entropy: ResMut<GlobalEntropy>,
+    rules: Res<MatchRules>,
 ) {
-    let track = Track::default();
-    spawn_cars(&mut commands, &track, entropy.as_mut());
-    commands.spawn((
-        Name::new("Background"),
-        ColorMesh2dBundle {
-            mesh: meshes
-                .add(Rectangle::new(track.radius(), track.half_length()))
-                .into(),
-            material: materials.add(Color::Srgba(palettes::css::DARK_SLATE_GREY)),
-            transform: Transform::from_translation(Vec3::NEG_Z).with_scale(Vec3::splat(5.)),
-            ..Default::default()
-        },
-    ));
-    let interior = TrackInterior::from_track(&track);
-    commands.spawn(interior.bundle());
-    commands.spawn(track.bundle());
-    commands.spawn(Scoreboard);
+    let track = match_track(&rules, entropy.as_mut());
+    spawn_cars(&mut commands, &track, entropy.as_mut(), &rules);
+    spawn_arena(&mut commands, &mut meshes, &mut materials, &track);
 }
 
 fn despawn_ui(mut commands: Commands, ui_roots: Query<Entity, With<LoadingUI>>) {
@@ -0,0 +1,394 @@
+use std::{net::SocketAddr, time::Duration};
+
+use avian2d::prelude::PhysicsSet;
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy_ggrs::{GgrsApp, GgrsSchedule, Session};
+
+use bot_controller::{BotControllerSystems, BotProgress, BotReaction};
+use camera::CameraTracker;
+use car::{Car, CarGraphicsBundle};
+use controller::Controller;
+use entropy::GlobalEntropy;
+use laptag::LapTagAssets;
+use netcode::{
+    start_p2p_session, ClientSession, LocalNetCar, LocalRollbackInput, NetCar, NetClientPlugin,
+    NetProp, NetServerPlugin, NetcodeSystems, PropKind, ReadyUp, RemoteDriver, RollbackConfig,
+    RollbackDriver, RollbackPlugin, RollbackSystems, ServerLobby, StartNetworkMatch,
+};
+use resurfacer::{Peg, PegGraphicsBundle, Resurfacer, ResurfacerGraphicsBundle, ResurfacerSystems};
+use scoreboard::CarName;
+use track::{CheckpointHighlightTracker, Track, TrackAssets};
+
+use crate::{
+    camera_bounds, game_loop::GameOver, match_track, respawn::RespawnTimer, spawn_arena,
+    spawn_grid, CarNameAssets, MatchRules, Player, RespawnSystems,
+};
+
+/// Hosts matches for remote players on a server with nobody sitting at it.
+pub struct NetHostPlugin {
+    pub address: SocketAddr,
+}
+
+impl Plugin for NetHostPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(NetServerPlugin {
+            address: self.address,
+        })
+        .add_systems(
+            Update,
+            (
+                // a match that just started has no drivers until its cars are spawned
+                Self::end_network_match.before(NetcodeSystems),
+                Self::start_network_match.after(NetcodeSystems),
+            ),
+        );
+    }
+}
+
+impl NetHostPlugin {
+    fn start_network_match(
+        mut commands: Commands,
+        mut starts: EventReader<StartNetworkMatch>,
+        track: Query<&Track>,
+        mut entropy: ResMut<GlobalEntropy>,
+        rules: Res<MatchRules>,
+    ) {
+        let Some(start) = starts.read().last() else {
+            return;
+        };
+        let Ok(track) = track.get_single() else {
+            return;
+        };
+        let cars = spawn_grid(
+            &mut commands,
+            track,
+            entropy.as_mut(),
+            &rules,
+            start.players.len(),
+        );
+        for (car, player) in cars.into_iter().zip(&start.players) {
+            commands.entity(car).insert((
+                RemoteDriver(player.client),
+                CarName::from(player.name.clone()),
+            ));
+        }
+    }
+
+    fn end_network_match(
+        mut commands: Commands,
+        mut lobby: ResMut<ServerLobby>,
+        game_over: Option<Res<GameOver>>,
+        drivers: Query<(), With<RemoteDriver>>,
+        cars: Query<Entity, With<Car>>,
+    ) {
+        // the match is over once it is won or everyone has left or been knocked out
+        if !lobby.is_running() || (game_over.is_none() && !drivers.is_empty()) {
+            return;
+        }
+        for entity in &cars {
+            commands.entity(entity).despawn_recursive();
+        }
+        // taking the game over screen down along with it
+        commands.remove_resource::<GameOver>();
+        lobby.reopen();
+    }
+}
+
+/// Joins a match on a server and shows it, with a lobby screen until it starts.
+pub struct NetJoinPlugin {
+    pub server: SocketAddr,
+    pub name: String,
+}
+
+impl Plugin for NetJoinPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(NetClientPlugin {
+            server: self.server,
+            name: self.name.clone(),
+        })
+        .add_systems(Startup, Self::spawn_lobby_ui)
+        .add_systems(
+            Update,
+            (
+                Self::ready_up.before(NetcodeSystems),
+                (
+                    Self::dress_net_cars,
+                    Self::dress_net_props,
+                    Self::update_lobby_ui,
+                )
+                    .after(NetcodeSystems),
+            ),
+        );
+    }
+}
+
+impl NetJoinPlugin {
+    fn ready_up(inputs: Res<ButtonInput<KeyCode>>, mut readies: EventWriter<ReadyUp>) {
+        if inputs.just_pressed(KeyCode::Enter) {
+            readies.send(ReadyUp);
+        }
+    }
+
+    fn dress_net_cars(
+        mut commands: Commands,
+        new_cars: Query<(Entity, &Transform), Added<NetCar>>,
+        new_local_cars: Query<Entity, Added<LocalNetCar>>,
+        track: Query<&Track>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        for (entity, transform) in &new_cars {
+            let color = if new_local_cars.contains(entity) {
+                Color::srgb(0.3, 0.63, 0.98)
+            } else {
+                Color::srgb(0.77, 0.42, 0.34)
+            };
+            commands
+                .entity(entity)
+                .insert(CarGraphicsBundle::new(MaterialMesh2dBundle {
+                    mesh: meshes.add(Rectangle::new(Car::LENGTH, Car::WIDTH)).into(),
+                    material: materials.add(color),
+                    transform: *transform,
+                    ..Default::default()
+                }));
+        }
+        let Ok(track) = track.get_single() else {
+            return;
+        };
+        let bounds_max = camera_bounds(track);
+        for entity in &new_local_cars {
+            commands.entity(entity).insert((
+                Player,
+                CarName::new("Me (You)"),
+                CameraTracker::rect(-bounds_max, bounds_max),
+                CheckpointHighlightTracker,
+            ));
+        }
+    }
+
+    fn dress_net_props(
+        mut commands: Commands,
+        mut new_props: Query<(Entity, &NetProp, &mut Transform), Added<NetProp>>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        for (entity, prop, mut transform) in &mut new_props {
+            let mut entity = commands.entity(entity);
+            match prop.kind {
+                PropKind::Resurfacer => {
+                    transform.translation.z = Resurfacer::Z_INDEX;
+                    let graphics = ResurfacerGraphicsBundle::from_resurfacer(
+                        &Resurfacer::default(),
+                        &mut meshes,
+                        &mut materials,
+                    );
+                    entity.with_children(|builder| {
+                        builder.spawn(graphics);
+                    });
+                }
+                PropKind::Peg => {
+                    transform.translation.z = Peg::Z_INDEX;
+                    let graphics = PegGraphicsBundle::from_peg(&Peg, &mut meshes, &mut materials);
+                    entity.with_children(|builder| {
+                        builder.spawn(graphics);
+                    });
+                }
+            }
+        }
+    }
+
+    fn spawn_lobby_ui(mut commands: Commands) {
+        commands
+            .spawn((
+                Name::new("Lobby UI"),
+                LobbyUI,
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ))
+            .with_children(|builder| {
+                builder.spawn((
+                    LobbyText,
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 36.,
+                            color: Color::srgb(0.02, 0.02, 0.1),
+                            ..Default::default()
+                        },
+                    ),
+                ));
+            });
+    }
+
+    fn update_lobby_ui(
+        session: Res<ClientSession>,
+        mut roots: Query<&mut Visibility, With<LobbyUI>>,
+        mut texts: Query<&mut Text, With<LobbyText>>,
+    ) {
+        if !session.is_changed() {
+            return;
+        }
+        let is_playing = session.car().is_some();
+        for mut visibility in &mut roots {
+            *visibility = if is_playing {
+                Visibility::Hidden
+            } else {
+                Visibility::Inherited
+            };
+        }
+        let text = if !session.is_connected() {
+            "Connecting...".to_string()
+        } else {
+            let players = session
+                .lobby()
+                .iter()
+                .map(|player| {
+                    let is_you = session.client() == Some(player.client);
+                    format!(
+                        "{}{} - {}",
+                        player.name,
+                        if is_you { " (You)" } else { "" },
+                        if player.is_ready { "Ready" } else { "Waiting" },
+                    )
+                })
+                .collect::<Vec<_>>();
+            format!(
+                "LOBBY\n\n{}\n\nPress Enter when you're ready",
+                players.join("\n")
+            )
+        };
+        for mut lobby_text in &mut texts {
+            lobby_text.sections[0].value.clone_from(&text);
+        }
+    }
+}
+
+/// Plays a two player match against a peer with rollback netcode.
+///
+/// The simulation has to run in [`GgrsSchedule`], see
+/// [`TagcarPlugins::simulation_schedule`](crate::TagcarPlugins::simulation_schedule), and both
+/// peers need the same entropy seed so they build the same grid.
+pub struct NetPeerPlugin {
+    pub local_port: u16,
+    /// 0 or 1, and the other one for the peer.
+    pub local_player: usize,
+    pub remote: SocketAddr,
+    /// How long every packet is held back for, to try out bad connections on one machine.
+    pub latency: Duration,
+}
+
+impl Plugin for NetPeerPlugin {
+    fn build(&self, app: &mut App) {
+        let session = start_p2p_session(
+            self.local_port,
+            self.local_player,
+            self.remote,
+            self.latency,
+        )
+        .unwrap_or_else(|error| panic!("Failed to reach {}: {error}", self.remote));
+        info!(
+            "Playing as player {} against {}",
+            self.local_player, self.remote
+        );
+        app.add_plugins(RollbackPlugin)
+            .rollback_component_with_clone::<BotReaction>()
+            .rollback_component_with_clone::<BotProgress>()
+            .rollback_component_with_clone::<RespawnTimer>()
+            .rollback_resource_with_clone::<GameOver>()
+            .insert_resource(PendingSession {
+                session: Some(session),
+                local_player: self.local_player,
+            })
+            .configure_sets(
+                GgrsSchedule,
+                (ResurfacerSystems, BotControllerSystems)
+                    .after(RollbackSystems::Prepare)
+                    .before(PhysicsSet::Prepare),
+            )
+            // respawn delays tick on the rollback clock, which is swapped back out when the
+            // frame finishes
+            .configure_sets(
+                GgrsSchedule,
+                RespawnSystems
+                    .after(RollbackSystems::Prepare)
+                    .before(RollbackSystems::Finish),
+            )
+            .add_systems(Startup, Self::spawn_local_input)
+            .add_systems(
+                Update,
+                // both peers pick names with the same entropy, so they need the same pool
+                Self::start_peer_match.run_if(
+                    resource_exists::<TrackAssets>
+                        .and_then(resource_exists::<LapTagAssets>)
+                        .and_then(resource_exists::<CarNameAssets>)
+                        .and_then(resource_exists::<PendingSession>),
+                ),
+            );
+    }
+}
+
+impl NetPeerPlugin {
+    fn spawn_local_input(mut commands: Commands) {
+        commands.spawn((
+            Name::new("Local Input"),
+            LocalRollbackInput,
+            Controller::ArrowKeys,
+        ));
+    }
+
+    fn start_peer_match(
+        mut commands: Commands,
+        mut pending: ResMut<PendingSession>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+        mut entropy: ResMut<GlobalEntropy>,
+        rules: Res<MatchRules>,
+    ) {
+        let Some(session) = pending.session.take() else {
+            return;
+        };
+        let track = match_track(&rules, entropy.as_mut());
+        spawn_arena(&mut commands, &mut meshes, &mut materials, &track);
+        let bounds_max = camera_bounds(&track);
+        let cars = spawn_grid(&mut commands, &track, entropy.as_mut(), &rules, 2);
+        for (handle, car) in cars.into_iter().enumerate() {
+            let mut car = commands.entity(car);
+            car.insert(RollbackDriver(handle));
+            if handle == pending.local_player {
+                car.insert((
+                    Player,
+                    CarName::new("Me (You)"),
+                    CameraTracker::rect(-bounds_max, bounds_max),
+                    CheckpointHighlightTracker,
+                ));
+            } else {
+                car.insert(CarName::new("Them"));
+            }
+        }
+        // the simulation only starts once both peers have their cars
+        commands.insert_resource(session);
+        commands.remove_resource::<PendingSession>();
+    }
+}
+
+#[derive(Resource)]
+struct PendingSession {
+    session: Option<Session<RollbackConfig>>,
+    local_player: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct LobbyUI;
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct LobbyText;
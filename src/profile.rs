@@ -0,0 +1,248 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use laptag::{BombExploded, BombTagIt, LapTagIt, LapTagSystems, TagEvent};
+use track::{LapComplete, TrackSystems};
+
+use crate::{game_loop::GameOutcome, storage, BestLapRecord, Player};
+
+/// Keeps the player's stats between runs and shows them on a leaderboard screen.
+pub struct ProfilePlugin;
+
+impl Plugin for ProfilePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(storage::load::<Profile>(Profile::SAVE_NAME).unwrap_or_default())
+            // outcomes only come from the game loop, which the editor leaves out
+            .add_event::<GameOutcome>()
+            .add_systems(
+                Update,
+                (
+                    // the bomb is gone once its lap has been handled
+                    Self::count_bomb_eliminations
+                        .after(TrackSystems)
+                        .before(LapTagSystems),
+                    Self::count_stolen_flags.after(LapTagSystems),
+                    Self::record_best_laps,
+                    Self::record_outcomes,
+                    Self::save_profile,
+                    Self::toggle_leaderboard,
+                ),
+            );
+    }
+}
+
+impl ProfilePlugin {
+    fn count_bomb_eliminations(
+        mut laps: EventReader<LapComplete>,
+        mut explosions: EventReader<BombExploded>,
+        players: Query<(Entity, Has<BombTagIt>), With<Player>>,
+        mut profile: ResMut<Profile>,
+        mut last_player: Local<Option<Entity>>,
+    ) {
+        // an exploding bomb may have taken the player's car with it already
+        if let Ok((player, _)) = players.get_single() {
+            *last_player = Some(player);
+        }
+        let laps_with_bomb = laps
+            .read()
+            .filter(|lap| players.get(lap.racer).is_ok_and(|(_, has_bomb)| has_bomb));
+        let explosions = explosions
+            .read()
+            .filter(|explosion| *last_player == Some(explosion.holder));
+        let count = laps_with_bomb.count() + explosions.count();
+        if count > 0 {
+            profile.bomb_eliminations += count as u32;
+        }
+    }
+
+    fn count_stolen_flags(
+        mut tags: EventReader<TagEvent>,
+        players: Query<Has<LapTagIt>, With<Player>>,
+        mut profile: ResMut<Profile>,
+    ) {
+        let count = tags
+            .read()
+            .filter(|tag| players.get(tag.next_it).is_ok_and(|has_flag| has_flag))
+            .count();
+        if count > 0 {
+            profile.flags_stolen += count as u32;
+        }
+    }
+
+    fn record_best_laps(record: Res<BestLapRecord>, mut profile: ResMut<Profile>) {
+        if !record.is_changed() {
+            return;
+        }
+        let (Some(track), Some(best)) = (record.track(), record.best.as_ref()) else {
+            return;
+        };
+        let is_better = profile
+            .best_laps
+            .get(track)
+            .map_or(true, |time| best.time < *time);
+        if is_better {
+            profile.best_laps.insert(track.to_string(), best.time);
+        }
+    }
+
+    fn record_outcomes(mut outcomes: EventReader<GameOutcome>, mut profile: ResMut<Profile>) {
+        for outcome in outcomes.read() {
+            profile.matches_played += 1;
+            if outcome.won {
+                profile.wins += 1;
+            }
+            let entry = LeaderboardEntry {
+                score: outcome.score,
+                won: outcome.won,
+                match_number: profile.matches_played,
+            };
+            profile.add_to_leaderboard(entry);
+        }
+    }
+
+    fn save_profile(profile: Res<Profile>) {
+        if profile.is_changed() && !profile.is_added() {
+            storage::save(Profile::SAVE_NAME, &*profile);
+        }
+    }
+
+    fn toggle_leaderboard(
+        mut commands: Commands,
+        inputs: Res<ButtonInput<KeyCode>>,
+        profile: Res<Profile>,
+        screens: Query<Entity, With<LeaderboardScreen>>,
+    ) {
+        if !inputs.just_pressed(KeyCode::KeyL) {
+            return;
+        }
+        if !screens.is_empty() {
+            for screen in &screens {
+                commands.entity(screen).despawn_recursive();
+            }
+            return;
+        }
+        let line = |text: String, font_size: f32| {
+            TextBundle::from_section(
+                text,
+                TextStyle {
+                    font_size,
+                    color: Color::WHITE,
+                    ..Default::default()
+                },
+            )
+        };
+        commands
+            .spawn((
+                Name::new("Leaderboard"),
+                LeaderboardScreen,
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ))
+            .with_children(|builder| {
+                builder
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            row_gap: Val::Px(6.),
+                            padding: UiRect::all(Val::Px(20.)),
+                            border: UiRect::all(Val::Px(4.)),
+                            ..Default::default()
+                        },
+                        border_color: Color::BLACK.into(),
+                        background_color: Color::srgba(0.2, 0.2, 0.2, 0.9).into(),
+                        ..Default::default()
+                    })
+                    .with_children(|builder| {
+                        builder.spawn(line("Leaderboard".to_string(), 48.));
+                        builder.spawn(line(
+                            format!(
+                                "Played {}   Won {}   Flags stolen {}   Bombed out {}",
+                                profile.matches_played,
+                                profile.wins,
+                                profile.flags_stolen,
+                                profile.bomb_eliminations
+                            ),
+                            24.,
+                        ));
+                        builder.spawn(line("Best matches".to_string(), 32.));
+                        if profile.leaderboard.is_empty() {
+                            builder.spawn(line("No matches finished yet".to_string(), 20.));
+                        }
+                        for (rank, entry) in profile.leaderboard.iter().enumerate() {
+                            builder.spawn(line(
+                                format!(
+                                    "{}. {} points{}",
+                                    rank + 1,
+                                    entry.score,
+                                    if entry.won { " (won)" } else { "" }
+                                ),
+                                20.,
+                            ));
+                        }
+                        builder.spawn(line("Best laps".to_string(), 32.));
+                        if profile.best_laps.is_empty() {
+                            builder.spawn(line("No time trial laps yet".to_string(), 20.));
+                        }
+                        for (track, time) in &profile.best_laps {
+                            builder
+                                .spawn(line(format!("{track}: {:.3}s", time.as_secs_f32()), 20.));
+                        }
+                        builder.spawn(line("Press L to close".to_string(), 16.));
+                    });
+            });
+    }
+}
+
+/// Everything remembered about the player between runs.
+#[derive(Clone, Debug, Default)]
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub matches_played: u32,
+    pub wins: u32,
+    pub flags_stolen: u32,
+    /// Times the player was knocked out while holding the bomb.
+    pub bomb_eliminations: u32,
+    /// The fastest time trial lap on each track, by [`Track::key`](track::Track::key).
+    pub best_laps: BTreeMap<String, Duration>,
+    /// The player's best finished matches, highest score first.
+    pub leaderboard: Vec<LeaderboardEntry>,
+}
+
+impl Profile {
+    const SAVE_NAME: &'static str = "profile";
+    const LEADERBOARD_SIZE: usize = 10;
+
+    fn add_to_leaderboard(&mut self, entry: LeaderboardEntry) {
+        self.leaderboard.push(entry);
+        // wins break ties, then the most recent match
+        self.leaderboard.sort_by(|a, b| {
+            (b.score, b.won, b.match_number).cmp(&(a.score, a.won, a.match_number))
+        });
+        self.leaderboard.truncate(Self::LEADERBOARD_SIZE);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub score: u32,
+    pub won: bool,
+    /// Which of the player's matches this was, counting from 1.
+    pub match_number: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct LeaderboardScreen;
@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use avian2d::prelude::{LayerMask, SpatialQuery, SpatialQueryFilter};
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+
+use car::{Car, CarPhysicsBundle, CarResetRequested, DrivingSystems, Ghost, Parked, TeleportCar};
+use laptag::{Eliminated, LapTagSystems, RespawnRequested, Score, TagImmunity};
+use track::{Checkpoint, LastCheckpoint, Track, TrackChunk};
+
+use crate::{spawn_pose, MatchRules};
+
+/// Sits eliminated cars out for a while and then brings them back behind the pack.
+///
+/// Also puts stuck cars back on the track at the last checkpoint they reached.
+pub struct RespawnPlugin {
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for RespawnPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Update.intern(),
+        }
+    }
+}
+
+impl Plugin for RespawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            self.schedule,
+            (
+                (Self::sit_out_eliminated_cars, Self::respawn_cars)
+                    .chain()
+                    .after(LapTagSystems),
+                Self::reset_cars.after(DrivingSystems),
+            )
+                .in_set(RespawnSystems),
+        );
+        app.register_type::<RespawnTimer>();
+    }
+}
+
+impl RespawnPlugin {
+    /// How long a car reset for being stuck can drive through other cars.
+    const RESET_GHOST_DURATION: Duration = Duration::from_secs(2);
+
+    fn reset_cars(
+        mut commands: Commands,
+        mut resets: EventReader<CarResetRequested>,
+        cars: Query<(&Transform, Option<&LastCheckpoint>), (With<Car>, Without<Parked>)>,
+        tracks: Query<&Track>,
+    ) {
+        let Ok(track) = tracks.get_single() else {
+            return;
+        };
+        let chunks = track.chunks().collect::<Vec<_>>();
+        for CarResetRequested { car, is_stuck } in resets.read() {
+            let Ok((transform, last_checkpoint)) = cars.get(*car) else {
+                continue;
+            };
+            let chunk_index = last_checkpoint
+                .and_then(|last_checkpoint| **last_checkpoint)
+                .unwrap_or_else(|| nearest_chunk(&chunks, transform.translation.xy()));
+            let Some(chunk) = chunks.get(chunk_index) else {
+                continue;
+            };
+            let (position, angle) = spawn_pose(chunk, 0.5);
+            let mut car = commands.entity(*car);
+            car.add(TeleportCar { position, angle });
+            // a car that asked to be reset could otherwise ghost through whoever is about to tag
+            // it, so only stuck cars get to pull away through traffic
+            if *is_stuck {
+                car.insert(Ghost::new(Self::RESET_GHOST_DURATION));
+            }
+        }
+    }
+
+    fn sit_out_eliminated_cars(
+        mut commands: Commands,
+        mut eliminated_cars: Query<(Entity, Option<&mut Score>), Added<Eliminated>>,
+        rules: Res<MatchRules>,
+    ) {
+        let respawn = rules.respawn.clone().unwrap_or_default();
+        for (car, score) in &mut eliminated_cars {
+            if let Some(mut score) = score {
+                **score = score.saturating_sub(respawn.score_penalty);
+            }
+            commands.entity(car).insert((
+                Parked,
+                RespawnTimer(Timer::new(respawn.delay, TimerMode::Once)),
+            ));
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn respawn_cars(
+        mut commands: Commands,
+        mut waiting_cars: Query<(Entity, &mut RespawnTimer)>,
+        requests: Query<Entity, With<RespawnRequested>>,
+        active_cars: Query<&Transform, (With<Car>, Without<Eliminated>)>,
+        tracks: Query<&Track>,
+        spatial_query: SpatialQuery,
+        time: Res<Time>,
+    ) {
+        let Ok(track) = tracks.get_single() else {
+            return;
+        };
+        let mut ready_cars = requests.iter().collect::<Vec<_>>();
+        for (car, mut timer) in &mut waiting_cars {
+            if timer.0.tick(time.delta()).finished() {
+                commands
+                    .entity(car)
+                    .remove::<(RespawnTimer, Eliminated, Parked)>();
+                ready_cars.push(car);
+            }
+        }
+        if ready_cars.is_empty() {
+            return;
+        }
+
+        let occupied = active_cars
+            .iter()
+            .map(|transform| transform.translation.xy())
+            .collect::<Vec<_>>();
+        for car in ready_cars {
+            let (position, angle) = safe_pose(track, &occupied, &spatial_query);
+            commands
+                .entity(car)
+                .remove::<RespawnRequested>()
+                .insert(TagImmunity::default())
+                .add(TeleportCar { position, angle });
+        }
+    }
+}
+
+// find the longest stretch of track without any cars on it and pick a clear spot
+// near its end, just behind the last car of the pack
+fn safe_pose(track: &Track, occupied: &[Vec2], spatial_query: &SpatialQuery) -> (Vec2, f32) {
+    let chunks = track.chunks().collect::<Vec<_>>();
+    let mut is_occupied = vec![false; chunks.len()];
+    for position in occupied {
+        is_occupied[nearest_chunk(&chunks, *position)] = true;
+    }
+
+    let chunk_index = match is_occupied.iter().position(|occupied| *occupied) {
+        None => 0,
+        Some(first_occupied) => {
+            // walk the ring once, starting just after an occupied chunk
+            let mut best_gap = (first_occupied, 0);
+            let mut gap_start = None;
+            for step in 1..=chunks.len() {
+                let index = (first_occupied + step) % chunks.len();
+                if is_occupied[index] {
+                    if let Some(start) = gap_start.take() {
+                        let length = (index + chunks.len() - start) % chunks.len();
+                        if length > best_gap.1 {
+                            best_gap = (start, length);
+                        }
+                    }
+                } else if gap_start.is_none() {
+                    gap_start = Some(index);
+                }
+            }
+            let (start, length) = best_gap;
+            let margin = (length / 2).min(2);
+            (start + length.saturating_sub(1 + margin)) % chunks.len()
+        }
+    };
+
+    let chunk = &chunks[chunk_index];
+    // checkpoints sit on every chunk, so leave them out when looking for obstacles
+    let filter = SpatialQueryFilter::from_mask(LayerMask::ALL & !Checkpoint::COLLISION_LAYER);
+    [0.5, 0.2, 0.8]
+        .into_iter()
+        .map(|offset| spawn_pose(chunk, offset))
+        .find(|(position, angle)| {
+            spatial_query
+                .shape_intersections(
+                    &CarPhysicsBundle::collider(),
+                    *position,
+                    *angle,
+                    filter.clone(),
+                )
+                .is_empty()
+        })
+        .unwrap_or_else(|| spawn_pose(chunk, 0.5))
+}
+
+fn nearest_chunk(chunks: &[TrackChunk], position: Vec2) -> usize {
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| (index, chunk.center().distance(position)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(index, _)| index)
+        .unwrap_or_default()
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[derive(SystemSet)]
+pub struct RespawnSystems;
+
+#[derive(Clone, Debug)]
+#[derive(Component, Reflect)]
+pub(crate) struct RespawnTimer(Timer);
@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bot_controller::BotSkill;
+use car::CarClass;
+use laptag::BombFuseRules;
+
+use crate::GridConfig;
+
+/// Settings for how a match is set up and played.
+#[derive(Clone, Debug, Default)]
+#[derive(Resource, Reflect)]
+pub struct MatchRules {
+    pub mode: MatchMode,
+    /// Splits the cars into this many teams, or plays free-for-all when `None`.
+    pub teams: Option<usize>,
+    /// Brings eliminated cars back after a short wait, or removes them for good when `None`.
+    pub respawn: Option<RespawnRules>,
+    /// Gives the bomb a fuse that blows up whoever is holding it when it runs out, or lets it
+    /// be held for as long as it takes when `None`.
+    pub bomb_fuse: Option<BombFuseRules>,
+    /// Whether a wrecked car drops its tags to the nearest car.
+    pub wrecks_drop_tags: bool,
+    /// What kind of car the player drives.
+    pub player_class: CarClass,
+    /// Plays each match on a freshly generated course of this difficulty, from 0 to 1, or on
+    /// the oval when `None`.
+    pub track_difficulty: Option<f32>,
+    pub grid: GridConfig,
+    /// How well bots drive, each with a personality of its own on top.
+    pub bot_skill: BotSkill,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Reflect)]
+pub enum MatchMode {
+    /// Race bots for flags while dodging the bomb.
+    #[default]
+    Tag,
+    /// Drive the track alone against the clock and a ghost of your best lap.
+    TimeTrial,
+}
+
+#[derive(Clone, Debug)]
+#[derive(Reflect)]
+pub struct RespawnRules {
+    /// How many times each car can come back before it is out of the match.
+    pub lives: u32,
+    /// How long an eliminated car sits out.
+    pub delay: Duration,
+    /// Points taken from a car each time it is eliminated.
+    pub score_penalty: u32,
+}
+
+impl Default for RespawnRules {
+    fn default() -> Self {
+        Self {
+            lives: 3,
+            delay: Duration::from_secs(3),
+            score_penalty: 1,
+        }
+    }
+}
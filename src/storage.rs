@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Reads a file saved with [`save`], or `None` if there isn't one yet.
+pub fn load<T: DeserializeOwned>(name: &str) -> Option<T> {
+    let text = read(name)?;
+    match ron::from_str(&text) {
+        Ok(value) => Some(value),
+        Err(error) => {
+            warn!("Ignoring unreadable save file {name}: {error}");
+            None
+        }
+    }
+}
+
+/// Saves a value under `name` in the user's data folder, or in `localStorage` in the browser.
+pub fn save<T: Serialize>(name: &str, value: &T) {
+    let text = match ron::to_string(value) {
+        Ok(text) => text,
+        Err(error) => {
+            error!("Failed to serialize save file {name}: {error}");
+            return;
+        }
+    };
+    write(name, &text);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn path(name: &str) -> Option<std::path::PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("tagcar").join(format!("{name}.ron")))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(name: &str) -> Option<String> {
+    std::fs::read_to_string(path(name)?).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(name: &str, text: &str) {
+    let Some(path) = path(name) else {
+        warn!("No data folder to save {name} in");
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, text));
+    if let Err(error) = result {
+        error!("Failed to write {}: {error}", path.display());
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(name: &str) -> Option<String> {
+    local_storage()?
+        .get_item(&format!("tagcar.{name}"))
+        .ok()
+        .flatten()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(name: &str, text: &str) {
+    let Some(storage) = local_storage() else {
+        warn!("No localStorage to save {name} in");
+        return;
+    };
+    if storage.set_item(&format!("tagcar.{name}"), text).is_err() {
+        error!("Failed to write {name} to localStorage");
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    f32::consts::{PI, TAU},
+    time::Duration,
+};
+
+use avian2d::prelude::Physics;
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use serde::{Deserialize, Serialize};
+
+use camera::CameraTracker;
+use car::{Car, CarBlueprint, CarGraphicsBundle, CarResetRequested};
+use controller::Controller;
+use scoreboard::CarName;
+use track::{CheckpointHighlightTracker, CheckpointTracker, LapComplete, LastCheckpoint, Track};
+
+use crate::{camera_bounds, spawn_pose, storage, MatchRules, Player};
+
+/// Times the player's laps when playing [`MatchMode::TimeTrial`](crate::MatchMode::TimeTrial)
+/// and races them against a ghost of their best lap.
+pub struct TimeTrialPlugin;
+
+impl Plugin for TimeTrialPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BestLapRecord>().add_systems(
+            Update,
+            (
+                Self::load_best_lap,
+                Self::tick_lap_timers,
+                Self::void_reset_laps,
+                Self::complete_laps,
+                Self::replay_lap_ghost,
+                Self::spawn_lap_display,
+                Self::update_lap_display,
+            )
+                .chain()
+                .after(track::TrackSystems)
+                .after(car::DrivingSystems),
+        );
+        app.register_type::<LapTimer>();
+    }
+}
+
+impl TimeTrialPlugin {
+    /// How often the car's position is recorded for the ghost.
+    const FRAME_INTERVAL: f32 = 1. / 30.;
+    const GHOST_ALPHA: f32 = 0.35;
+
+    fn load_best_lap(
+        new_timers: Query<(), Added<LapTimer>>,
+        tracks: Query<&Track>,
+        mut record: ResMut<BestLapRecord>,
+    ) {
+        if new_timers.is_empty() {
+            return;
+        }
+        let Ok(track) = tracks.get_single() else {
+            return;
+        };
+        let key = track.key();
+        if record.track.as_ref() != Some(&key) {
+            record.best = storage::load(&BestLapRecord::save_name(&key));
+            record.track = Some(key);
+        }
+    }
+
+    fn tick_lap_timers(mut timers: Query<(&mut LapTimer, &Transform)>, time: Res<Time<Physics>>) {
+        for (mut timer, transform) in &mut timers {
+            timer.elapsed += time.delta();
+            let elapsed = timer.elapsed.as_secs_f32();
+            let is_due = timer
+                .frames
+                .last()
+                .map_or(true, |frame| elapsed - frame.time >= Self::FRAME_INTERVAL);
+            if is_due {
+                timer.frames.push(GhostFrame {
+                    time: elapsed,
+                    x: transform.translation.x,
+                    y: transform.translation.y,
+                    angle: transform.rotation.to_euler(EulerRot::ZYX).0,
+                });
+            }
+        }
+    }
+
+    // putting the car back on the track is a shortcut, so that lap can't be a record
+    fn void_reset_laps(
+        mut resets: EventReader<CarResetRequested>,
+        mut timers: Query<&mut LapTimer>,
+    ) {
+        for reset in resets.read() {
+            if let Ok(mut timer) = timers.get_mut(reset.car) {
+                timer.is_void = true;
+            }
+        }
+    }
+
+    fn complete_laps(
+        mut completed_laps: EventReader<LapComplete>,
+        mut timers: Query<&mut LapTimer>,
+        mut record: ResMut<BestLapRecord>,
+    ) {
+        for lap in completed_laps.read() {
+            let Ok(mut timer) = timers.get_mut(lap.racer) else {
+                continue;
+            };
+            let finished = std::mem::take(&mut *timer);
+            timer.last = Some(finished.elapsed);
+            if finished.is_void {
+                continue;
+            }
+            let is_record = record
+                .best
+                .as_ref()
+                .map_or(true, |best| finished.elapsed < best.time);
+            if !is_record {
+                continue;
+            }
+            let best = BestLap {
+                time: finished.elapsed,
+                frames: finished.frames,
+            };
+            if let Some(track) = &record.track {
+                storage::save(&BestLapRecord::save_name(track), &best);
+            }
+            record.best = Some(best);
+        }
+    }
+
+    fn replay_lap_ghost(
+        mut commands: Commands,
+        record: Res<BestLapRecord>,
+        timers: Query<&LapTimer, With<Player>>,
+        mut ghosts: Query<(Entity, &mut Transform, &mut Visibility), With<LapGhost>>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        let (Ok(timer), Some(best)) = (timers.get_single(), record.best.as_ref()) else {
+            for (ghost, ..) in &ghosts {
+                commands.entity(ghost).despawn_recursive();
+            }
+            return;
+        };
+        let Ok((_, mut transform, mut visibility)) = ghosts.get_single_mut() else {
+            commands.spawn((
+                Name::new("Lap Ghost"),
+                LapGhost,
+                CarGraphicsBundle::new(MaterialMesh2dBundle {
+                    mesh: meshes.add(Rectangle::new(Car::LENGTH, Car::WIDTH)).into(),
+                    material: materials.add(Color::srgba(0.9, 0.9, 1., Self::GHOST_ALPHA)),
+                    transform: Transform::from_xyz(0., 0., Car::Z_INDEX),
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                }),
+            ));
+            return;
+        };
+        match best.pose_at(timer.elapsed.as_secs_f32()) {
+            Some((position, angle)) => {
+                transform.translation = position.extend(transform.translation.z);
+                transform.rotation = Quat::from_rotation_z(angle);
+                *visibility = Visibility::Inherited;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+
+    fn spawn_lap_display(
+        mut commands: Commands,
+        new_timers: Query<(), (Added<LapTimer>, With<Player>)>,
+        displays: Query<(), With<LapTimeDisplay>>,
+    ) {
+        if new_timers.is_empty() || !displays.is_empty() {
+            return;
+        }
+        commands
+            .spawn((
+                Name::new("Lap Time Display"),
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        width: Val::Percent(100.),
+                        top: Val::Px(10.),
+                        justify_content: JustifyContent::Center,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ))
+            .with_children(|builder| {
+                builder.spawn((
+                    LapTimeDisplay,
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 32.,
+                            color: Color::WHITE,
+                            ..Default::default()
+                        },
+                    ),
+                ));
+            });
+    }
+
+    fn update_lap_display(
+        timers: Query<&LapTimer, With<Player>>,
+        record: Res<BestLapRecord>,
+        mut displays: Query<&mut Text, With<LapTimeDisplay>>,
+    ) {
+        let Ok(timer) = timers.get_single() else {
+            return;
+        };
+        let show = |time: Option<Duration>| time.map_or("-:--.---".to_string(), format_lap_time);
+        for mut text in &mut displays {
+            text.sections[0].value = format!(
+                "LAP {}{}   LAST {}   BEST {}",
+                format_lap_time(timer.elapsed),
+                if timer.is_void { " (reset)" } else { "" },
+                show(timer.last),
+                show(record.best.as_ref().map(|best| best.time)),
+            );
+        }
+    }
+}
+
+pub(crate) fn spawn_time_trial_car(commands: &mut Commands, track: &Track, rules: &MatchRules) {
+    let chunk = track
+        .chunks()
+        .next()
+        .expect("Cars to spawn on known checkpoints");
+    let (position, angle) = spawn_pose(&chunk, 0.5);
+    let bounds_max = camera_bounds(track);
+    commands.spawn((
+        CarBlueprint::new(position, angle, true, rules.player_class),
+        Player,
+        CarName::new("Me (You)"),
+        Controller::ArrowKeys,
+        CameraTracker::rect(-bounds_max, bounds_max),
+        CheckpointHighlightTracker,
+        CheckpointTracker::default(),
+        LastCheckpoint::default(),
+        LapTimer::default(),
+    ));
+}
+
+fn format_lap_time(time: Duration) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Times the lap a car is on and records where it has been.
+#[derive(Clone, Debug, Default)]
+#[derive(Component, Reflect)]
+pub struct LapTimer {
+    elapsed: Duration,
+    last: Option<Duration>,
+    /// Whether something happened this lap that keeps it from being a record.
+    is_void: bool,
+    #[reflect(ignore)]
+    frames: Vec<GhostFrame>,
+}
+
+impl LapTimer {
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn last(&self) -> Option<Duration> {
+        self.last
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize)]
+struct GhostFrame {
+    time: f32,
+    x: f32,
+    y: f32,
+    angle: f32,
+}
+
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct BestLap {
+    pub time: Duration,
+    frames: Vec<GhostFrame>,
+}
+
+impl BestLap {
+    /// Where the car was `time` seconds into the lap, or `None` once the lap is over.
+    fn pose_at(&self, time: f32) -> Option<(Vec2, f32)> {
+        let next = self.frames.iter().position(|frame| frame.time >= time)?;
+        let to = self.frames[next];
+        let from = self.frames[next.saturating_sub(1)];
+        let span = to.time - from.time;
+        let t = if span > f32::EPSILON {
+            (time - from.time) / span
+        } else {
+            1.
+        };
+        let position = Vec2::new(from.x, from.y).lerp(Vec2::new(to.x, to.y), t);
+        // turn the short way round
+        let turn = (to.angle - from.angle + PI).rem_euclid(TAU) - PI;
+        let angle = from.angle + turn * t;
+        Some((position, angle))
+    }
+}
+
+/// The best lap on the current track, loaded from and saved to disk.
+#[derive(Debug, Default)]
+#[derive(Resource)]
+pub struct BestLapRecord {
+    track: Option<String>,
+    pub best: Option<BestLap>,
+}
+
+impl BestLapRecord {
+    /// The [`Track::key`] of the track the record is for.
+    pub fn track(&self) -> Option<&str> {
+        self.track.as_deref()
+    }
+
+    fn save_name(track: &str) -> String {
+        format!("time-trial-{track}")
+    }
+}
+
+/// The translucent car that replays the best lap.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct LapGhost;
+
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Component)]
+struct LapTimeDisplay;